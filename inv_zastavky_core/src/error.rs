@@ -0,0 +1,107 @@
+use crate::model::station::StationError;
+use std::fmt::{Display, Formatter};
+
+/// Error of a core operation grouped by how the caller should react to it.
+///
+/// Every variant other than storage carries a stable machine readable code, e.g.
+/// `station_not_found`, and a human readable message.
+#[derive(Debug)]
+pub enum Error {
+    /// Referenced entity does not exist.
+    NotFound { code: &'static str, message: String },
+    /// Operation conflicts with the current state of the data.
+    Conflict { code: &'static str, message: String },
+    /// Input is malformed or out of range.
+    Validation { code: &'static str, message: String },
+    /// Credentials are missing or wrong.
+    Unauthorized { code: &'static str, message: String },
+    /// Database failed.
+    Storage(sqlx::Error),
+}
+
+impl Error {
+    pub fn not_found(code: &'static str, message: impl Into<String>) -> Self {
+        Error::NotFound {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn conflict(code: &'static str, message: impl Into<String>) -> Self {
+        Error::Conflict {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn validation(code: &'static str, message: impl Into<String>) -> Self {
+        Error::Validation {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn unauthorized(code: &'static str, message: impl Into<String>) -> Self {
+        Error::Unauthorized {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// Stable code identifying the error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::NotFound { code, .. }
+            | Error::Conflict { code, .. }
+            | Error::Validation { code, .. }
+            | Error::Unauthorized { code, .. } => code,
+            Error::Storage(_) => "storage",
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotFound { message, .. }
+            | Error::Conflict { message, .. }
+            | Error::Validation { message, .. }
+            | Error::Unauthorized { message, .. } => write!(f, "{}", message),
+            Error::Storage(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<sqlx::Error> for Error {
+    fn from(err: sqlx::Error) -> Self {
+        Error::Storage(err)
+    }
+}
+
+impl From<StationError> for Error {
+    fn from(err: StationError) -> Self {
+        let message = err.to_string();
+        match err {
+            StationError::Storage(err) => Error::Storage(err),
+            StationError::UnknownStation(_) => Error::not_found("station_not_found", message),
+            StationError::UnknownName { .. } => Error::not_found("station_name_not_found", message),
+            StationError::DuplicateName { .. } => {
+                Error::conflict("duplicate_station_name", message)
+            }
+            StationError::LastName(_) => Error::conflict("last_station_name", message),
+            StationError::EmptyName => Error::validation("empty_station_name", message),
+            StationError::SelfMerge(_) => Error::validation("self_merge", message),
+            StationError::Assigned { .. } => Error::conflict("station_assigned", message),
+            StationError::EmptySplit(_) => Error::validation("empty_split", message),
+            StationError::UnassignedPosition { .. } => {
+                Error::validation("position_not_assigned", message)
+            }
+            StationError::AmbiguousPosition { .. } => {
+                Error::validation("ambiguous_split_position", message)
+            }
+            StationError::InvalidPosition { .. } => Error::validation("invalid_position", message),
+        }
+    }
+}
@@ -1,3 +1,9 @@
+pub mod error;
+pub mod migrations;
 pub mod model;
+pub mod names;
+pub mod search;
 pub mod suggest;
+#[cfg(test)]
+mod testing;
 pub mod utils;
@@ -1,8 +1,3 @@
-use sqlx::{Pool, Sqlite};
-
-pub async fn ensure_tables(db_pool: &Pool<Sqlite>) -> anyhow::Result<()> {
-    sqlx::query(
-        "
 create table if not exists sl_chains(
     chain_hash TEXT,
     station_name TEXT,
@@ -14,6 +9,22 @@ create table if not exists sl_base_stations(
     lon float,
     station_name TEXT
 );
+create table if not exists sl_netex_stations(
+    netex_id TEXT,
+    parent_id TEXT,
+    station_name TEXT,
+    lat float,
+    lon float,
+    UNIQUE(netex_id)
+);
+create table if not exists sl_gtfs_stations(
+    gtfs_id TEXT,
+    parent_id TEXT,
+    station_name TEXT,
+    lat float,
+    lon float,
+    UNIQUE(gtfs_id)
+);
 create table if not exists sl_base_cities(
     city_name text,
     lat float,
@@ -37,13 +48,9 @@ create table if not exists el_station_pos(
     lon float
 );
 CREATE INDEX IF NOT EXISTS sl_chains_idx1 ON sl_chains (chain_hash, station_name);
+CREATE INDEX IF NOT EXISTS sl_netex_stations_idx1 ON sl_netex_stations (lat, lon);
+CREATE INDEX IF NOT EXISTS sl_gtfs_stations_idx1 ON sl_gtfs_stations (lat, lon);
 CREATE INDEX IF NOT EXISTS sl_base_cities_idx1 ON sl_base_cities (city_name);
 CREATE INDEX IF NOT EXISTS hl_stations_idx1 ON hl_stations (chain_hash, station_name);
 CREATE INDEX IF NOT EXISTS el_station_pos_idx1 ON el_station_pos (stop_id);
 CREATE INDEX IF NOT EXISTS el_station_names_idx1 ON el_station_names (stop_id);
-",
-    )
-    .execute(db_pool)
-    .await?;
-    Ok(())
-}
@@ -0,0 +1,23 @@
+create table el_stop_ids(
+    stop_id integer PRIMARY KEY AUTOINCREMENT,
+    legacy_stop_id integer UNIQUE
+);
+-- renumber randomly generated ids in order of creation, keeping the old ones in legacy_stop_id;
+-- ids already taken are filtered out, ignored inserts would still use up AUTOINCREMENT values
+INSERT INTO el_stop_ids (legacy_stop_id)
+    SELECT stop_id FROM el_station_pos GROUP BY stop_id ORDER BY min(rowid);
+INSERT INTO el_stop_ids (legacy_stop_id)
+    SELECT stop_id FROM el_station_names
+    WHERE stop_id NOT IN (SELECT legacy_stop_id FROM el_stop_ids)
+    GROUP BY stop_id ORDER BY min(rowid);
+INSERT INTO el_stop_ids (legacy_stop_id)
+    SELECT stop_id FROM hl_stations
+    WHERE stop_id IS NOT NULL AND stop_id NOT IN (SELECT legacy_stop_id FROM el_stop_ids)
+    GROUP BY stop_id ORDER BY min(rowid);
+-- move ids out of the range of the old ones first, so the UNIQUE constraints hold during the update
+UPDATE el_station_pos SET stop_id = (SELECT stop_id FROM el_stop_ids WHERE legacy_stop_id = el_station_pos.stop_id) + 4294967296;
+UPDATE el_station_names SET stop_id = (SELECT stop_id FROM el_stop_ids WHERE legacy_stop_id = el_station_names.stop_id) + 4294967296;
+UPDATE hl_stations SET stop_id = (SELECT stop_id FROM el_stop_ids WHERE legacy_stop_id = hl_stations.stop_id) + 4294967296 WHERE stop_id IS NOT NULL;
+UPDATE el_station_pos SET stop_id = stop_id - 4294967296;
+UPDATE el_station_names SET stop_id = stop_id - 4294967296;
+UPDATE hl_stations SET stop_id = stop_id - 4294967296 WHERE stop_id IS NOT NULL;
@@ -0,0 +1,21 @@
+create table users(
+    user_id integer PRIMARY KEY AUTOINCREMENT,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    created_at TEXT DEFAULT CURRENT_TIMESTAMP
+);
+-- only hashes of API tokens are stored, the token itself is shown once when created
+create table user_tokens(
+    token_hash TEXT PRIMARY KEY,
+    user_id integer NOT NULL REFERENCES users (user_id),
+    name TEXT,
+    created_at TEXT DEFAULT CURRENT_TIMESTAMP
+);
+-- who made the last change of a row, NULL for rows from before users existed
+ALTER TABLE el_station_pos ADD COLUMN edited_by integer REFERENCES users (user_id);
+ALTER TABLE el_station_pos ADD COLUMN edited_at TEXT;
+ALTER TABLE el_station_names ADD COLUMN edited_by integer REFERENCES users (user_id);
+ALTER TABLE el_station_names ADD COLUMN edited_at TEXT;
+ALTER TABLE hl_stations ADD COLUMN edited_by integer REFERENCES users (user_id);
+ALTER TABLE hl_stations ADD COLUMN edited_at TEXT;
+CREATE INDEX IF NOT EXISTS user_tokens_idx1 ON user_tokens (user_id);
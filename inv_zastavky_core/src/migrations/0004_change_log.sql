@@ -0,0 +1,18 @@
+-- append-only log of edits, rows are never updated except for marking them as reverted
+create table el_changes(
+    change_id integer PRIMARY KEY AUTOINCREMENT,
+    changed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    user_id integer REFERENCES users (user_id),
+    session_id TEXT,
+    entity TEXT NOT NULL,
+    action TEXT NOT NULL,
+    stop_id integer,
+    chain_hash TEXT,
+    pos integer,
+    -- JSON snapshots of the station or the assignment, NULL if it did not exist
+    before TEXT,
+    after TEXT,
+    reverted_by integer REFERENCES el_changes (change_id)
+);
+CREATE INDEX IF NOT EXISTS el_changes_idx1 ON el_changes (stop_id);
+CREATE INDEX IF NOT EXISTS el_changes_idx2 ON el_changes (session_id);
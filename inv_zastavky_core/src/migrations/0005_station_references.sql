@@ -0,0 +1,36 @@
+-- hl_stations.stop_id must point to a station in el_station_pos, existing dangling rows are left
+-- for `manager repair`
+CREATE TRIGGER hl_stations_stop_id_insert BEFORE INSERT ON hl_stations
+WHEN NEW.stop_id IS NOT NULL
+    AND NOT EXISTS (SELECT 1 FROM el_station_pos WHERE stop_id = NEW.stop_id)
+BEGIN
+    SELECT RAISE(ABORT, 'hl_stations.stop_id references missing station');
+END;
+CREATE TRIGGER hl_stations_stop_id_update BEFORE UPDATE OF stop_id ON hl_stations
+WHEN NEW.stop_id IS NOT NULL
+    AND NOT EXISTS (SELECT 1 FROM el_station_pos WHERE stop_id = NEW.stop_id)
+BEGIN
+    SELECT RAISE(ABORT, 'hl_stations.stop_id references missing station');
+END;
+-- stations can not disappear from under their chain positions
+CREATE TRIGGER el_station_pos_delete BEFORE DELETE ON el_station_pos
+WHEN EXISTS (SELECT 1 FROM hl_stations WHERE stop_id = OLD.stop_id)
+BEGIN
+    SELECT RAISE(ABORT, 'station is assigned to chain positions');
+END;
+CREATE TRIGGER el_station_pos_stop_id_update BEFORE UPDATE OF stop_id ON el_station_pos
+WHEN OLD.stop_id IS NOT NEW.stop_id
+    AND EXISTS (SELECT 1 FROM hl_stations WHERE stop_id = OLD.stop_id)
+BEGIN
+    SELECT RAISE(ABORT, 'station is assigned to chain positions');
+END;
+CREATE TRIGGER el_station_names_delete BEFORE DELETE ON el_station_names
+WHEN EXISTS (SELECT 1 FROM hl_stations WHERE stop_id = OLD.stop_id)
+    AND NOT EXISTS (
+        SELECT 1 FROM el_station_names
+        WHERE stop_id = OLD.stop_id AND station_name IS NOT OLD.station_name
+    )
+BEGIN
+    SELECT RAISE(ABORT, 'station is assigned to chain positions');
+END;
+CREATE INDEX IF NOT EXISTS hl_stations_idx2 ON hl_stations (stop_id);
@@ -0,0 +1,122 @@
+-- R*Tree indexes of station positions, kept in sync by triggers. Entries of the source tables
+-- are points, bounds are rounded outwards to 32-bit floats, so queries also filter by lat/lon.
+-- Source tables get an INTEGER PRIMARY KEY first, the indexes refer to it instead of a rowid
+-- which VACUUM could renumber.
+CREATE VIRTUAL TABLE el_station_pos_rtree USING rtree(stop_id, lat_min, lat_max, lon_min, lon_max);
+INSERT INTO el_station_pos_rtree (stop_id, lat_min, lat_max, lon_min, lon_max)
+    SELECT stop_id, lat, lat, lon, lon FROM el_station_pos WHERE stop_id IS NOT NULL AND lat IS NOT NULL AND lon IS NOT NULL;
+CREATE TRIGGER el_station_pos_rtree_insert AFTER INSERT ON el_station_pos
+WHEN NEW.stop_id IS NOT NULL AND NEW.lat IS NOT NULL AND NEW.lon IS NOT NULL
+BEGIN
+    INSERT OR REPLACE INTO el_station_pos_rtree (stop_id, lat_min, lat_max, lon_min, lon_max)
+        VALUES (NEW.stop_id, NEW.lat, NEW.lat, NEW.lon, NEW.lon);
+END;
+CREATE TRIGGER el_station_pos_rtree_update AFTER UPDATE OF stop_id, lat, lon ON el_station_pos
+BEGIN
+    DELETE FROM el_station_pos_rtree WHERE stop_id = OLD.stop_id;
+    INSERT INTO el_station_pos_rtree (stop_id, lat_min, lat_max, lon_min, lon_max)
+        SELECT NEW.stop_id, NEW.lat, NEW.lat, NEW.lon, NEW.lon
+        WHERE NEW.stop_id IS NOT NULL AND NEW.lat IS NOT NULL AND NEW.lon IS NOT NULL;
+END;
+CREATE TRIGGER el_station_pos_rtree_delete AFTER DELETE ON el_station_pos
+BEGIN
+    DELETE FROM el_station_pos_rtree WHERE stop_id = OLD.stop_id;
+END;
+CREATE TABLE sl_base_stations_new(
+    id INTEGER PRIMARY KEY,
+    lat float,
+    lon float,
+    station_name TEXT
+);
+INSERT INTO sl_base_stations_new (id, lat, lon, station_name)
+    SELECT rowid, lat, lon, station_name FROM sl_base_stations ORDER BY rowid;
+DROP TABLE sl_base_stations;
+ALTER TABLE sl_base_stations_new RENAME TO sl_base_stations;
+CREATE VIRTUAL TABLE sl_base_stations_rtree USING rtree(id, lat_min, lat_max, lon_min, lon_max);
+INSERT INTO sl_base_stations_rtree (id, lat_min, lat_max, lon_min, lon_max)
+    SELECT id, lat, lat, lon, lon FROM sl_base_stations WHERE lat IS NOT NULL AND lon IS NOT NULL;
+CREATE TRIGGER sl_base_stations_rtree_insert AFTER INSERT ON sl_base_stations
+WHEN NEW.lat IS NOT NULL AND NEW.lon IS NOT NULL
+BEGIN
+    INSERT OR REPLACE INTO sl_base_stations_rtree (id, lat_min, lat_max, lon_min, lon_max)
+        VALUES (NEW.id, NEW.lat, NEW.lat, NEW.lon, NEW.lon);
+END;
+CREATE TRIGGER sl_base_stations_rtree_update AFTER UPDATE OF id, lat, lon ON sl_base_stations
+BEGIN
+    DELETE FROM sl_base_stations_rtree WHERE id = OLD.id;
+    INSERT INTO sl_base_stations_rtree (id, lat_min, lat_max, lon_min, lon_max)
+        SELECT NEW.id, NEW.lat, NEW.lat, NEW.lon, NEW.lon
+        WHERE NEW.lat IS NOT NULL AND NEW.lon IS NOT NULL;
+END;
+CREATE TRIGGER sl_base_stations_rtree_delete AFTER DELETE ON sl_base_stations
+BEGIN
+    DELETE FROM sl_base_stations_rtree WHERE id = OLD.id;
+END;
+CREATE TABLE sl_netex_stations_new(
+    id INTEGER PRIMARY KEY,
+    netex_id TEXT,
+    parent_id TEXT,
+    station_name TEXT,
+    lat float,
+    lon float,
+    UNIQUE(netex_id)
+);
+INSERT INTO sl_netex_stations_new (id, netex_id, parent_id, station_name, lat, lon)
+    SELECT rowid, netex_id, parent_id, station_name, lat, lon FROM sl_netex_stations ORDER BY rowid;
+DROP TABLE sl_netex_stations;
+ALTER TABLE sl_netex_stations_new RENAME TO sl_netex_stations;
+CREATE INDEX sl_netex_stations_idx1 ON sl_netex_stations (lat, lon);
+CREATE VIRTUAL TABLE sl_netex_stations_rtree USING rtree(id, lat_min, lat_max, lon_min, lon_max);
+INSERT INTO sl_netex_stations_rtree (id, lat_min, lat_max, lon_min, lon_max)
+    SELECT id, lat, lat, lon, lon FROM sl_netex_stations WHERE lat IS NOT NULL AND lon IS NOT NULL;
+CREATE TRIGGER sl_netex_stations_rtree_insert AFTER INSERT ON sl_netex_stations
+WHEN NEW.lat IS NOT NULL AND NEW.lon IS NOT NULL
+BEGIN
+    INSERT OR REPLACE INTO sl_netex_stations_rtree (id, lat_min, lat_max, lon_min, lon_max)
+        VALUES (NEW.id, NEW.lat, NEW.lat, NEW.lon, NEW.lon);
+END;
+CREATE TRIGGER sl_netex_stations_rtree_update AFTER UPDATE OF id, lat, lon ON sl_netex_stations
+BEGIN
+    DELETE FROM sl_netex_stations_rtree WHERE id = OLD.id;
+    INSERT INTO sl_netex_stations_rtree (id, lat_min, lat_max, lon_min, lon_max)
+        SELECT NEW.id, NEW.lat, NEW.lat, NEW.lon, NEW.lon
+        WHERE NEW.lat IS NOT NULL AND NEW.lon IS NOT NULL;
+END;
+CREATE TRIGGER sl_netex_stations_rtree_delete AFTER DELETE ON sl_netex_stations
+BEGIN
+    DELETE FROM sl_netex_stations_rtree WHERE id = OLD.id;
+END;
+CREATE TABLE sl_gtfs_stations_new(
+    id INTEGER PRIMARY KEY,
+    gtfs_id TEXT,
+    parent_id TEXT,
+    station_name TEXT,
+    lat float,
+    lon float,
+    UNIQUE(gtfs_id)
+);
+INSERT INTO sl_gtfs_stations_new (id, gtfs_id, parent_id, station_name, lat, lon)
+    SELECT rowid, gtfs_id, parent_id, station_name, lat, lon FROM sl_gtfs_stations ORDER BY rowid;
+DROP TABLE sl_gtfs_stations;
+ALTER TABLE sl_gtfs_stations_new RENAME TO sl_gtfs_stations;
+CREATE INDEX sl_gtfs_stations_idx1 ON sl_gtfs_stations (lat, lon);
+CREATE VIRTUAL TABLE sl_gtfs_stations_rtree USING rtree(id, lat_min, lat_max, lon_min, lon_max);
+INSERT INTO sl_gtfs_stations_rtree (id, lat_min, lat_max, lon_min, lon_max)
+    SELECT id, lat, lat, lon, lon FROM sl_gtfs_stations WHERE lat IS NOT NULL AND lon IS NOT NULL;
+CREATE TRIGGER sl_gtfs_stations_rtree_insert AFTER INSERT ON sl_gtfs_stations
+WHEN NEW.lat IS NOT NULL AND NEW.lon IS NOT NULL
+BEGIN
+    INSERT OR REPLACE INTO sl_gtfs_stations_rtree (id, lat_min, lat_max, lon_min, lon_max)
+        VALUES (NEW.id, NEW.lat, NEW.lat, NEW.lon, NEW.lon);
+END;
+CREATE TRIGGER sl_gtfs_stations_rtree_update AFTER UPDATE OF id, lat, lon ON sl_gtfs_stations
+BEGIN
+    DELETE FROM sl_gtfs_stations_rtree WHERE id = OLD.id;
+    INSERT INTO sl_gtfs_stations_rtree (id, lat_min, lat_max, lon_min, lon_max)
+        SELECT NEW.id, NEW.lat, NEW.lat, NEW.lon, NEW.lon
+        WHERE NEW.lat IS NOT NULL AND NEW.lon IS NOT NULL;
+END;
+CREATE TRIGGER sl_gtfs_stations_rtree_delete AFTER DELETE ON sl_gtfs_stations
+BEGIN
+    DELETE FROM sl_gtfs_stations_rtree WHERE id = OLD.id;
+END;
@@ -0,0 +1,99 @@
+-- Full-text indexes of names, diacritics are removed so "Ceske Budejovice" finds "České Budějovice".
+-- They use the named tables as external content, which get an INTEGER PRIMARY KEY first, so their
+-- rowids are not renumbered by VACUUM.
+CREATE TABLE el_station_names_new(
+    id INTEGER PRIMARY KEY,
+    stop_id integer,
+    station_name TEXT,
+    edited_by integer REFERENCES users (user_id),
+    edited_at TEXT,
+    UNIQUE(stop_id, station_name)
+);
+INSERT INTO el_station_names_new (id, stop_id, station_name, edited_by, edited_at)
+    SELECT rowid, stop_id, station_name, edited_by, edited_at FROM el_station_names ORDER BY rowid;
+DROP TABLE el_station_names;
+ALTER TABLE el_station_names_new RENAME TO el_station_names;
+CREATE INDEX el_station_names_idx1 ON el_station_names (stop_id);
+CREATE TRIGGER el_station_names_delete BEFORE DELETE ON el_station_names
+WHEN EXISTS (SELECT 1 FROM hl_stations WHERE stop_id = OLD.stop_id)
+    AND NOT EXISTS (
+        SELECT 1 FROM el_station_names
+        WHERE stop_id = OLD.stop_id AND station_name IS NOT OLD.station_name
+    )
+BEGIN
+    SELECT RAISE(ABORT, 'station is assigned to chain positions');
+END;
+CREATE TABLE sl_base_cities_new(
+    id INTEGER PRIMARY KEY,
+    city_name text,
+    lat float,
+    lon float
+);
+INSERT INTO sl_base_cities_new (id, city_name, lat, lon)
+    SELECT rowid, city_name, lat, lon FROM sl_base_cities ORDER BY rowid;
+DROP TABLE sl_base_cities;
+ALTER TABLE sl_base_cities_new RENAME TO sl_base_cities;
+CREATE INDEX sl_base_cities_idx1 ON sl_base_cities (city_name);
+CREATE TABLE sl_chains_new(
+    id INTEGER PRIMARY KEY,
+    chain_hash TEXT,
+    station_name TEXT,
+    pos integer,
+    UNIQUE(chain_hash, station_name, pos)
+);
+INSERT INTO sl_chains_new (id, chain_hash, station_name, pos)
+    SELECT rowid, chain_hash, station_name, pos FROM sl_chains ORDER BY rowid;
+DROP TABLE sl_chains;
+ALTER TABLE sl_chains_new RENAME TO sl_chains;
+CREATE INDEX sl_chains_idx1 ON sl_chains (chain_hash, station_name);
+CREATE VIRTUAL TABLE el_station_names_fts USING fts5(
+    station_name, content = 'el_station_names', content_rowid = 'id', tokenize = 'unicode61 remove_diacritics 2'
+);
+INSERT INTO el_station_names_fts (el_station_names_fts) VALUES ('rebuild');
+CREATE TRIGGER el_station_names_fts_insert AFTER INSERT ON el_station_names
+BEGIN
+    INSERT INTO el_station_names_fts (rowid, station_name) VALUES (NEW.id, NEW.station_name);
+END;
+CREATE TRIGGER el_station_names_fts_update AFTER UPDATE OF station_name ON el_station_names
+BEGIN
+    INSERT INTO el_station_names_fts (el_station_names_fts, rowid, station_name) VALUES ('delete', OLD.id, OLD.station_name);
+    INSERT INTO el_station_names_fts (rowid, station_name) VALUES (NEW.id, NEW.station_name);
+END;
+CREATE TRIGGER el_station_names_fts_delete AFTER DELETE ON el_station_names
+BEGIN
+    INSERT INTO el_station_names_fts (el_station_names_fts, rowid, station_name) VALUES ('delete', OLD.id, OLD.station_name);
+END;
+CREATE VIRTUAL TABLE sl_base_cities_fts USING fts5(
+    city_name, content = 'sl_base_cities', content_rowid = 'id', tokenize = 'unicode61 remove_diacritics 2'
+);
+INSERT INTO sl_base_cities_fts (sl_base_cities_fts) VALUES ('rebuild');
+CREATE TRIGGER sl_base_cities_fts_insert AFTER INSERT ON sl_base_cities
+BEGIN
+    INSERT INTO sl_base_cities_fts (rowid, city_name) VALUES (NEW.id, NEW.city_name);
+END;
+CREATE TRIGGER sl_base_cities_fts_update AFTER UPDATE OF city_name ON sl_base_cities
+BEGIN
+    INSERT INTO sl_base_cities_fts (sl_base_cities_fts, rowid, city_name) VALUES ('delete', OLD.id, OLD.city_name);
+    INSERT INTO sl_base_cities_fts (rowid, city_name) VALUES (NEW.id, NEW.city_name);
+END;
+CREATE TRIGGER sl_base_cities_fts_delete AFTER DELETE ON sl_base_cities
+BEGIN
+    INSERT INTO sl_base_cities_fts (sl_base_cities_fts, rowid, city_name) VALUES ('delete', OLD.id, OLD.city_name);
+END;
+CREATE VIRTUAL TABLE sl_chains_fts USING fts5(
+    station_name, content = 'sl_chains', content_rowid = 'id', tokenize = 'unicode61 remove_diacritics 2'
+);
+INSERT INTO sl_chains_fts (sl_chains_fts) VALUES ('rebuild');
+CREATE TRIGGER sl_chains_fts_insert AFTER INSERT ON sl_chains
+BEGIN
+    INSERT INTO sl_chains_fts (rowid, station_name) VALUES (NEW.id, NEW.station_name);
+END;
+CREATE TRIGGER sl_chains_fts_update AFTER UPDATE OF station_name ON sl_chains
+BEGIN
+    INSERT INTO sl_chains_fts (sl_chains_fts, rowid, station_name) VALUES ('delete', OLD.id, OLD.station_name);
+    INSERT INTO sl_chains_fts (rowid, station_name) VALUES (NEW.id, NEW.station_name);
+END;
+CREATE TRIGGER sl_chains_fts_delete AFTER DELETE ON sl_chains
+BEGIN
+    INSERT INTO sl_chains_fts (sl_chains_fts, rowid, station_name) VALUES ('delete', OLD.id, OLD.station_name);
+END;
@@ -0,0 +1,88 @@
+-- Districts (okresy) by the codes CIS JŘ appends to stop names of same-named municipalities,
+-- e.g. "Lhota [ZL]", and the regions (kraje) they belong to.
+CREATE TABLE sl_districts(
+    district_code TEXT PRIMARY KEY,
+    district_name TEXT NOT NULL UNIQUE,
+    region_name TEXT NOT NULL
+);
+INSERT INTO sl_districts (district_code, district_name, region_name) VALUES
+    ('AB', 'Praha', 'Hlavní město Praha'),
+    ('BN', 'Benešov', 'Středočeský kraj'),
+    ('BE', 'Beroun', 'Středočeský kraj'),
+    ('KD', 'Kladno', 'Středočeský kraj'),
+    ('KO', 'Kolín', 'Středočeský kraj'),
+    ('KH', 'Kutná Hora', 'Středočeský kraj'),
+    ('ME', 'Mělník', 'Středočeský kraj'),
+    ('MB', 'Mladá Boleslav', 'Středočeský kraj'),
+    ('NB', 'Nymburk', 'Středočeský kraj'),
+    ('PY', 'Praha-východ', 'Středočeský kraj'),
+    ('PZ', 'Praha-západ', 'Středočeský kraj'),
+    ('PB', 'Příbram', 'Středočeský kraj'),
+    ('RA', 'Rakovník', 'Středočeský kraj'),
+    ('CB', 'České Budějovice', 'Jihočeský kraj'),
+    ('CK', 'Český Krumlov', 'Jihočeský kraj'),
+    ('JH', 'Jindřichův Hradec', 'Jihočeský kraj'),
+    ('PI', 'Písek', 'Jihočeský kraj'),
+    ('PT', 'Prachatice', 'Jihočeský kraj'),
+    ('ST', 'Strakonice', 'Jihočeský kraj'),
+    ('TA', 'Tábor', 'Jihočeský kraj'),
+    ('DO', 'Domažlice', 'Plzeňský kraj'),
+    ('KT', 'Klatovy', 'Plzeňský kraj'),
+    ('PM', 'Plzeň-město', 'Plzeňský kraj'),
+    ('PJ', 'Plzeň-jih', 'Plzeňský kraj'),
+    ('PS', 'Plzeň-sever', 'Plzeňský kraj'),
+    ('RO', 'Rokycany', 'Plzeňský kraj'),
+    ('TC', 'Tachov', 'Plzeňský kraj'),
+    ('CH', 'Cheb', 'Karlovarský kraj'),
+    ('KV', 'Karlovy Vary', 'Karlovarský kraj'),
+    ('SO', 'Sokolov', 'Karlovarský kraj'),
+    ('DC', 'Děčín', 'Ústecký kraj'),
+    ('CV', 'Chomutov', 'Ústecký kraj'),
+    ('LT', 'Litoměřice', 'Ústecký kraj'),
+    ('LN', 'Louny', 'Ústecký kraj'),
+    ('MO', 'Most', 'Ústecký kraj'),
+    ('TP', 'Teplice', 'Ústecký kraj'),
+    ('UL', 'Ústí nad Labem', 'Ústecký kraj'),
+    ('CL', 'Česká Lípa', 'Liberecký kraj'),
+    ('JN', 'Jablonec nad Nisou', 'Liberecký kraj'),
+    ('LI', 'Liberec', 'Liberecký kraj'),
+    ('SM', 'Semily', 'Liberecký kraj'),
+    ('HK', 'Hradec Králové', 'Královéhradecký kraj'),
+    ('JC', 'Jičín', 'Královéhradecký kraj'),
+    ('NA', 'Náchod', 'Královéhradecký kraj'),
+    ('RK', 'Rychnov nad Kněžnou', 'Královéhradecký kraj'),
+    ('TU', 'Trutnov', 'Královéhradecký kraj'),
+    ('CR', 'Chrudim', 'Pardubický kraj'),
+    ('PU', 'Pardubice', 'Pardubický kraj'),
+    ('SY', 'Svitavy', 'Pardubický kraj'),
+    ('UO', 'Ústí nad Orlicí', 'Pardubický kraj'),
+    ('HB', 'Havlíčkův Brod', 'Kraj Vysočina'),
+    ('JI', 'Jihlava', 'Kraj Vysočina'),
+    ('PE', 'Pelhřimov', 'Kraj Vysočina'),
+    ('TR', 'Třebíč', 'Kraj Vysočina'),
+    ('ZR', 'Žďár nad Sázavou', 'Kraj Vysočina'),
+    ('BK', 'Blansko', 'Jihomoravský kraj'),
+    ('BM', 'Brno-město', 'Jihomoravský kraj'),
+    ('BO', 'Brno-venkov', 'Jihomoravský kraj'),
+    ('BV', 'Břeclav', 'Jihomoravský kraj'),
+    ('HO', 'Hodonín', 'Jihomoravský kraj'),
+    ('VY', 'Vyškov', 'Jihomoravský kraj'),
+    ('ZN', 'Znojmo', 'Jihomoravský kraj'),
+    ('JE', 'Jeseník', 'Olomoucký kraj'),
+    ('OL', 'Olomouc', 'Olomoucký kraj'),
+    ('PR', 'Přerov', 'Olomoucký kraj'),
+    ('PV', 'Prostějov', 'Olomoucký kraj'),
+    ('SU', 'Šumperk', 'Olomoucký kraj'),
+    ('KM', 'Kroměříž', 'Zlínský kraj'),
+    ('UH', 'Uherské Hradiště', 'Zlínský kraj'),
+    ('VS', 'Vsetín', 'Zlínský kraj'),
+    ('ZL', 'Zlín', 'Zlínský kraj'),
+    ('BR', 'Bruntál', 'Moravskoslezský kraj'),
+    ('FM', 'Frýdek-Místek', 'Moravskoslezský kraj'),
+    ('KI', 'Karviná', 'Moravskoslezský kraj'),
+    ('NJ', 'Nový Jičín', 'Moravskoslezský kraj'),
+    ('OP', 'Opava', 'Moravskoslezský kraj'),
+    ('OV', 'Ostrava-město', 'Moravskoslezský kraj');
+-- imported from the district of the city, unknown for cities imported before
+ALTER TABLE sl_base_cities ADD COLUMN district_code TEXT;
+CREATE INDEX sl_base_cities_idx2 ON sl_base_cities (city_name, district_code);
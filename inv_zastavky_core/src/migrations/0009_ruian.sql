@@ -0,0 +1,37 @@
+-- Municipalities (obce) and their parts (části obcí) imported from RÚIAN are cities identified by
+-- their RÚIAN codes, parts point to their municipality by `municipality_code`.
+ALTER TABLE sl_districts ADD COLUMN ruian_code INTEGER;
+ALTER TABLE sl_base_cities ADD COLUMN ruian_code INTEGER;
+ALTER TABLE sl_base_cities ADD COLUMN municipality_code INTEGER;
+CREATE INDEX sl_base_cities_idx3 ON sl_base_cities (municipality_code, city_name);
+-- Boundaries of municipalities by name as GeoJSON geometries in WGS 84, from RÚIAN or GeoJSON, with
+-- their bounding boxes indexed by an R*Tree to find the municipalities around a station. Boundaries
+-- from RÚIAN keep their code.
+CREATE TABLE sl_municipality_boundaries(
+    id INTEGER PRIMARY KEY,
+    municipality_name TEXT NOT NULL,
+    district_code TEXT,
+    ruian_code INTEGER UNIQUE,
+    lat_min float NOT NULL,
+    lat_max float NOT NULL,
+    lon_min float NOT NULL,
+    lon_max float NOT NULL,
+    geometry TEXT NOT NULL
+);
+CREATE INDEX sl_municipality_boundaries_idx1 ON sl_municipality_boundaries (municipality_name, district_code);
+CREATE VIRTUAL TABLE sl_municipality_boundaries_rtree USING rtree(id, lat_min, lat_max, lon_min, lon_max);
+CREATE TRIGGER sl_municipality_boundaries_rtree_insert AFTER INSERT ON sl_municipality_boundaries
+BEGIN
+    INSERT OR REPLACE INTO sl_municipality_boundaries_rtree (id, lat_min, lat_max, lon_min, lon_max)
+        VALUES (NEW.id, NEW.lat_min, NEW.lat_max, NEW.lon_min, NEW.lon_max);
+END;
+CREATE TRIGGER sl_municipality_boundaries_rtree_update AFTER UPDATE OF id, lat_min, lat_max, lon_min, lon_max ON sl_municipality_boundaries
+BEGIN
+    DELETE FROM sl_municipality_boundaries_rtree WHERE id = OLD.id;
+    INSERT INTO sl_municipality_boundaries_rtree (id, lat_min, lat_max, lon_min, lon_max)
+        VALUES (NEW.id, NEW.lat_min, NEW.lat_max, NEW.lon_min, NEW.lon_max);
+END;
+CREATE TRIGGER sl_municipality_boundaries_rtree_delete AFTER DELETE ON sl_municipality_boundaries
+BEGIN
+    DELETE FROM sl_municipality_boundaries_rtree WHERE id = OLD.id;
+END;
@@ -0,0 +1,5 @@
+-- IDs of base stations given by a property of the imported GeoJSON.
+ALTER TABLE sl_base_stations ADD COLUMN source_id TEXT;
+-- Names used to be imported as JSON strings, e.g. "\"Praha hl.n.\"".
+UPDATE sl_base_stations SET station_name = json_extract(station_name, '$')
+    WHERE json_valid(station_name) AND json_type(station_name) = 'text';
@@ -0,0 +1,28 @@
+-- Counter of changes of station positions and names, tiles of stations are cached by it. Unlike
+-- the change log it also counts changes made outside of it, e.g. by `manager repair`.
+CREATE TABLE el_stations_version(version integer NOT NULL);
+INSERT INTO el_stations_version (version) VALUES (0);
+CREATE TRIGGER el_station_pos_version_insert AFTER INSERT ON el_station_pos
+BEGIN
+    UPDATE el_stations_version SET version = version + 1;
+END;
+CREATE TRIGGER el_station_pos_version_update AFTER UPDATE ON el_station_pos
+BEGIN
+    UPDATE el_stations_version SET version = version + 1;
+END;
+CREATE TRIGGER el_station_pos_version_delete AFTER DELETE ON el_station_pos
+BEGIN
+    UPDATE el_stations_version SET version = version + 1;
+END;
+CREATE TRIGGER el_station_names_version_insert AFTER INSERT ON el_station_names
+BEGIN
+    UPDATE el_stations_version SET version = version + 1;
+END;
+CREATE TRIGGER el_station_names_version_update AFTER UPDATE ON el_station_names
+BEGIN
+    UPDATE el_stations_version SET version = version + 1;
+END;
+CREATE TRIGGER el_station_names_version_delete AFTER DELETE ON el_station_names
+BEGIN
+    UPDATE el_stations_version SET version = version + 1;
+END;
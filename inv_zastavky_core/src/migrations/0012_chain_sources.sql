@@ -0,0 +1,9 @@
+-- Timetable sources of imported chains, `netex`, `jdf` or `gtfs`. Importing a source replaces only
+-- its chains, a chain found in several sources is kept while any of them still has it.
+CREATE TABLE sl_chain_sources(
+    chain_hash TEXT NOT NULL,
+    source TEXT NOT NULL,
+    PRIMARY KEY (chain_hash, source)
+);
+-- chains imported before sources were recorded are replaced by the next import of any source
+INSERT INTO sl_chain_sources (chain_hash, source) SELECT DISTINCT chain_hash, 'unknown' FROM sl_chains;
@@ -0,0 +1,54 @@
+-- Counters of changes of base stations and cities, their tiles are cached by them like tiles of
+-- stations by `el_stations_version`. Imports rewrite the tables, so any import expires the tiles.
+CREATE TABLE sl_base_stations_version(version integer NOT NULL);
+INSERT INTO sl_base_stations_version (version) VALUES (0);
+CREATE TRIGGER sl_base_stations_version_insert AFTER INSERT ON sl_base_stations
+BEGIN
+    UPDATE sl_base_stations_version SET version = version + 1;
+END;
+CREATE TRIGGER sl_base_stations_version_update AFTER UPDATE ON sl_base_stations
+BEGIN
+    UPDATE sl_base_stations_version SET version = version + 1;
+END;
+CREATE TRIGGER sl_base_stations_version_delete AFTER DELETE ON sl_base_stations
+BEGIN
+    UPDATE sl_base_stations_version SET version = version + 1;
+END;
+CREATE TRIGGER sl_netex_stations_version_insert AFTER INSERT ON sl_netex_stations
+BEGIN
+    UPDATE sl_base_stations_version SET version = version + 1;
+END;
+CREATE TRIGGER sl_netex_stations_version_update AFTER UPDATE ON sl_netex_stations
+BEGIN
+    UPDATE sl_base_stations_version SET version = version + 1;
+END;
+CREATE TRIGGER sl_netex_stations_version_delete AFTER DELETE ON sl_netex_stations
+BEGIN
+    UPDATE sl_base_stations_version SET version = version + 1;
+END;
+CREATE TRIGGER sl_gtfs_stations_version_insert AFTER INSERT ON sl_gtfs_stations
+BEGIN
+    UPDATE sl_base_stations_version SET version = version + 1;
+END;
+CREATE TRIGGER sl_gtfs_stations_version_update AFTER UPDATE ON sl_gtfs_stations
+BEGIN
+    UPDATE sl_base_stations_version SET version = version + 1;
+END;
+CREATE TRIGGER sl_gtfs_stations_version_delete AFTER DELETE ON sl_gtfs_stations
+BEGIN
+    UPDATE sl_base_stations_version SET version = version + 1;
+END;
+CREATE TABLE sl_base_cities_version(version integer NOT NULL);
+INSERT INTO sl_base_cities_version (version) VALUES (0);
+CREATE TRIGGER sl_base_cities_version_insert AFTER INSERT ON sl_base_cities
+BEGIN
+    UPDATE sl_base_cities_version SET version = version + 1;
+END;
+CREATE TRIGGER sl_base_cities_version_update AFTER UPDATE ON sl_base_cities
+BEGIN
+    UPDATE sl_base_cities_version SET version = version + 1;
+END;
+CREATE TRIGGER sl_base_cities_version_delete AFTER DELETE ON sl_base_cities
+BEGIN
+    UPDATE sl_base_cities_version SET version = version + 1;
+END;
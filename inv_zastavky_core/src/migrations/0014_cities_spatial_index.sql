@@ -0,0 +1,21 @@
+-- Spatial index of cities for tiles of the cities layer, like `sl_base_stations_rtree`.
+CREATE VIRTUAL TABLE sl_base_cities_rtree USING rtree(id, lat_min, lat_max, lon_min, lon_max);
+INSERT INTO sl_base_cities_rtree (id, lat_min, lat_max, lon_min, lon_max)
+    SELECT id, lat, lat, lon, lon FROM sl_base_cities WHERE lat IS NOT NULL AND lon IS NOT NULL;
+CREATE TRIGGER sl_base_cities_rtree_insert AFTER INSERT ON sl_base_cities
+WHEN NEW.lat IS NOT NULL AND NEW.lon IS NOT NULL
+BEGIN
+    INSERT OR REPLACE INTO sl_base_cities_rtree (id, lat_min, lat_max, lon_min, lon_max)
+        VALUES (NEW.id, NEW.lat, NEW.lat, NEW.lon, NEW.lon);
+END;
+CREATE TRIGGER sl_base_cities_rtree_update AFTER UPDATE OF id, lat, lon ON sl_base_cities
+BEGIN
+    DELETE FROM sl_base_cities_rtree WHERE id = OLD.id;
+    INSERT INTO sl_base_cities_rtree (id, lat_min, lat_max, lon_min, lon_max)
+        SELECT NEW.id, NEW.lat, NEW.lat, NEW.lon, NEW.lon
+        WHERE NEW.lat IS NOT NULL AND NEW.lon IS NOT NULL;
+END;
+CREATE TRIGGER sl_base_cities_rtree_delete AFTER DELETE ON sl_base_cities
+BEGIN
+    DELETE FROM sl_base_cities_rtree WHERE id = OLD.id;
+END;
@@ -0,0 +1,300 @@
+use sqlx::pool::PoolConnection;
+use sqlx::{Connection, Row, Sqlite, query};
+use std::fmt::{Display, Formatter};
+
+/// Step of the database schema, applied in order of versions.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    sql: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        sql: include_str!("0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "stop_ids",
+        sql: include_str!("0002_stop_ids.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "users",
+        sql: include_str!("0003_users.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "change_log",
+        sql: include_str!("0004_change_log.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "station_references",
+        sql: include_str!("0005_station_references.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "spatial_index",
+        sql: include_str!("0006_spatial_index.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "search_index",
+        sql: include_str!("0007_search_index.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "districts",
+        sql: include_str!("0008_districts.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "ruian",
+        sql: include_str!("0009_ruian.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "base_station_ids",
+        sql: include_str!("0010_base_station_ids.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "stations_version",
+        sql: include_str!("0011_stations_version.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "chain_sources",
+        sql: include_str!("0012_chain_sources.sql"),
+    },
+    Migration {
+        version: 13,
+        name: "base_versions",
+        sql: include_str!("0013_base_versions.sql"),
+    },
+    Migration {
+        version: 14,
+        name: "cities_spatial_index",
+        sql: include_str!("0014_cities_spatial_index.sql"),
+    },
+];
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Storage(sqlx::Error),
+    /// The database was migrated by newer version of the application.
+    UnknownVersion { found: u32, supported: u32 },
+}
+
+impl Display for MigrationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationError::Storage(err) => write!(f, "{}", err),
+            MigrationError::UnknownVersion { found, supported } => write!(
+                f,
+                "database schema version {} is newer than supported version {}",
+                found, supported
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<sqlx::Error> for MigrationError {
+    fn from(err: sqlx::Error) -> Self {
+        MigrationError::Storage(err)
+    }
+}
+
+/// Latest schema version known to this build.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+/// Returns version of the schema, 0 for database without applied migrations.
+pub async fn schema_version(db: &mut PoolConnection<Sqlite>) -> Result<u32, MigrationError> {
+    query(
+        "CREATE TABLE IF NOT EXISTS schema_migrations(
+    version integer PRIMARY KEY,
+    name TEXT,
+    applied_at TEXT DEFAULT CURRENT_TIMESTAMP
+);",
+    )
+    .execute(&mut **db)
+    .await?;
+    let row = query("SELECT coalesce(max(version), 0) FROM schema_migrations;")
+        .fetch_one(&mut **db)
+        .await?;
+    Ok(row.try_get(0)?)
+}
+
+/// Fails if the database schema is newer than the latest known migration.
+pub async fn verify(db: &mut PoolConnection<Sqlite>) -> Result<u32, MigrationError> {
+    let version = schema_version(db).await?;
+    if version > latest_version() {
+        return Err(MigrationError::UnknownVersion {
+            found: version,
+            supported: latest_version(),
+        });
+    }
+    Ok(version)
+}
+
+/// Applies pending migrations, each in its own transaction, and returns the new version.
+pub async fn migrate(db: &mut PoolConnection<Sqlite>) -> Result<u32, MigrationError> {
+    let mut version = verify(db).await?;
+    for migration in MIGRATIONS {
+        if migration.version <= version {
+            continue;
+        }
+        let mut tx = db.begin().await?;
+        query(migration.sql).execute(&mut *tx).await?;
+        query("INSERT INTO schema_migrations (version, name) VALUES ($1, $2);")
+            .bind(migration.version)
+            .bind(migration.name)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        version = migration.version;
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{memory_db, migrated_db};
+
+    async fn count(db: &mut PoolConnection<Sqlite>, sql: &str) -> i64 {
+        query(sql).fetch_one(&mut **db).await.unwrap().get(0)
+    }
+
+    #[test]
+    fn versions_are_consecutive() {
+        for (idx, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version as usize, idx + 1, "{}", migration.name);
+        }
+    }
+
+    #[tokio::test]
+    async fn fresh_database_reaches_latest_version() {
+        let mut db = memory_db().await;
+        assert_eq!(schema_version(&mut db).await.unwrap(), 0);
+
+        assert_eq!(migrate(&mut db).await.unwrap(), latest_version());
+        assert_eq!(schema_version(&mut db).await.unwrap(), latest_version());
+        let applied = count(&mut db, "SELECT count(*) FROM schema_migrations;").await;
+        assert_eq!(applied, MIGRATIONS.len() as i64);
+    }
+
+    #[tokio::test]
+    async fn legacy_random_stop_ids_are_renumbered() {
+        let mut db = memory_db().await;
+        query(include_str!("0001_initial.sql"))
+            .execute(&mut *db)
+            .await
+            .unwrap();
+        query(
+            "INSERT INTO el_station_pos (stop_id, lat, lon) VALUES
+    (9000001, 50.0, 14.0), (17, 50.1, 14.1), (123456789, 50.2, 14.2);
+INSERT INTO el_station_names (stop_id, station_name) VALUES
+    (123456789, 'Praha,Florenc'), (9000001, 'Kolín'), (17, 'Brno'), (17, 'Brno,hl.n.'),
+    (555, 'Bez pozice');
+INSERT INTO hl_stations (chain_hash, station_name, pos, stop_id) VALUES
+    ('h1', 'Kolín', 0, 9000001), ('h1', 'Praha,Florenc', 1, 123456789), ('h1', 'Beroun', 2, NULL);",
+        )
+        .execute(&mut *db)
+        .await
+        .unwrap();
+
+        assert_eq!(migrate(&mut db).await.unwrap(), latest_version());
+
+        // ids follow the order of creation of positions, then of names
+        let rows = query("SELECT stop_id, legacy_stop_id FROM el_stop_ids ORDER BY stop_id;")
+            .fetch_all(&mut *db)
+            .await
+            .unwrap();
+        let ids: Vec<(i64, i64)> = rows.iter().map(|row| (row.get(0), row.get(1))).collect();
+        assert_eq!(ids, [(1, 9000001), (2, 17), (3, 123456789), (4, 555)]);
+
+        let rows = query("SELECT stop_id, lat FROM el_station_pos ORDER BY stop_id;")
+            .fetch_all(&mut *db)
+            .await
+            .unwrap();
+        let positions: Vec<(i64, f64)> = rows.iter().map(|row| (row.get(0), row.get(1))).collect();
+        assert_eq!(positions, [(1, 50.0), (2, 50.1), (3, 50.2)]);
+
+        let rows = query(
+            "SELECT stop_id, station_name FROM el_station_names ORDER BY stop_id, station_name;",
+        )
+        .fetch_all(&mut *db)
+        .await
+        .unwrap();
+        let names: Vec<(i64, String)> = rows.iter().map(|row| (row.get(0), row.get(1))).collect();
+        assert_eq!(
+            names,
+            [
+                (1, String::from("Kolín")),
+                (2, String::from("Brno")),
+                (2, String::from("Brno,hl.n.")),
+                (3, String::from("Praha,Florenc")),
+                (4, String::from("Bez pozice")),
+            ]
+        );
+
+        let rows = query("SELECT pos, stop_id FROM hl_stations ORDER BY pos;")
+            .fetch_all(&mut *db)
+            .await
+            .unwrap();
+        let assignments: Vec<(i64, Option<i64>)> =
+            rows.iter().map(|row| (row.get(0), row.get(1))).collect();
+        assert_eq!(assignments, [(0, Some(1)), (1, Some(3)), (2, None)]);
+
+        // new ids continue after the renumbered ones
+        let next: i64 = query("INSERT INTO el_stop_ids DEFAULT VALUES RETURNING stop_id;")
+            .fetch_one(&mut *db)
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(next, 5);
+    }
+
+    #[tokio::test]
+    async fn migrating_a_migrated_database_does_nothing() {
+        let mut db = migrated_db().await;
+        query("INSERT INTO el_stop_ids DEFAULT VALUES;")
+            .execute(&mut *db)
+            .await
+            .unwrap();
+
+        assert_eq!(migrate(&mut db).await.unwrap(), latest_version());
+        let applied = count(&mut db, "SELECT count(*) FROM schema_migrations;").await;
+        assert_eq!(applied, MIGRATIONS.len() as i64);
+        assert_eq!(count(&mut db, "SELECT count(*) FROM el_stop_ids;").await, 1);
+    }
+
+    #[tokio::test]
+    async fn newer_schema_is_rejected() {
+        let mut db = migrated_db().await;
+        query("INSERT INTO schema_migrations (version, name) VALUES ($1, 'future');")
+            .bind(latest_version() + 1)
+            .execute(&mut *db)
+            .await
+            .unwrap();
+
+        let found = latest_version() + 1;
+        let supported = latest_version();
+        assert!(matches!(
+            verify(&mut db).await,
+            Err(MigrationError::UnknownVersion { found: f, supported: s }) if f == found && s == supported
+        ));
+        assert!(matches!(
+            migrate(&mut db).await,
+            Err(MigrationError::UnknownVersion { .. })
+        ));
+    }
+}
@@ -1,3 +1,6 @@
+use crate::model::bbox::BBox;
+use crate::model::cluster::{Clustered, Grid, MAX_POINTS};
+use crate::search;
 use serde::Serialize;
 use sqlx::pool::PoolConnection;
 use sqlx::sqlite::SqliteRow;
@@ -6,19 +9,62 @@ use sqlx::{Row, Sqlite, query};
 #[derive(Serialize, Debug)]
 pub struct BaseCity {
     name: String,
+    /// CIS JŘ code of the district, unknown if the import had no districts.
+    district: Option<String>,
+    region: Option<String>,
+    /// RÚIAN code of municipalities and their parts.
+    code: Option<i64>,
+    /// RÚIAN code of the municipality of a municipality part.
+    municipality_code: Option<i64>,
     lat: f64,
     lon: f64,
 }
 
+/// Columns read by [`BaseCity::from_row`].
+const COLUMNS: &str =
+    "city_name, district_code, region_name, sl_base_cities.ruian_code, municipality_code, lat, lon";
+
 impl BaseCity {
-    pub fn new(name: String, lat: f64, lon: f64) -> BaseCity {
-        BaseCity { name, lat, lon }
+    pub fn new(
+        name: String,
+        district: Option<String>,
+        region: Option<String>,
+        code: Option<i64>,
+        municipality_code: Option<i64>,
+        lat: f64,
+        lon: f64,
+    ) -> BaseCity {
+        BaseCity {
+            name,
+            district,
+            region,
+            code,
+            municipality_code,
+            lat,
+            lon,
+        }
     }
 
     pub fn name(&self) -> &str {
         self.name.as_str()
     }
 
+    pub fn district(&self) -> Option<&str> {
+        self.district.as_deref()
+    }
+
+    pub fn region(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+
+    pub fn code(&self) -> Option<i64> {
+        self.code
+    }
+
+    pub fn municipality_code(&self) -> Option<i64> {
+        self.municipality_code
+    }
+
     pub fn lat(&self) -> f64 {
         self.lat
     }
@@ -29,9 +75,21 @@ impl BaseCity {
 
     fn from_row(row: SqliteRow) -> Result<BaseCity, sqlx::Error> {
         let name = row.try_get(0)?;
-        let lat = row.try_get(1)?;
-        let lon = row.try_get(2)?;
-        Ok(Self::new(name, lat, lon))
+        let district = row.try_get(1)?;
+        let region = row.try_get(2)?;
+        let code = row.try_get(3)?;
+        let municipality_code = row.try_get(4)?;
+        let lat = row.try_get(5)?;
+        let lon = row.try_get(6)?;
+        Ok(Self::new(
+            name,
+            district,
+            region,
+            code,
+            municipality_code,
+            lat,
+            lon,
+        ))
     }
 
     fn from_rows(rows: Vec<SqliteRow>) -> Result<Vec<BaseCity>, sqlx::Error> {
@@ -42,26 +100,265 @@ impl BaseCity {
         Ok(cities)
     }
 
+    /// Counter increased by every change of cities, so by every import of them.
+    pub async fn data_version(db: &mut PoolConnection<Sqlite>) -> Result<i64, sqlx::Error> {
+        query("SELECT version FROM sl_base_cities_version;")
+            .fetch_one(&mut **db)
+            .await?
+            .try_get(0)
+    }
+
+    /// Loads all cities in the bbox, ordered by name.
+    pub async fn get_by_bbox(
+        db: &mut PoolConnection<Sqlite>,
+        bbox: &BBox,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let rows: Vec<SqliteRow> = query(&format!(
+            "SELECT {} FROM sl_base_cities
+    LEFT JOIN sl_districts USING (district_code)
+WHERE id IN (
+    SELECT id FROM sl_base_cities_rtree
+    WHERE lat_max >= $1 AND lat_min <= $2 AND lon_max >= $3 AND lon_min <= $4
+) AND $1 <= lat AND lat <= $2 AND $3 <= lon AND lon <= $4 ORDER BY city_name, lat, lon;",
+            COLUMNS
+        ))
+        .bind(bbox.lat_from())
+        .bind(bbox.lat_to())
+        .bind(bbox.lon_from())
+        .bind(bbox.lon_to())
+        .fetch_all(&mut **db)
+        .await?;
+        Self::from_rows(rows)
+    }
+
+    /// Loads cities in the bbox, clustered for the zoom level if there are too many of them.
+    pub async fn get_clustered(
+        db: &mut PoolConnection<Sqlite>,
+        bbox: &BBox,
+        zoom: u8,
+    ) -> Result<Clustered<Self>, sqlx::Error> {
+        let count: i64 = query(
+            "SELECT count(*) FROM sl_base_cities_rtree
+WHERE lat_max >= $1 AND lat_min <= $2 AND lon_max >= $3 AND lon_min <= $4;",
+        )
+        .bind(bbox.lat_from())
+        .bind(bbox.lat_to())
+        .bind(bbox.lon_from())
+        .bind(bbox.lon_to())
+        .fetch_one(&mut **db)
+        .await?
+        .try_get(0)?;
+        if count <= MAX_POINTS as i64 {
+            return Ok(Clustered::Points(Self::get_by_bbox(db, bbox).await?));
+        }
+        let rows = query(
+            "SELECT lat, lon FROM sl_base_cities
+WHERE id IN (
+    SELECT id FROM sl_base_cities_rtree
+    WHERE lat_max >= $1 AND lat_min <= $2 AND lon_max >= $3 AND lon_min <= $4
+) AND $1 <= lat AND lat <= $2 AND $3 <= lon AND lon <= $4;",
+        )
+        .bind(bbox.lat_from())
+        .bind(bbox.lat_to())
+        .bind(bbox.lon_from())
+        .bind(bbox.lon_to())
+        .fetch_all(&mut **db)
+        .await?;
+        let mut grid = Grid::new(zoom);
+        for row in rows {
+            grid.add(row.try_get(0)?, row.try_get(1)?);
+        }
+        Ok(Clustered::Clusters(grid.clusters()))
+    }
+
+    /// Searches cities without diacritics, names starting with the text first.
     pub async fn search(
         db: &mut PoolConnection<Sqlite>,
         search: &str,
     ) -> Result<Vec<Self>, sqlx::Error> {
-        let rows: Vec<SqliteRow> = query(
-            "SELECT city_name, lat, lon FROM sl_base_cities WHERE city_name LIKE $1 ORDER BY city_name LIMIT 50;")
-            .bind(search)
-            .fetch_all(&mut **db).await?;
-        Self::from_rows(rows)
+        let matches = search::search_cities(db, search, 50).await?;
+        Ok(matches
+            .into_iter()
+            .filter_map(|found| {
+                Some(Self::new(
+                    found.name().to_string(),
+                    found.district().map(str::to_string),
+                    found.region().map(str::to_string),
+                    found.code(),
+                    found.municipality_code(),
+                    found.lat()?,
+                    found.lon()?,
+                ))
+            })
+            .collect())
     }
 
+    /// Loads municipalities with the name, with `district` only those in the district or in an
+    /// unknown one.
     pub async fn get_by_name(
         db: &mut PoolConnection<Sqlite>,
         name: &str,
+        district: Option<&str>,
     ) -> Result<Vec<Self>, sqlx::Error> {
-        let rows: Vec<SqliteRow> =
-            query("SELECT city_name, lat, lon FROM sl_base_cities WHERE city_name = $1;")
-                .bind(name)
-                .fetch_all(&mut **db)
-                .await?;
+        let rows: Vec<SqliteRow> = query(&format!(
+            "SELECT {} FROM sl_base_cities
+    LEFT JOIN sl_districts USING (district_code)
+WHERE city_name = $1 AND municipality_code IS NULL
+    AND ($2 IS NULL OR district_code IS NULL OR district_code = $2);",
+            COLUMNS
+        ))
+        .bind(name)
+        .bind(district)
+        .fetch_all(&mut **db)
+        .await?;
         Self::from_rows(rows)
     }
+
+    /// Loads the part of the municipality with the name.
+    pub async fn get_part(
+        db: &mut PoolConnection<Sqlite>,
+        municipality_code: i64,
+        name: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let row = query(&format!(
+            "SELECT {} FROM sl_base_cities
+    LEFT JOIN sl_districts USING (district_code)
+WHERE municipality_code = $1 AND city_name = $2
+ORDER BY sl_base_cities.ruian_code LIMIT 1;",
+            COLUMNS
+        ))
+        .bind(municipality_code)
+        .bind(name)
+        .fetch_optional(&mut **db)
+        .await?;
+        row.map(Self::from_row).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::cluster::Cluster;
+    use crate::testing::migrated_db;
+
+    async fn db_with_cities() -> PoolConnection<Sqlite> {
+        let mut db = migrated_db().await;
+        query(
+            "INSERT INTO sl_base_cities (city_name, district_code, ruian_code, municipality_code, lat, lon) VALUES
+    ('Lhota', 'ZL', 585301, NULL, 49.15, 17.6),
+    ('Lhota', 'KD', 532622, NULL, 50.25, 14.0),
+    ('Lhota', NULL, NULL, NULL, 49.7, 15.5),
+    ('Lhota', 'BK', 80001, 581283, 49.4, 16.6),
+    ('Dolní Lhota', 'ZL', 585302, NULL, 49.14, 17.62);",
+        )
+        .execute(&mut *db)
+        .await
+        .unwrap();
+        db
+    }
+
+    fn districts(cities: &[BaseCity]) -> Vec<Option<&str>> {
+        let mut districts: Vec<_> = cities.iter().map(BaseCity::district).collect();
+        districts.sort();
+        districts
+    }
+
+    #[tokio::test]
+    async fn same_named_municipalities_are_told_apart_by_district() {
+        let mut db = db_with_cities().await;
+
+        let cities = BaseCity::get_by_name(&mut db, "Lhota", Some("ZL"))
+            .await
+            .unwrap();
+        assert_eq!(districts(&cities), [None, Some("ZL")]);
+        let zlin = cities.iter().find(|city| city.code() == Some(585301));
+        assert_eq!(zlin.unwrap().region(), Some("Zlínský kraj"));
+
+        let cities = BaseCity::get_by_name(&mut db, "Lhota", Some("KD"))
+            .await
+            .unwrap();
+        assert_eq!(districts(&cities), [None, Some("KD")]);
+
+        // parts of municipalities are not municipalities
+        let cities = BaseCity::get_by_name(&mut db, "Lhota", None).await.unwrap();
+        assert_eq!(districts(&cities), [None, Some("KD"), Some("ZL")]);
+        let cities = BaseCity::get_by_name(&mut db, "Lhota", Some("BK"))
+            .await
+            .unwrap();
+        assert_eq!(districts(&cities), [None]);
+    }
+
+    #[tokio::test]
+    async fn imports_change_the_version() {
+        let mut db = migrated_db().await;
+        let empty = BaseCity::data_version(&mut db).await.unwrap();
+        query("INSERT INTO sl_base_cities (city_name, lat, lon) VALUES ('Lhota', 49.15, 17.6);")
+            .execute(&mut *db)
+            .await
+            .unwrap();
+        let imported = BaseCity::data_version(&mut db).await.unwrap();
+        assert!(imported > empty);
+        query("DELETE FROM sl_base_cities;")
+            .execute(&mut *db)
+            .await
+            .unwrap();
+        assert!(BaseCity::data_version(&mut db).await.unwrap() > imported);
+    }
+
+    #[tokio::test]
+    async fn cities_in_bbox_follow_moves() {
+        let mut db = db_with_cities().await;
+        let bbox = BBox::new([49.1, 49.2], [17.5, 17.7]);
+        let names = |cities: Vec<BaseCity>| -> Vec<String> {
+            cities.into_iter().map(|city| city.name).collect()
+        };
+        let cities = BaseCity::get_by_bbox(&mut db, &bbox).await.unwrap();
+        assert_eq!(names(cities), ["Dolní Lhota", "Lhota"]);
+
+        query("UPDATE sl_base_cities SET lat = 48.0 WHERE city_name = 'Dolní Lhota';")
+            .execute(&mut *db)
+            .await
+            .unwrap();
+        let cities = BaseCity::get_by_bbox(&mut db, &bbox).await.unwrap();
+        assert_eq!(names(cities), ["Lhota"]);
+    }
+
+    #[tokio::test]
+    async fn cities_are_clustered_above_max_points() {
+        let mut db = migrated_db().await;
+        let bbox = BBox::new([49.0, 50.0], [16.0, 17.0]);
+        for (cities, clustered) in [(MAX_POINTS, false), (MAX_POINTS + 1, true)] {
+            query(
+                "DELETE FROM sl_base_cities;
+WITH RECURSIVE ids(id) AS (SELECT 1 UNION ALL SELECT id + 1 FROM ids WHERE id < $1)
+INSERT INTO sl_base_cities (city_name, lat, lon) SELECT 'Lhota', 49.5, 16.0 + id / 1000.0 FROM ids;",
+            )
+            .bind(cities as i64)
+            .execute(&mut *db)
+            .await
+            .unwrap();
+
+            match BaseCity::get_clustered(&mut db, &bbox, 10).await.unwrap() {
+                Clustered::Points(points) => {
+                    assert!(!clustered);
+                    assert_eq!(points.len(), cities);
+                }
+                Clustered::Clusters(clusters) => {
+                    assert!(clustered);
+                    let count: usize = clusters.iter().map(Cluster::count).sum();
+                    assert_eq!(count, cities);
+                    assert!(clusters.len() < cities);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn parts_are_found_within_their_municipality() {
+        let mut db = db_with_cities().await;
+        let part = BaseCity::get_part(&mut db, 581283, "Lhota").await.unwrap();
+        assert_eq!(part.unwrap().code(), Some(80001));
+        let part = BaseCity::get_part(&mut db, 585301, "Lhota").await.unwrap();
+        assert!(part.is_none());
+    }
 }
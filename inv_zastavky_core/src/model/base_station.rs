@@ -1,39 +1,239 @@
 use crate::model::bbox::BBox;
+use crate::model::cluster::{Clustered, Grid, MAX_POINTS};
 use serde::Serialize;
 use sqlx::pool::PoolConnection;
 use sqlx::sqlite::SqliteRow;
 use sqlx::{Row, Sqlite, query};
 
+/// Origin of a base station position.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BaseStationSource {
+    /// Imported from GeoJSON into `sl_base_stations`.
+    GeoJson,
+    /// StopPlace or Quay location from NeTEx timetables in `sl_netex_stations`.
+    Netex,
+    /// Stop or station location from GTFS feed in `sl_gtfs_stations`.
+    Gtfs,
+}
+
+impl BaseStationSource {
+    pub fn tag(&self) -> &'static str {
+        match self {
+            BaseStationSource::GeoJson => "geojson",
+            BaseStationSource::Netex => "netex",
+            BaseStationSource::Gtfs => "gtfs",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "geojson" => Some(BaseStationSource::GeoJson),
+            "netex" => Some(BaseStationSource::Netex),
+            "gtfs" => Some(BaseStationSource::Gtfs),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Serialize)]
 pub struct BaseStation {
     lat: f64,
     lon: f64,
     name: String,
+    source: BaseStationSource,
+    source_id: Option<String>,
 }
 
 impl BaseStation {
-    pub fn new(lat: f64, lon: f64, name: String) -> BaseStation {
-        BaseStation { lat, lon, name }
+    pub fn new(
+        lat: f64,
+        lon: f64,
+        name: String,
+        source: BaseStationSource,
+        source_id: Option<String>,
+    ) -> BaseStation {
+        BaseStation {
+            lat,
+            lon,
+            name,
+            source,
+            source_id,
+        }
+    }
+
+    pub fn lat(&self) -> f64 {
+        self.lat
+    }
+
+    pub fn lon(&self) -> f64 {
+        self.lon
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> BaseStationSource {
+        self.source
+    }
+
+    pub fn source_id(&self) -> Option<&str> {
+        self.source_id.as_deref()
+    }
+
+    /// Loads up to 500 base stations in the bbox, ordered by source and import order.
     pub async fn get_by_bbox(
         db: &mut PoolConnection<Sqlite>,
         bbox: &BBox,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        Self::load_bbox(db, bbox, Some(MAX_POINTS)).await
+    }
+
+    /// Loads all base stations in the bbox, ordered by source and import order.
+    pub async fn get_all_by_bbox(
+        db: &mut PoolConnection<Sqlite>,
+        bbox: &BBox,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        Self::load_bbox(db, bbox, None).await
+    }
+
+    async fn load_bbox(
+        db: &mut PoolConnection<Sqlite>,
+        bbox: &BBox,
+        limit: Option<usize>,
     ) -> Result<Vec<Self>, sqlx::Error> {
         let rows: Vec<SqliteRow> = query(
-            "SELECT lat, lon, station_name FROM sl_base_stations WHERE $1 <= lat AND lat <= $2 AND $3 <= lon AND lon <= $4 LIMIT 500;")
-            .bind(bbox.lat_from())
-            .bind(bbox.lat_to())
-            .bind(bbox.lon_from())
-            .bind(bbox.lon_to())
-            .fetch_all(&mut **db).await?;
+            "SELECT lat, lon, station_name, 'geojson', source_id, 1, id FROM sl_base_stations
+WHERE id IN (
+    SELECT id FROM sl_base_stations_rtree
+    WHERE lat_max >= $1 AND lat_min <= $2 AND lon_max >= $3 AND lon_min <= $4
+) AND $1 <= lat AND lat <= $2 AND $3 <= lon AND lon <= $4
+UNION ALL
+SELECT lat, lon, station_name, 'netex', netex_id, 2, id FROM sl_netex_stations
+WHERE id IN (
+    SELECT id FROM sl_netex_stations_rtree
+    WHERE lat_max >= $1 AND lat_min <= $2 AND lon_max >= $3 AND lon_min <= $4
+) AND $1 <= lat AND lat <= $2 AND $3 <= lon AND lon <= $4
+UNION ALL
+SELECT lat, lon, station_name, 'gtfs', gtfs_id, 3, id FROM sl_gtfs_stations
+WHERE id IN (
+    SELECT id FROM sl_gtfs_stations_rtree
+    WHERE lat_max >= $1 AND lat_min <= $2 AND lon_max >= $3 AND lon_min <= $4
+) AND $1 <= lat AND lat <= $2 AND $3 <= lon AND lon <= $4
+ORDER BY 6, 7
+LIMIT $5;",
+        )
+        .bind(bbox.lat_from())
+        .bind(bbox.lat_to())
+        .bind(bbox.lon_from())
+        .bind(bbox.lon_to())
+        .bind(limit.map_or(-1, |limit| limit as i64))
+        .fetch_all(&mut **db)
+        .await?;
         let mut stations = Vec::new();
         for row in rows {
             let lat = row.try_get(0)?;
             let lon = row.try_get(1)?;
             let name = row.try_get(2)?;
-            stations.push(Self::new(lat, lon, name));
+            let tag: &str = row.try_get(3)?;
+            let source = BaseStationSource::from_tag(tag).ok_or_else(|| {
+                sqlx::Error::Decode(format!("unknown base station source {}", tag).into())
+            })?;
+            let source_id = row.try_get(4)?;
+            stations.push(Self::new(lat, lon, name, source, source_id));
         }
         Ok(stations)
     }
+
+    /// Counter increased by every change of base stations from GeoJSON, NeTEx or GTFS, so by
+    /// every import of them.
+    pub async fn data_version(db: &mut PoolConnection<Sqlite>) -> Result<i64, sqlx::Error> {
+        query("SELECT version FROM sl_base_stations_version;")
+            .fetch_one(&mut **db)
+            .await?
+            .try_get(0)
+    }
+
+    /// Loads base stations in the bbox, clustered for the zoom level if there are too many of
+    /// them.
+    pub async fn get_clustered(
+        db: &mut PoolConnection<Sqlite>,
+        bbox: &BBox,
+        zoom: u8,
+    ) -> Result<Clustered<Self>, sqlx::Error> {
+        let count: i64 = query(
+            "SELECT
+    (SELECT count(*) FROM sl_base_stations_rtree
+        WHERE lat_max >= $1 AND lat_min <= $2 AND lon_max >= $3 AND lon_min <= $4)
+    + (SELECT count(*) FROM sl_netex_stations_rtree
+        WHERE lat_max >= $1 AND lat_min <= $2 AND lon_max >= $3 AND lon_min <= $4)
+    + (SELECT count(*) FROM sl_gtfs_stations_rtree
+        WHERE lat_max >= $1 AND lat_min <= $2 AND lon_max >= $3 AND lon_min <= $4);",
+        )
+        .bind(bbox.lat_from())
+        .bind(bbox.lat_to())
+        .bind(bbox.lon_from())
+        .bind(bbox.lon_to())
+        .fetch_one(&mut **db)
+        .await?
+        .try_get(0)?;
+        if count <= MAX_POINTS as i64 {
+            return Ok(Clustered::Points(Self::get_by_bbox(db, bbox).await?));
+        }
+        let rows = query(
+            "SELECT lat, lon FROM sl_base_stations
+WHERE id IN (
+    SELECT id FROM sl_base_stations_rtree
+    WHERE lat_max >= $1 AND lat_min <= $2 AND lon_max >= $3 AND lon_min <= $4
+) AND $1 <= lat AND lat <= $2 AND $3 <= lon AND lon <= $4
+UNION ALL
+SELECT lat, lon FROM sl_netex_stations
+WHERE id IN (
+    SELECT id FROM sl_netex_stations_rtree
+    WHERE lat_max >= $1 AND lat_min <= $2 AND lon_max >= $3 AND lon_min <= $4
+) AND $1 <= lat AND lat <= $2 AND $3 <= lon AND lon <= $4
+UNION ALL
+SELECT lat, lon FROM sl_gtfs_stations
+WHERE id IN (
+    SELECT id FROM sl_gtfs_stations_rtree
+    WHERE lat_max >= $1 AND lat_min <= $2 AND lon_max >= $3 AND lon_min <= $4
+) AND $1 <= lat AND lat <= $2 AND $3 <= lon AND lon <= $4;",
+        )
+        .bind(bbox.lat_from())
+        .bind(bbox.lat_to())
+        .bind(bbox.lon_from())
+        .bind(bbox.lon_to())
+        .fetch_all(&mut **db)
+        .await?;
+        let mut grid = Grid::new(zoom);
+        for row in rows {
+            grid.add(row.try_get(0)?, row.try_get(1)?);
+        }
+        Ok(Clustered::Clusters(grid.clusters()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::migrated_db;
+
+    #[tokio::test]
+    async fn imports_of_any_source_change_the_version() {
+        let mut db = migrated_db().await;
+        let mut version = BaseStation::data_version(&mut db).await.unwrap();
+        for sql in [
+            "INSERT INTO sl_base_stations (lat, lon, station_name) VALUES (49.3, 16.66, 'Adamov');",
+            "INSERT INTO sl_netex_stations (netex_id, station_name, lat, lon) VALUES ('SP1', 'Adamov', 49.3, 16.66);",
+            "INSERT INTO sl_gtfs_stations (gtfs_id, station_name, lat, lon) VALUES ('U1', 'Adamov', 49.3, 16.66);",
+            "UPDATE sl_netex_stations SET lat = 49.31;",
+            "DELETE FROM sl_gtfs_stations;",
+        ] {
+            query(sql).execute(&mut *db).await.unwrap();
+            let changed = BaseStation::data_version(&mut db).await.unwrap();
+            assert!(changed > version, "{}", sql);
+            version = changed;
+        }
+    }
 }
@@ -1,3 +1,6 @@
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone, Copy)]
 pub struct BBox {
     lat_from: f64,
     lat_to: f64,
@@ -30,4 +33,17 @@ impl BBox {
     pub fn lon_to(&self) -> f64 {
         self.lon_to
     }
+
+    /// Smallest bbox containing the point.
+    pub fn point(lat: f64, lon: f64) -> BBox {
+        BBox::new([lat, lat], [lon, lon])
+    }
+
+    /// Grows the bbox to contain the point.
+    pub fn extend(&mut self, lat: f64, lon: f64) {
+        self.lat_from = f64::min(self.lat_from, lat);
+        self.lat_to = f64::max(self.lat_to, lat);
+        self.lon_from = f64::min(self.lon_from, lon);
+        self.lon_to = f64::max(self.lon_to, lon);
+    }
 }
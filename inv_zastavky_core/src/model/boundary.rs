@@ -0,0 +1,311 @@
+use crate::model::StopId;
+use crate::model::station::Station;
+use crate::names::StopName;
+use crate::utils::geo::polygon_contains;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqliteConnection, query};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap};
+
+/// Name of a station placed outside of the municipality it names.
+#[derive(Serialize, Debug, Clone)]
+pub struct BoundaryWarning {
+    stop_id: StopId,
+    name: String,
+    municipality: String,
+    district: Option<String>,
+    /// Municipalities whose boundaries contain the station.
+    found: Vec<String>,
+}
+
+impl BoundaryWarning {
+    pub fn stop_id(&self) -> StopId {
+        self.stop_id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn municipality(&self) -> &str {
+        &self.municipality
+    }
+
+    pub fn district(&self) -> Option<&str> {
+        self.district.as_deref()
+    }
+
+    pub fn found(&self) -> &[String] {
+        &self.found
+    }
+}
+
+/// Station with warnings about its position, serialized as the station with `warnings`.
+#[derive(Serialize, Debug)]
+pub struct CheckedStation {
+    #[serde(flatten)]
+    station: Station,
+    warnings: Vec<BoundaryWarning>,
+}
+
+impl CheckedStation {
+    /// Checks the station against municipality boundaries.
+    pub async fn check(conn: &mut SqliteConnection, station: Station) -> Result<Self, sqlx::Error> {
+        let warnings = BoundaryChecker::default().check(conn, &station).await?;
+        Ok(Self { station, warnings })
+    }
+
+    pub fn station(&self) -> &Station {
+        &self.station
+    }
+
+    pub fn warnings(&self) -> &[BoundaryWarning] {
+        &self.warnings
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum Geometry {
+    Polygon {
+        coordinates: Vec<Vec<Vec<f64>>>,
+    },
+    MultiPolygon {
+        coordinates: Vec<Vec<Vec<Vec<f64>>>>,
+    },
+}
+
+/// Polygons of a boundary, rings of `(lat, lon)`.
+type Polygons = Vec<Vec<Vec<(f64, f64)>>>;
+
+fn parse_polygons(geometry: &str) -> Option<Polygons> {
+    let polygons = match serde_json::from_str(geometry).ok()? {
+        Geometry::Polygon { coordinates } => vec![coordinates],
+        Geometry::MultiPolygon { coordinates } => coordinates,
+    };
+    Some(
+        polygons
+            .into_iter()
+            .map(|polygon| {
+                polygon
+                    .into_iter()
+                    .map(|ring| {
+                        ring.into_iter()
+                            .filter_map(|position| Some((*position.get(1)?, *position.first()?)))
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect(),
+    )
+}
+
+/// Compares municipalities named by stations with boundaries containing them.
+///
+/// Names of municipalities without a known boundary, e.g. abbreviated ones, are not checked.
+/// Parsed boundaries are kept, so one checker should be used for checking many stations.
+#[derive(Default)]
+pub struct BoundaryChecker {
+    polygons: HashMap<i64, Option<Polygons>>,
+}
+
+impl BoundaryChecker {
+    pub async fn check(
+        &mut self,
+        conn: &mut SqliteConnection,
+        station: &Station,
+    ) -> Result<Vec<BoundaryWarning>, sqlx::Error> {
+        let containing = self.containing(conn, station.lat(), station.lon()).await?;
+        let mut warnings = Vec::new();
+        let mut checked = BTreeSet::new();
+        for name in station.names() {
+            let stop_name = StopName::parse(name);
+            let municipality = stop_name.municipality();
+            let district = stop_name.district();
+            if !checked.insert((municipality.to_string(), district.map(str::to_string))) {
+                continue;
+            }
+            let inside = containing.iter().any(|(name, boundary_district)| {
+                name == municipality
+                    && (district.is_none()
+                        || boundary_district.is_none()
+                        || boundary_district.as_deref() == district)
+            });
+            if inside || !Self::is_known(conn, municipality, district).await? {
+                continue;
+            }
+            warnings.push(BoundaryWarning {
+                stop_id: station.stop_id(),
+                name: name.clone(),
+                municipality: municipality.to_string(),
+                district: district.map(str::to_string),
+                found: containing
+                    .iter()
+                    .map(|(name, _)| name.clone())
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect(),
+            });
+        }
+        Ok(warnings)
+    }
+
+    /// Names and districts of municipalities whose boundaries contain the position.
+    async fn containing(
+        &mut self,
+        conn: &mut SqliteConnection,
+        lat: f64,
+        lon: f64,
+    ) -> Result<Vec<(String, Option<String>)>, sqlx::Error> {
+        let rows = query(
+            "SELECT id, municipality_name, district_code FROM sl_municipality_boundaries
+WHERE id IN (
+    SELECT id FROM sl_municipality_boundaries_rtree
+    WHERE lat_min <= $1 AND $1 <= lat_max AND lon_min <= $2 AND $2 <= lon_max
+)
+ORDER BY id;",
+        )
+        .bind(lat)
+        .bind(lon)
+        .fetch_all(&mut *conn)
+        .await?;
+        let mut containing = Vec::new();
+        for row in rows {
+            let id: i64 = row.try_get(0)?;
+            if let Entry::Vacant(entry) = self.polygons.entry(id) {
+                let geometry: String =
+                    query("SELECT geometry FROM sl_municipality_boundaries WHERE id = $1;")
+                        .bind(id)
+                        .fetch_one(&mut *conn)
+                        .await?
+                        .try_get(0)?;
+                entry.insert(parse_polygons(&geometry));
+            }
+            let inside = self.polygons[&id].as_ref().is_some_and(|polygons| {
+                polygons
+                    .iter()
+                    .any(|polygon| polygon_contains(polygon, lat, lon))
+            });
+            if inside {
+                containing.push((row.try_get(1)?, row.try_get(2)?));
+            }
+        }
+        Ok(containing)
+    }
+
+    async fn is_known(
+        conn: &mut SqliteConnection,
+        municipality: &str,
+        district: Option<&str>,
+    ) -> Result<bool, sqlx::Error> {
+        query(
+            "SELECT EXISTS (
+    SELECT 1 FROM sl_municipality_boundaries WHERE municipality_name = $1
+        AND ($2 IS NULL OR district_code IS NULL OR district_code = $2)
+);",
+        )
+        .bind(municipality)
+        .bind(district)
+        .fetch_one(&mut *conn)
+        .await?
+        .try_get(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::migrated_db;
+    use sqlx::Sqlite;
+    use sqlx::pool::PoolConnection;
+
+    async fn db_with_boundaries() -> PoolConnection<Sqlite> {
+        let mut db = migrated_db().await;
+        query(
+            r#"INSERT INTO sl_municipality_boundaries (municipality_name, district_code, lat_min, lat_max, lon_min, lon_max, geometry) VALUES
+    ('Adamov', 'BK', 49.25, 49.35, 16.6, 16.7, '{"type": "Polygon", "coordinates": [
+        [[16.6, 49.25], [16.7, 49.25], [16.7, 49.35], [16.6, 49.35], [16.6, 49.25]],
+        [[16.64, 49.29], [16.66, 49.29], [16.66, 49.31], [16.64, 49.31], [16.64, 49.29]]
+    ]}'),
+    ('Blansko', 'BK', 49.33, 49.4, 16.55, 16.7, '{"type": "Polygon", "coordinates": [
+        [[16.55, 49.35], [16.7, 49.35], [16.7, 49.4], [16.55, 49.4], [16.55, 49.35]]
+    ]}'),
+    ('Lhota', 'ZL', 49.1, 49.2, 17.5, 17.7, '{"type": "MultiPolygon", "coordinates": [
+        [[[17.5, 49.1], [17.6, 49.1], [17.6, 49.2], [17.5, 49.2], [17.5, 49.1]]],
+        [[[17.65, 49.1], [17.7, 49.1], [17.7, 49.2], [17.65, 49.1]]]
+    ]}'),
+    ('Lhota', 'KD', 50.2, 50.3, 13.9, 14.1, '{"type": "Polygon", "coordinates": [
+        [[13.9, 50.2], [14.1, 50.2], [14.1, 50.3], [13.9, 50.3], [13.9, 50.2]]
+    ]}');"#,
+        )
+        .execute(&mut *db)
+        .await
+        .unwrap();
+        db
+    }
+
+    async fn warnings(
+        db: &mut PoolConnection<Sqlite>,
+        name: &str,
+        lat: f64,
+        lon: f64,
+    ) -> Vec<BoundaryWarning> {
+        let station = Station::new(1, vec![name.to_string()], lat, lon);
+        BoundaryChecker::default()
+            .check(db, &station)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn stations_inside_their_municipality_are_not_reported() {
+        let mut db = db_with_boundaries().await;
+        assert!(
+            warnings(&mut db, "Adamov,žel.st.", 49.27, 16.62)
+                .await
+                .is_empty()
+        );
+        assert!(
+            warnings(&mut db, "Lhota [ZL]", 49.15, 17.55)
+                .await
+                .is_empty()
+        );
+        assert!(warnings(&mut db, "Lhota", 49.15, 17.55).await.is_empty());
+        // the second polygon of a multipolygon
+        assert!(
+            warnings(&mut db, "Lhota,,rozc.", 49.12, 17.69)
+                .await
+                .is_empty()
+        );
+        // municipalities without a boundary are not checked
+        assert!(
+            warnings(&mut db, "Brno,hl.n.", 49.27, 16.62)
+                .await
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn stations_outside_their_municipality_are_reported() {
+        let mut db = db_with_boundaries().await;
+
+        let found = warnings(&mut db, "Adamov,nádr.", 49.37, 16.6).await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name(), "Adamov,nádr.");
+        assert_eq!(found[0].municipality(), "Adamov");
+        assert_eq!(found[0].found(), ["Blansko"]);
+
+        // holes are outside of the boundary
+        let found = warnings(&mut db, "Adamov", 49.3, 16.65).await;
+        assert_eq!(found.len(), 1);
+        assert!(found[0].found().is_empty());
+
+        // outside of the triangle of the second polygon
+        assert_eq!(warnings(&mut db, "Lhota", 49.19, 17.66).await.len(), 1);
+
+        let found = warnings(&mut db, "Lhota [KD]", 49.15, 17.55).await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].district(), Some("KD"));
+        assert_eq!(found[0].found(), ["Lhota"]);
+    }
+}
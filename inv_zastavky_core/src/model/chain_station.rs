@@ -1,10 +1,11 @@
 use crate::model::StopId;
-use serde::Serialize;
+use crate::model::change::ChangeAuthor;
+use serde::{Deserialize, Serialize};
 use sqlx::pool::PoolConnection;
 use sqlx::sqlite::SqliteRow;
-use sqlx::{Row, Sqlite, query};
+use sqlx::{Row, Sqlite, SqliteConnection, query};
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct ChainStation {
     chain_hash: String,
     name: String,
@@ -22,10 +23,22 @@ impl ChainStation {
         }
     }
 
+    pub fn chain_hash(&self) -> &str {
+        &self.chain_hash
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    pub fn pos(&self) -> i32 {
+        self.pos
+    }
+
+    pub fn stop_id(&self) -> Option<StopId> {
+        self.stop_id
+    }
+
     fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
         let chain_hash = row.try_get(0)?;
         let name = row.try_get(1)?;
@@ -76,4 +89,78 @@ ORDER BY sl_chains.chain_hash, sl_chains.pos")
         }
         Ok(stations)
     }
+
+    pub async fn get_position(
+        db: &mut PoolConnection<Sqlite>,
+        chain_hash: &str,
+        pos: i32,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let row = query("SELECT sl_chains.chain_hash, sl_chains.station_name, sl_chains.pos, hl_stations.stop_id FROM sl_chains
+LEFT JOIN hl_stations
+    ON hl_stations.chain_hash = sl_chains.chain_hash AND hl_stations.pos = sl_chains.pos
+WHERE sl_chains.chain_hash = $1 AND sl_chains.pos = $2;")
+            .bind(chain_hash)
+            .bind(pos)
+            .fetch_optional(&mut **db)
+            .await?;
+        row.as_ref().map(Self::from_row).transpose()
+    }
+
+    /// Loads station assigned to the chain position from `hl_stations`.
+    pub async fn get_assignment(
+        conn: &mut SqliteConnection,
+        chain_hash: &str,
+        pos: i32,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let row = query(
+            "SELECT chain_hash, station_name, pos, stop_id FROM hl_stations WHERE chain_hash = $1 AND pos = $2;",
+        )
+        .bind(chain_hash)
+        .bind(pos)
+        .fetch_optional(conn)
+        .await?;
+        row.as_ref().map(Self::from_row).transpose()
+    }
+
+    /// Loads chain positions assigned to the station from `hl_stations`.
+    pub async fn get_by_stop_id(
+        conn: &mut SqliteConnection,
+        stop_id: StopId,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let rows = query(
+            "SELECT chain_hash, station_name, pos, stop_id FROM hl_stations WHERE stop_id = $1 ORDER BY chain_hash, pos;",
+        )
+        .bind(stop_id)
+        .fetch_all(conn)
+        .await?;
+        rows.iter().map(Self::from_row).collect()
+    }
+
+    /// Replaces assignment of the chain position, `None` leaves the position unassigned.
+    pub(crate) async fn restore_assignment(
+        conn: &mut SqliteConnection,
+        author: &ChangeAuthor,
+        chain_hash: &str,
+        pos: i32,
+        state: Option<&Self>,
+    ) -> Result<(), sqlx::Error> {
+        query("DELETE FROM hl_stations WHERE chain_hash = $1 AND pos = $2;")
+            .bind(chain_hash)
+            .bind(pos)
+            .execute(&mut *conn)
+            .await?;
+        if let Some(state) = state {
+            query(
+                "INSERT INTO hl_stations (chain_hash, station_name, pos, stop_id, edited_by, edited_at) VALUES ($1, $2, $3, $4, $5, CURRENT_TIMESTAMP);",
+            )
+            .bind(chain_hash)
+            .bind(&state.name)
+            .bind(pos)
+            .bind(state.stop_id)
+            .bind(author.user_id())
+            .execute(&mut *conn)
+            .await?;
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,625 @@
+use crate::error::Error;
+use crate::model::chain_station::ChainStation;
+use crate::model::station::{Station, StationError};
+use crate::model::{StopId, UserId};
+use serde::Serialize;
+use serde_json::{Value, json};
+use sqlx::pool::PoolConnection;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Connection, Row, Sqlite, SqliteConnection, query};
+
+pub type ChangeId = i64;
+
+/// User making changes, changes made in one session can be reverted together.
+#[derive(Debug, Clone)]
+pub struct ChangeAuthor {
+    user_id: UserId,
+    session_id: Option<String>,
+}
+
+impl ChangeAuthor {
+    pub fn new(user_id: UserId, session_id: Option<String>) -> Self {
+        Self {
+            user_id,
+            session_id,
+        }
+    }
+
+    pub fn user_id(&self) -> UserId {
+        self.user_id
+    }
+
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+}
+
+/// What the change was made to.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeEntity {
+    /// Position and names of a station in `el_station_pos` and `el_station_names`.
+    Station,
+    /// Station assigned to a chain position in `hl_stations`.
+    Assignment,
+}
+
+impl ChangeEntity {
+    fn tag(&self) -> &'static str {
+        match self {
+            ChangeEntity::Station => "station",
+            ChangeEntity::Assignment => "assignment",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "station" => Some(ChangeEntity::Station),
+            "assignment" => Some(ChangeEntity::Assignment),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeAction {
+    Create,
+    Move,
+    AddName,
+    RemoveName,
+    Remove,
+    Assign,
+    Unassign,
+    /// Station was merged into another one.
+    Merge,
+    /// Station was split into several stations.
+    Split,
+    /// Restores the state from before another change.
+    Revert,
+}
+
+impl ChangeAction {
+    fn tag(&self) -> &'static str {
+        match self {
+            ChangeAction::Create => "create",
+            ChangeAction::Move => "move",
+            ChangeAction::AddName => "add_name",
+            ChangeAction::RemoveName => "remove_name",
+            ChangeAction::Remove => "remove",
+            ChangeAction::Assign => "assign",
+            ChangeAction::Unassign => "unassign",
+            ChangeAction::Merge => "merge",
+            ChangeAction::Split => "split",
+            ChangeAction::Revert => "revert",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "create" => Some(ChangeAction::Create),
+            "move" => Some(ChangeAction::Move),
+            "add_name" => Some(ChangeAction::AddName),
+            "remove_name" => Some(ChangeAction::RemoveName),
+            "remove" => Some(ChangeAction::Remove),
+            "assign" => Some(ChangeAction::Assign),
+            "unassign" => Some(ChangeAction::Unassign),
+            "merge" => Some(ChangeAction::Merge),
+            "split" => Some(ChangeAction::Split),
+            "revert" => Some(ChangeAction::Revert),
+            _ => None,
+        }
+    }
+}
+
+/// Entry of the change log with states of the entity before and after the change.
+#[derive(Serialize, Debug)]
+pub struct Change {
+    change_id: ChangeId,
+    changed_at: String,
+    user_id: Option<UserId>,
+    username: Option<String>,
+    session_id: Option<String>,
+    entity: ChangeEntity,
+    action: ChangeAction,
+    stop_id: Option<StopId>,
+    chain_hash: Option<String>,
+    pos: Option<i32>,
+    before: Option<Value>,
+    after: Option<Value>,
+    reverted_by: Option<ChangeId>,
+}
+
+const CHANGE_COLUMNS: &str = "change_id, changed_at, user_id, username, session_id, entity, action, stop_id, chain_hash, pos, before, after, reverted_by";
+
+fn decode_error(message: String) -> sqlx::Error {
+    sqlx::Error::Decode(message.into())
+}
+
+fn parse_snapshot(snapshot: Option<String>) -> Result<Option<Value>, sqlx::Error> {
+    snapshot
+        .map(|snapshot| serde_json::from_str(&snapshot))
+        .transpose()
+        .map_err(|err| decode_error(format!("malformed change snapshot: {}", err)))
+}
+
+/// Snapshot of a station, names are sorted so equal stations have equal snapshots.
+fn station_snapshot(station: &Station) -> Value {
+    let mut names = station.names().to_vec();
+    names.sort();
+    json!({
+        "stop_id": station.stop_id(),
+        "names": names,
+        "lat": station.lat(),
+        "lon": station.lon(),
+    })
+}
+
+fn assignment_snapshot(assignment: &ChainStation) -> Value {
+    json!({
+        "chain_hash": assignment.chain_hash(),
+        "name": assignment.name(),
+        "pos": assignment.pos(),
+        "stop_id": assignment.stop_id(),
+    })
+}
+
+impl Change {
+    pub fn change_id(&self) -> ChangeId {
+        self.change_id
+    }
+
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        let entity: &str = row.try_get(5)?;
+        let action: &str = row.try_get(6)?;
+        Ok(Change {
+            change_id: row.try_get(0)?,
+            changed_at: row.try_get(1)?,
+            user_id: row.try_get(2)?,
+            username: row.try_get(3)?,
+            session_id: row.try_get(4)?,
+            entity: ChangeEntity::from_tag(entity)
+                .ok_or_else(|| decode_error(format!("unknown change entity {}", entity)))?,
+            action: ChangeAction::from_tag(action)
+                .ok_or_else(|| decode_error(format!("unknown change action {}", action)))?,
+            stop_id: row.try_get(7)?,
+            chain_hash: row.try_get(8)?,
+            pos: row.try_get(9)?,
+            before: parse_snapshot(row.try_get(10)?)?,
+            after: parse_snapshot(row.try_get(11)?)?,
+            reverted_by: row.try_get(12)?,
+        })
+    }
+
+    async fn find(
+        conn: &mut SqliteConnection,
+        change_id: ChangeId,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let row = query(&format!(
+            "SELECT {} FROM el_changes LEFT JOIN users USING (user_id) WHERE change_id = $1;",
+            CHANGE_COLUMNS
+        ))
+        .bind(change_id)
+        .fetch_optional(conn)
+        .await?;
+        row.as_ref().map(Self::from_row).transpose()
+    }
+
+    /// Changes of the station and of assignments from or to it, newest first.
+    pub async fn get_by_stop_id(
+        db: &mut PoolConnection<Sqlite>,
+        stop_id: StopId,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let rows = query(&format!(
+            "SELECT {} FROM el_changes LEFT JOIN users USING (user_id)
+WHERE stop_id = $1 OR json_extract(before, '$.stop_id') = $1
+ORDER BY change_id DESC;",
+            CHANGE_COLUMNS
+        ))
+        .bind(stop_id)
+        .fetch_all(&mut **db)
+        .await?;
+        rows.iter().map(Self::from_row).collect()
+    }
+
+    /// Changes made in the session, newest first.
+    pub async fn get_by_session(
+        db: &mut PoolConnection<Sqlite>,
+        session_id: &str,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let rows = query(&format!(
+            "SELECT {} FROM el_changes LEFT JOIN users USING (user_id) WHERE session_id = $1
+ORDER BY change_id DESC;",
+            CHANGE_COLUMNS
+        ))
+        .bind(session_id)
+        .fetch_all(&mut **db)
+        .await?;
+        rows.iter().map(Self::from_row).collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn record(
+        conn: &mut SqliteConnection,
+        author: &ChangeAuthor,
+        entity: ChangeEntity,
+        action: ChangeAction,
+        stop_id: Option<StopId>,
+        position: Option<(&str, i32)>,
+        before: Option<Value>,
+        after: Option<Value>,
+    ) -> Result<ChangeId, sqlx::Error> {
+        let row = query(
+            "INSERT INTO el_changes (user_id, session_id, entity, action, stop_id, chain_hash, pos, before, after)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING change_id;",
+        )
+        .bind(author.user_id())
+        .bind(author.session_id())
+        .bind(entity.tag())
+        .bind(action.tag())
+        .bind(stop_id)
+        .bind(position.map(|(chain_hash, _)| chain_hash))
+        .bind(position.map(|(_, pos)| pos))
+        .bind(before.map(|before| before.to_string()))
+        .bind(after.map(|after| after.to_string()))
+        .fetch_one(conn)
+        .await?;
+        row.try_get(0)
+    }
+
+    /// Logs change of a station, `None` stands for a station which does not exist.
+    pub async fn record_station(
+        conn: &mut SqliteConnection,
+        author: &ChangeAuthor,
+        action: ChangeAction,
+        stop_id: StopId,
+        before: Option<&Station>,
+        after: Option<&Station>,
+    ) -> Result<ChangeId, sqlx::Error> {
+        Self::record(
+            conn,
+            author,
+            ChangeEntity::Station,
+            action,
+            Some(stop_id),
+            None,
+            before.map(station_snapshot),
+            after.map(station_snapshot),
+        )
+        .await
+    }
+
+    /// Logs change of a chain position assignment, `None` stands for an unassigned position.
+    pub async fn record_assignment(
+        conn: &mut SqliteConnection,
+        author: &ChangeAuthor,
+        action: ChangeAction,
+        chain_hash: &str,
+        pos: i32,
+        before: Option<&ChainStation>,
+        after: Option<&ChainStation>,
+    ) -> Result<ChangeId, sqlx::Error> {
+        let stop_id = after.or(before).and_then(|assignment| assignment.stop_id());
+        Self::record(
+            conn,
+            author,
+            ChangeEntity::Assignment,
+            action,
+            stop_id,
+            Some((chain_hash, pos)),
+            before.map(assignment_snapshot),
+            after.map(assignment_snapshot),
+        )
+        .await
+    }
+
+    /// Restores the state from before the change, if nothing changed the entity since then.
+    pub async fn revert(
+        conn: &mut SqliteConnection,
+        author: &ChangeAuthor,
+        change_id: ChangeId,
+    ) -> Result<Self, Error> {
+        let mut tx = conn.begin().await?;
+        let change = Self::revert_one(&mut tx, author, change_id).await?;
+        tx.commit().await?;
+        Ok(change)
+    }
+
+    /// Reverts all changes of the session which were not reverted yet, newest first.
+    ///
+    /// Nothing is reverted if any of the changes can not be reverted.
+    pub async fn revert_session(
+        conn: &mut SqliteConnection,
+        author: &ChangeAuthor,
+        session_id: &str,
+    ) -> Result<Vec<Self>, Error> {
+        let mut tx = conn.begin().await?;
+        let rows = query(
+            "SELECT change_id FROM el_changes WHERE session_id = $1 AND reverted_by IS NULL
+ORDER BY change_id DESC;",
+        )
+        .bind(session_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        if rows.is_empty() {
+            return Err(Error::not_found(
+                "session_not_found",
+                format!("no changes to revert in session {}", session_id),
+            ));
+        }
+        let mut changes = Vec::new();
+        for row in rows {
+            changes.push(Self::revert_one(&mut tx, author, row.try_get(0)?).await?);
+        }
+        tx.commit().await?;
+        Ok(changes)
+    }
+
+    async fn revert_one(
+        conn: &mut SqliteConnection,
+        author: &ChangeAuthor,
+        change_id: ChangeId,
+    ) -> Result<Self, Error> {
+        let change = Self::find(conn, change_id).await?.ok_or_else(|| {
+            Error::not_found("change_not_found", format!("no change {}", change_id))
+        })?;
+        if let Some(reverted_by) = change.reverted_by {
+            return Err(Error::conflict(
+                "change_already_reverted",
+                format!("change {} was reverted by {}", change_id, reverted_by),
+            ));
+        }
+        let superseded = || {
+            Error::conflict(
+                "change_superseded",
+                format!("change {} was followed by other changes", change_id),
+            )
+        };
+        let revert_id = match change.entity {
+            ChangeEntity::Station => {
+                let stop_id = change
+                    .stop_id
+                    .ok_or_else(|| decode_error(format!("change {} has no stop id", change_id)))?;
+                let current = Station::find(conn, stop_id).await?;
+                if current.as_ref().map(station_snapshot) != change.after {
+                    return Err(superseded());
+                }
+                let target: Option<Station> = change
+                    .before
+                    .map(serde_json::from_value)
+                    .transpose()
+                    .map_err(|err| decode_error(format!("malformed station snapshot: {}", err)))?;
+                if target.is_none() {
+                    let assignments = ChainStation::get_by_stop_id(conn, stop_id).await?;
+                    if !assignments.is_empty() {
+                        return Err(StationError::Assigned {
+                            stop_id,
+                            positions: assignments.len(),
+                        }
+                        .into());
+                    }
+                }
+                Station::restore(conn, author, stop_id, target.as_ref()).await?;
+                Self::record_station(
+                    conn,
+                    author,
+                    ChangeAction::Revert,
+                    stop_id,
+                    current.as_ref(),
+                    target.as_ref(),
+                )
+                .await?
+            }
+            ChangeEntity::Assignment => {
+                let (Some(chain_hash), Some(pos)) = (change.chain_hash.as_deref(), change.pos)
+                else {
+                    return Err(
+                        decode_error(format!("change {} has no position", change_id)).into(),
+                    );
+                };
+                let current = ChainStation::get_assignment(conn, chain_hash, pos).await?;
+                if current.as_ref().map(assignment_snapshot) != change.after {
+                    return Err(superseded());
+                }
+                let target: Option<ChainStation> = change
+                    .before
+                    .map(serde_json::from_value)
+                    .transpose()
+                    .map_err(|err| {
+                        decode_error(format!("malformed assignment snapshot: {}", err))
+                    })?;
+                ChainStation::restore_assignment(conn, author, chain_hash, pos, target.as_ref())
+                    .await?;
+                Self::record_assignment(
+                    conn,
+                    author,
+                    ChangeAction::Revert,
+                    chain_hash,
+                    pos,
+                    current.as_ref(),
+                    target.as_ref(),
+                )
+                .await?
+            }
+        };
+        query("UPDATE el_changes SET reverted_by = $2 WHERE change_id = $1;")
+            .bind(change_id)
+            .bind(revert_id)
+            .execute(&mut *conn)
+            .await?;
+        Ok(Self::find(conn, revert_id)
+            .await?
+            .ok_or_else(|| decode_error(format!("missing change {}", revert_id)))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::station::DeletePolicy;
+    use crate::testing::{editor, migrated_db};
+
+    fn actions(changes: &[Change]) -> Vec<ChangeAction> {
+        changes.iter().map(|change| change.action).collect()
+    }
+
+    #[tokio::test]
+    async fn history_lists_changes_newest_first() {
+        let mut db = migrated_db().await;
+        let author = editor(&mut db).await;
+        let stop_id = Station::create_station(&mut db, &author, 49.3, 16.66, "Adamov")
+            .await
+            .unwrap()
+            .stop_id();
+        Station::move_station(&mut db, &author, stop_id, 49.29, 16.65)
+            .await
+            .unwrap();
+        let other = ChangeAuthor::new(author.user_id(), Some("s2".to_string()));
+        Station::add_name(&mut db, &other, stop_id, "Adamov,žel.st.")
+            .await
+            .unwrap();
+
+        let history = Change::get_by_stop_id(&mut db, stop_id).await.unwrap();
+        assert_eq!(
+            actions(&history),
+            [
+                ChangeAction::AddName,
+                ChangeAction::Move,
+                ChangeAction::Create
+            ]
+        );
+        assert_eq!(history[0].username.as_deref(), Some("editor"));
+        assert_eq!(history[1].before.as_ref().unwrap()["lat"], 49.3);
+        assert_eq!(history[1].after.as_ref().unwrap()["lat"], 49.29);
+        assert!(history[2].before.is_none());
+
+        let session = Change::get_by_session(&mut db, "s1").await.unwrap();
+        assert_eq!(
+            actions(&session),
+            [ChangeAction::Move, ChangeAction::Create]
+        );
+        assert!(
+            Change::get_by_session(&mut db, "s3")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn revert_restores_state_from_before_the_change() {
+        let mut db = migrated_db().await;
+        let author = editor(&mut db).await;
+        let stop_id = Station::create_station(&mut db, &author, 49.3, 16.66, "Adamov")
+            .await
+            .unwrap()
+            .stop_id();
+        Station::move_station(&mut db, &author, stop_id, 49.29, 16.65)
+            .await
+            .unwrap();
+        let moved = Change::get_by_stop_id(&mut db, stop_id).await.unwrap()[0].change_id();
+
+        let revert = Change::revert(&mut db, &author, moved).await.unwrap();
+        assert_eq!(revert.action, ChangeAction::Revert);
+        let station = Station::get_by_id(&mut db, stop_id).await.unwrap().unwrap();
+        assert_eq!((station.lat(), station.lon()), (49.3, 16.66));
+        let history = Change::get_by_stop_id(&mut db, stop_id).await.unwrap();
+        assert_eq!(history[1].reverted_by, Some(revert.change_id()));
+
+        let err = Change::revert(&mut db, &author, moved).await.unwrap_err();
+        assert_eq!(err.code(), "change_already_reverted");
+        let err = Change::revert(&mut db, &author, revert.change_id() + 1)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "change_not_found");
+    }
+
+    #[tokio::test]
+    async fn superseded_changes_are_not_reverted() {
+        let mut db = migrated_db().await;
+        let author = editor(&mut db).await;
+        let stop_id = Station::create_station(&mut db, &author, 49.3, 16.66, "Adamov")
+            .await
+            .unwrap()
+            .stop_id();
+        Station::move_station(&mut db, &author, stop_id, 49.29, 16.65)
+            .await
+            .unwrap();
+        Station::move_station(&mut db, &author, stop_id, 49.28, 16.64)
+            .await
+            .unwrap();
+        let first_move = Change::get_by_stop_id(&mut db, stop_id).await.unwrap()[1].change_id();
+
+        let err = Change::revert(&mut db, &author, first_move)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "change_superseded");
+        let station = Station::get_by_id(&mut db, stop_id).await.unwrap().unwrap();
+        assert_eq!((station.lat(), station.lon()), (49.28, 16.64));
+    }
+
+    #[tokio::test]
+    async fn created_station_with_assignments_is_not_reverted() {
+        let mut db = migrated_db().await;
+        let author = editor(&mut db).await;
+        let stop_id = Station::create_station(&mut db, &author, 49.3, 16.66, "Adamov")
+            .await
+            .unwrap()
+            .stop_id();
+        query(
+            "INSERT INTO hl_stations (chain_hash, station_name, pos, stop_id) VALUES ('h1', 'Adamov', 0, $1);",
+        )
+        .bind(stop_id)
+        .execute(&mut *db)
+        .await
+        .unwrap();
+        let created = Change::get_by_stop_id(&mut db, stop_id).await.unwrap()[0].change_id();
+
+        let err = Change::revert(&mut db, &author, created).await.unwrap_err();
+        assert_eq!(err.code(), "station_assigned");
+        assert!(
+            Station::get_by_id(&mut db, stop_id)
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn session_is_reverted_as_a_whole() {
+        let mut db = migrated_db().await;
+        let author = editor(&mut db).await;
+        let stop_id = Station::create_station(&mut db, &author, 49.3, 16.66, "Adamov")
+            .await
+            .unwrap()
+            .stop_id();
+        query(
+            "INSERT INTO hl_stations (chain_hash, station_name, pos, stop_id) VALUES ('h1', 'Adamov', 0, $1);",
+        )
+        .bind(stop_id)
+        .execute(&mut *db)
+        .await
+        .unwrap();
+        let session = ChangeAuthor::new(author.user_id(), Some("s2".to_string()));
+        Station::remove_station(&mut db, &session, stop_id, DeletePolicy::Unassign)
+            .await
+            .unwrap();
+
+        let reverted = Change::revert_session(&mut db, &author, "s2")
+            .await
+            .unwrap();
+        assert_eq!(reverted.len(), 2);
+        let station = Station::get_by_id(&mut db, stop_id).await.unwrap().unwrap();
+        assert_eq!(station.names(), ["Adamov"]);
+        let assignment = ChainStation::get_assignment(&mut db, "h1", 0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(assignment.stop_id(), Some(stop_id));
+
+        let err = Change::revert_session(&mut db, &author, "s2")
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "session_not_found");
+    }
+}
@@ -0,0 +1,202 @@
+use crate::model::bbox::BBox;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::f64::consts::{FRAC_PI_4, PI};
+
+/// Largest number of points returned for a bbox, more points are clustered.
+pub const MAX_POINTS: usize = 500;
+
+/// Highest zoom level with distinct grid cells.
+const MAX_ZOOM: u8 = 24;
+
+/// Grid cells are this many times smaller than a map tile.
+const CELLS_PER_TILE: f64 = 4.0;
+
+/// Latitude limit of the Web Mercator projection.
+const MAX_LAT: f64 = 85.051_128_78;
+
+/// Points close to each other on the map.
+#[derive(Serialize, Debug, Clone)]
+pub struct Cluster {
+    count: usize,
+    /// Centroid of the points.
+    lat: f64,
+    lon: f64,
+    bbox: BBox,
+}
+
+impl Cluster {
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn lat(&self) -> f64 {
+        self.lat
+    }
+
+    pub fn lon(&self) -> f64 {
+        self.lon
+    }
+
+    pub fn bbox(&self) -> &BBox {
+        &self.bbox
+    }
+}
+
+/// Points of a bbox, or their clusters when there are more than [`MAX_POINTS`] of them.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Clustered<T> {
+    Points(Vec<T>),
+    Clusters(Vec<Cluster>),
+}
+
+/// Groups points by cells of a grid which are square on a Web Mercator map of the zoom level.
+pub struct Grid {
+    /// Cell size in radians of longitude.
+    cell: f64,
+    cells: BTreeMap<(i64, i64), Cell>,
+}
+
+struct Cell {
+    count: usize,
+    lat_sum: f64,
+    lon_sum: f64,
+    bbox: BBox,
+}
+
+impl Grid {
+    pub fn new(zoom: u8) -> Self {
+        let tiles = f64::powi(2.0, i32::from(zoom.min(MAX_ZOOM)));
+        Self {
+            cell: 2.0 * PI / tiles / CELLS_PER_TILE,
+            cells: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, lat: f64, lon: f64) {
+        let y = f64::tan(FRAC_PI_4 + lat.clamp(-MAX_LAT, MAX_LAT).to_radians() / 2.0).ln();
+        let key = (
+            (y / self.cell).floor() as i64,
+            (lon.to_radians() / self.cell).floor() as i64,
+        );
+        let cell = self.cells.entry(key).or_insert_with(|| Cell {
+            count: 0,
+            lat_sum: 0.0,
+            lon_sum: 0.0,
+            bbox: BBox::point(lat, lon),
+        });
+        cell.count += 1;
+        cell.lat_sum += lat;
+        cell.lon_sum += lon;
+        cell.bbox.extend(lat, lon);
+    }
+
+    /// Non-empty cells ordered from south to north and from west to east.
+    pub fn clusters(self) -> Vec<Cluster> {
+        self.cells
+            .into_values()
+            .map(|cell| Cluster {
+                count: cell.count,
+                lat: cell.lat_sum / cell.count as f64,
+                lon: cell.lon_sum / cell.count as f64,
+                bbox: cell.bbox,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::station::Station;
+    use crate::testing::migrated_db;
+    use sqlx::query;
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn close_points_share_a_cluster() {
+        let mut grid = Grid::new(8);
+        grid.add(49.31, 16.62);
+        grid.add(50.08, 14.43);
+        grid.add(49.30, 16.60);
+
+        let clusters = grid.clusters();
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].count(), 2);
+        assert_near(clusters[0].lat(), 49.305);
+        assert_near(clusters[0].lon(), 16.61);
+        let bbox = clusters[0].bbox();
+        assert_eq!(
+            (
+                bbox.lat_from(),
+                bbox.lat_to(),
+                bbox.lon_from(),
+                bbox.lon_to()
+            ),
+            (49.30, 49.31, 16.60, 16.62)
+        );
+        assert_eq!(clusters[1].count(), 1);
+        assert_eq!((clusters[1].lat(), clusters[1].lon()), (50.08, 14.43));
+    }
+
+    #[test]
+    fn higher_zoom_levels_split_clusters() {
+        for (zoom, count) in [(8, 1), (16, 2), (30, 2)] {
+            let mut grid = Grid::new(zoom);
+            grid.add(49.30, 16.60);
+            grid.add(49.31, 16.62);
+            assert_eq!(grid.clusters().len(), count, "zoom {}", zoom);
+        }
+    }
+
+    #[test]
+    fn poles_are_clamped_to_the_map() {
+        let mut grid = Grid::new(0);
+        grid.add(90.0, 180.0);
+        grid.add(-90.0, -180.0);
+        let clusters = grid.clusters();
+        assert_eq!(clusters.len(), 2);
+        assert_eq!((clusters[0].lat(), clusters[1].lat()), (-90.0, 90.0));
+    }
+
+    #[tokio::test]
+    async fn stations_are_clustered_above_max_points() {
+        let mut db = migrated_db().await;
+        let bbox = BBox::new([49.0, 50.0], [16.0, 17.0]);
+        for (stations, clustered) in [(MAX_POINTS, false), (MAX_POINTS + 1, true)] {
+            query(
+                "DELETE FROM el_station_names;
+DELETE FROM el_station_pos;
+WITH RECURSIVE ids(stop_id) AS (SELECT 1 UNION ALL SELECT stop_id + 1 FROM ids WHERE stop_id < $1)
+INSERT INTO el_station_pos (stop_id, lat, lon) SELECT stop_id, 49.5, 16.0 + stop_id / 1000.0 FROM ids;
+INSERT INTO el_station_names (stop_id, station_name) SELECT stop_id, 'Adamov' FROM el_station_pos;",
+            )
+            .bind(stations as i64)
+            .execute(&mut *db)
+            .await
+            .unwrap();
+
+            match Station::get_clustered(&mut db, &bbox, 10).await.unwrap() {
+                Clustered::Points(points) => {
+                    assert!(!clustered);
+                    assert_eq!(points.len(), stations);
+                }
+                Clustered::Clusters(clusters) => {
+                    assert!(clustered);
+                    let count: usize = clusters.iter().map(Cluster::count).sum();
+                    assert_eq!(count, stations);
+                    assert!(clusters.len() < stations);
+                }
+            }
+        }
+    }
+}
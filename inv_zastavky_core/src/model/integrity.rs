@@ -0,0 +1,123 @@
+use serde::Serialize;
+use sqlx::{Connection, SqliteConnection, query};
+
+/// Rows fixed by [`repair`], databases from before references between `hl_stations` and the
+/// station tables were enforced may contain them.
+#[derive(Serialize, Debug, Default)]
+pub struct Repair {
+    /// Stations without names which got the names of their chain positions.
+    named_stations: u64,
+    /// Positions of stations without names and chain positions.
+    removed_positions: u64,
+    /// Chain positions assigned to stations which do not exist.
+    unassigned_positions: u64,
+    /// Names of stations without position.
+    removed_names: u64,
+}
+
+impl Repair {
+    pub fn named_stations(&self) -> u64 {
+        self.named_stations
+    }
+
+    pub fn removed_positions(&self) -> u64 {
+        self.removed_positions
+    }
+
+    pub fn unassigned_positions(&self) -> u64 {
+        self.unassigned_positions
+    }
+
+    pub fn removed_names(&self) -> u64 {
+        self.removed_names
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.named_stations == 0
+            && self.removed_positions == 0
+            && self.unassigned_positions == 0
+            && self.removed_names == 0
+    }
+}
+
+/// Removes dangling references between `hl_stations`, `el_station_pos` and `el_station_names`.
+///
+/// R*Tree and full-text indexes are rebuilt as well, in case they got out of sync with their
+/// tables.
+///
+/// With `dry_run` nothing is changed and only the counts are returned.
+pub async fn repair(conn: &mut SqliteConnection, dry_run: bool) -> Result<Repair, sqlx::Error> {
+    let mut tx = conn.begin().await?;
+    let named_stations = query(
+        "INSERT OR IGNORE INTO el_station_names (stop_id, station_name)
+    SELECT DISTINCT hl_stations.stop_id, hl_stations.station_name FROM hl_stations
+    JOIN el_station_pos USING (stop_id)
+    WHERE NOT EXISTS (SELECT 1 FROM el_station_names WHERE el_station_names.stop_id = hl_stations.stop_id);",
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    let removed_positions = query(
+        "DELETE FROM el_station_pos
+WHERE NOT EXISTS (SELECT 1 FROM el_station_names WHERE el_station_names.stop_id = el_station_pos.stop_id);",
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    let unassigned_positions = query(
+        "DELETE FROM hl_stations WHERE stop_id IS NOT NULL
+    AND NOT EXISTS (SELECT 1 FROM el_station_pos WHERE el_station_pos.stop_id = hl_stations.stop_id);",
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    let removed_names = query(
+        "DELETE FROM el_station_names
+WHERE NOT EXISTS (SELECT 1 FROM el_station_pos WHERE el_station_pos.stop_id = el_station_names.stop_id);",
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    rebuild_indexes(&mut tx).await?;
+    if dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+    }
+    Ok(Repair {
+        named_stations,
+        removed_positions,
+        unassigned_positions,
+        removed_names,
+    })
+}
+
+async fn rebuild_indexes(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    query(
+        "DELETE FROM el_station_pos_rtree;
+INSERT INTO el_station_pos_rtree (stop_id, lat_min, lat_max, lon_min, lon_max)
+    SELECT stop_id, lat, lat, lon, lon FROM el_station_pos
+    WHERE stop_id IS NOT NULL AND lat IS NOT NULL AND lon IS NOT NULL;
+DELETE FROM sl_base_stations_rtree;
+INSERT INTO sl_base_stations_rtree (id, lat_min, lat_max, lon_min, lon_max)
+    SELECT id, lat, lat, lon, lon FROM sl_base_stations WHERE lat IS NOT NULL AND lon IS NOT NULL;
+DELETE FROM sl_netex_stations_rtree;
+INSERT INTO sl_netex_stations_rtree (id, lat_min, lat_max, lon_min, lon_max)
+    SELECT id, lat, lat, lon, lon FROM sl_netex_stations WHERE lat IS NOT NULL AND lon IS NOT NULL;
+DELETE FROM sl_gtfs_stations_rtree;
+INSERT INTO sl_gtfs_stations_rtree (id, lat_min, lat_max, lon_min, lon_max)
+    SELECT id, lat, lat, lon, lon FROM sl_gtfs_stations WHERE lat IS NOT NULL AND lon IS NOT NULL;
+DELETE FROM sl_base_cities_rtree;
+INSERT INTO sl_base_cities_rtree (id, lat_min, lat_max, lon_min, lon_max)
+    SELECT id, lat, lat, lon, lon FROM sl_base_cities WHERE lat IS NOT NULL AND lon IS NOT NULL;
+DELETE FROM sl_municipality_boundaries_rtree;
+INSERT INTO sl_municipality_boundaries_rtree (id, lat_min, lat_max, lon_min, lon_max)
+    SELECT id, lat_min, lat_max, lon_min, lon_max FROM sl_municipality_boundaries;
+INSERT INTO el_station_names_fts (el_station_names_fts) VALUES ('rebuild');
+INSERT INTO sl_base_cities_fts (sl_base_cities_fts) VALUES ('rebuild');
+INSERT INTO sl_chains_fts (sl_chains_fts) VALUES ('rebuild');",
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
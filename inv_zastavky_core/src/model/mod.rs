@@ -1,8 +1,16 @@
 pub type StopId = i32;
+pub type UserId = i64;
 
 pub mod base_city;
 pub mod base_station;
 pub mod bbox;
+pub mod boundary;
 pub mod chain_station;
+pub mod change;
+pub mod cluster;
+pub mod integrity;
+pub mod split;
 pub mod station;
 pub mod stats;
+pub mod stop_id;
+pub mod user;
@@ -0,0 +1,488 @@
+use crate::model::StopId;
+use crate::model::chain_station::ChainStation;
+use crate::model::change::{Change, ChangeAction, ChangeAuthor};
+use crate::model::station::{Station, StationError};
+use crate::model::stop_id;
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection, Row, SqliteConnection, query};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Chain position selected by its chain hash and position in the chain.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChainPosition {
+    chain_hash: String,
+    pos: i32,
+}
+
+impl ChainPosition {
+    pub fn new(chain_hash: String, pos: i32) -> Self {
+        Self { chain_hash, pos }
+    }
+
+    pub fn chain_hash(&self) -> &str {
+        &self.chain_hash
+    }
+
+    pub fn pos(&self) -> i32 {
+        self.pos
+    }
+}
+
+/// New station split off an existing one.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SplitPart {
+    lat: f64,
+    lon: f64,
+    /// Names moved from the split station, without names the new station gets copies of all of
+    /// them.
+    #[serde(default)]
+    names: Vec<String>,
+    /// Chain positions moved from the split station.
+    #[serde(default)]
+    positions: Vec<ChainPosition>,
+    /// Chain positions of the split station next to a position of any of these stations are
+    /// moved.
+    #[serde(default)]
+    neighbours: Vec<StopId>,
+}
+
+impl SplitPart {
+    pub fn new(
+        lat: f64,
+        lon: f64,
+        names: Vec<String>,
+        positions: Vec<ChainPosition>,
+        neighbours: Vec<StopId>,
+    ) -> Self {
+        Self {
+            lat,
+            lon,
+            names,
+            positions,
+            neighbours,
+        }
+    }
+}
+
+/// Outcome of a split, the split station and the new stations.
+#[derive(Serialize, Debug)]
+pub struct Split {
+    station: Station,
+    parts: Vec<Station>,
+    assignments: Vec<ChainStation>,
+}
+
+impl Split {
+    pub fn station(&self) -> &Station {
+        &self.station
+    }
+
+    pub fn parts(&self) -> &[Station] {
+        &self.parts
+    }
+
+    /// Chain positions assigned to the new stations.
+    pub fn assignments(&self) -> &[ChainStation] {
+        &self.assignments
+    }
+}
+
+/// Remembers which part a chain position goes to, each position may go to one part only.
+fn select(
+    selected: &mut BTreeMap<(String, i32), usize>,
+    chain_hash: String,
+    pos: i32,
+    part: usize,
+) -> Result<(), StationError> {
+    match selected.entry((chain_hash, pos)) {
+        Entry::Vacant(entry) => {
+            entry.insert(part);
+        }
+        Entry::Occupied(entry) => {
+            if *entry.get() != part {
+                let (chain_hash, pos) = entry.key().clone();
+                return Err(StationError::AmbiguousPosition { chain_hash, pos });
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Station {
+    /// Splits station into the station and new stations created from `parts`.
+    ///
+    /// With `dry_run` the split is rolled back and only previewed, stop ids of the previewed
+    /// stations are not reserved.
+    pub async fn split(
+        conn: &mut SqliteConnection,
+        author: &ChangeAuthor,
+        stop_id: StopId,
+        parts: &[SplitPart],
+        dry_run: bool,
+    ) -> Result<Split, StationError> {
+        if parts.is_empty() {
+            return Err(StationError::EmptySplit(stop_id));
+        }
+        for part in parts {
+            Station::check_position(part.lat, part.lon)?;
+            for name in &part.names {
+                Station::check_name(name)?;
+            }
+        }
+        let mut tx = conn.begin().await?;
+        let before = Station::require(&mut tx, stop_id).await?;
+
+        let mut moved_names = BTreeSet::new();
+        for name in parts.iter().flat_map(|part| &part.names) {
+            if !before.names().contains(name) {
+                return Err(StationError::UnknownName {
+                    stop_id,
+                    name: name.clone(),
+                });
+            }
+            moved_names.insert(name.as_str());
+        }
+        if moved_names.len() == before.names().len() {
+            return Err(StationError::LastName(stop_id));
+        }
+
+        let mut selected = BTreeMap::new();
+        for (index, part) in parts.iter().enumerate() {
+            for position in &part.positions {
+                let assignment =
+                    ChainStation::get_assignment(&mut tx, &position.chain_hash, position.pos)
+                        .await?;
+                if assignment.and_then(|assignment| assignment.stop_id()) != Some(stop_id) {
+                    return Err(StationError::UnassignedPosition {
+                        stop_id,
+                        chain_hash: position.chain_hash.clone(),
+                        pos: position.pos,
+                    });
+                }
+                select(
+                    &mut selected,
+                    position.chain_hash.clone(),
+                    position.pos,
+                    index,
+                )?;
+            }
+            if part.neighbours.is_empty() {
+                continue;
+            }
+            let neighbours: Vec<String> = part.neighbours.iter().map(StopId::to_string).collect();
+            let rows = query(
+                "SELECT DISTINCT assigned.chain_hash, assigned.pos FROM hl_stations AS assigned
+    JOIN hl_stations AS neighbour
+        ON neighbour.chain_hash = assigned.chain_hash AND abs(neighbour.pos - assigned.pos) == 1
+WHERE assigned.stop_id == $1 AND neighbour.stop_id IN (SELECT value FROM json_each($2))
+ORDER BY assigned.chain_hash, assigned.pos;",
+            )
+            .bind(stop_id)
+            .bind(format!("[{}]", neighbours.join(",")))
+            .fetch_all(&mut *tx)
+            .await?;
+            for row in rows {
+                select(&mut selected, row.try_get(0)?, row.try_get(1)?, index)?;
+            }
+        }
+
+        let mut part_ids = Vec::new();
+        for part in parts {
+            let part_id = stop_id::allocate(&mut tx).await?;
+            query(
+                "INSERT INTO el_station_pos (stop_id, lat, lon, edited_by, edited_at) VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP);",
+            )
+            .bind(part_id)
+            .bind(part.lat)
+            .bind(part.lon)
+            .bind(author.user_id())
+            .execute(&mut *tx)
+            .await?;
+            let names = if part.names.is_empty() {
+                before.names()
+            } else {
+                &part.names[..]
+            };
+            for name in names {
+                query(
+                    "INSERT OR IGNORE INTO el_station_names (stop_id, station_name, edited_by, edited_at) VALUES ($1, $2, $3, CURRENT_TIMESTAMP);",
+                )
+                .bind(part_id)
+                .bind(name)
+                .bind(author.user_id())
+                .execute(&mut *tx)
+                .await?;
+            }
+            part_ids.push(part_id);
+        }
+
+        let mut moved = Vec::new();
+        for ((chain_hash, pos), index) in selected {
+            let part_id = part_ids[index];
+            let assignment_before = ChainStation::get_assignment(&mut tx, &chain_hash, pos).await?;
+            // the chain name stays resolvable to the station it is assigned to
+            query(
+                "UPDATE hl_stations SET stop_id = $3, edited_by = $4, edited_at = CURRENT_TIMESTAMP WHERE chain_hash = $1 AND pos = $2;
+INSERT OR IGNORE INTO el_station_names (stop_id, station_name, edited_by, edited_at)
+    SELECT $3, station_name, $4, CURRENT_TIMESTAMP FROM hl_stations WHERE chain_hash = $1 AND pos = $2;",
+            )
+            .bind(&chain_hash)
+            .bind(pos)
+            .bind(part_id)
+            .bind(author.user_id())
+            .execute(&mut *tx)
+            .await?;
+            let assignment_after = ChainStation::get_assignment(&mut tx, &chain_hash, pos).await?;
+            moved.push((chain_hash, pos, assignment_before, assignment_after));
+        }
+
+        // new stations are logged before their assignments, so reverting in reverse order
+        // unassigns them before removing them
+        let mut created = Vec::new();
+        for part_id in part_ids {
+            let station = Station::require(&mut tx, part_id).await?;
+            Change::record_station(
+                &mut tx,
+                author,
+                ChangeAction::Split,
+                part_id,
+                None,
+                Some(&station),
+            )
+            .await?;
+            created.push(station);
+        }
+        let mut assignments = Vec::new();
+        for (chain_hash, pos, assignment_before, assignment_after) in moved {
+            Change::record_assignment(
+                &mut tx,
+                author,
+                ChangeAction::Split,
+                &chain_hash,
+                pos,
+                assignment_before.as_ref(),
+                assignment_after.as_ref(),
+            )
+            .await?;
+            assignments.extend(assignment_after);
+        }
+
+        if !moved_names.is_empty() {
+            for name in &moved_names {
+                query("DELETE FROM el_station_names WHERE stop_id == $1 AND station_name == $2;")
+                    .bind(stop_id)
+                    .bind(name)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            query(
+                "UPDATE el_station_pos SET edited_by = $2, edited_at = CURRENT_TIMESTAMP WHERE stop_id == $1;",
+            )
+            .bind(stop_id)
+            .bind(author.user_id())
+            .execute(&mut *tx)
+            .await?;
+        }
+        let station = Station::require(&mut tx, stop_id).await?;
+        if !moved_names.is_empty() {
+            Change::record_station(
+                &mut tx,
+                author,
+                ChangeAction::Split,
+                stop_id,
+                Some(&before),
+                Some(&station),
+            )
+            .await?;
+        }
+
+        if dry_run {
+            tx.rollback().await?;
+        } else {
+            tx.commit().await?;
+        }
+        Ok(Split {
+            station,
+            parts: created,
+            assignments,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{editor, migrated_db};
+    use sqlx::Sqlite;
+    use sqlx::pool::PoolConnection;
+
+    /// Station `Adamov` assigned to `h1` next to station `Blansko` and to `h2` on its own.
+    async fn db_with_station() -> (PoolConnection<Sqlite>, ChangeAuthor, StopId, StopId) {
+        let mut db = migrated_db().await;
+        let author = editor(&mut db).await;
+        let stop_id = Station::create_station(&mut db, &author, 49.3, 16.66, "Adamov")
+            .await
+            .unwrap()
+            .stop_id();
+        Station::add_name(&mut db, &author, stop_id, "Adamov,aut.")
+            .await
+            .unwrap();
+        let neighbour = Station::create_station(&mut db, &author, 49.36, 16.64, "Blansko")
+            .await
+            .unwrap()
+            .stop_id();
+        query(
+            "INSERT INTO sl_chains (chain_hash, station_name, pos) VALUES
+    ('h1', 'Blansko', 0), ('h1', 'Adamov,aut.', 1), ('h2', 'Adamov', 0);
+INSERT INTO hl_stations (chain_hash, station_name, pos, stop_id) VALUES
+    ('h1', 'Blansko', 0, $2), ('h1', 'Adamov,aut.', 1, $1), ('h2', 'Adamov', 0, $1);",
+        )
+        .bind(stop_id)
+        .bind(neighbour)
+        .execute(&mut *db)
+        .await
+        .unwrap();
+        (db, author, stop_id, neighbour)
+    }
+
+    fn bus_stop(neighbour: StopId) -> SplitPart {
+        SplitPart::new(
+            49.31,
+            16.67,
+            vec!["Adamov,aut.".to_string()],
+            Vec::new(),
+            vec![neighbour],
+        )
+    }
+
+    #[tokio::test]
+    async fn split_moves_names_and_positions_to_new_station() {
+        let (mut db, author, stop_id, neighbour) = db_with_station().await;
+        let session = ChangeAuthor::new(author.user_id(), Some("s2".to_string()));
+
+        let split = Station::split(&mut db, &session, stop_id, &[bus_stop(neighbour)], false)
+            .await
+            .unwrap();
+        assert_eq!(split.station().names(), ["Adamov"]);
+        let part = &split.parts()[0];
+        assert_eq!(part.names(), ["Adamov,aut."]);
+        assert_eq!((part.lat(), part.lon()), (49.31, 16.67));
+        assert_eq!(split.assignments().len(), 1);
+        assert_eq!(split.assignments()[0].chain_hash(), "h1");
+        assert_eq!(split.assignments()[0].stop_id(), Some(part.stop_id()));
+        let assigned = ChainStation::get_by_stop_id(&mut db, stop_id)
+            .await
+            .unwrap();
+        assert_eq!(assigned.len(), 1);
+        assert_eq!(assigned[0].chain_hash(), "h2");
+
+        Change::revert_session(&mut db, &author, "s2")
+            .await
+            .unwrap();
+        let station = Station::require(&mut db, stop_id).await.unwrap();
+        assert_eq!(station.names(), ["Adamov", "Adamov,aut."]);
+        assert_eq!(
+            ChainStation::get_by_stop_id(&mut db, stop_id)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(
+            Station::find(&mut db, part.stop_id())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn dry_run_previews_split_without_writing() {
+        let (mut db, author, stop_id, neighbour) = db_with_station().await;
+
+        let split = Station::split(&mut db, &author, stop_id, &[bus_stop(neighbour)], true)
+            .await
+            .unwrap();
+        let preview_id = split.parts()[0].stop_id();
+        assert_eq!(split.assignments().len(), 1);
+
+        let station = Station::require(&mut db, stop_id).await.unwrap();
+        assert_eq!(station.names(), ["Adamov", "Adamov,aut."]);
+        assert_eq!(
+            ChainStation::get_by_stop_id(&mut db, stop_id)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(Station::find(&mut db, preview_id).await.unwrap().is_none());
+        let created = Station::create_station(&mut db, &author, 49.3, 16.66, "Adamov,pošta")
+            .await
+            .unwrap();
+        assert_eq!(created.stop_id(), preview_id);
+    }
+
+    #[tokio::test]
+    async fn invalid_splits_are_rejected() {
+        let (mut db, author, stop_id, neighbour) = db_with_station().await;
+        let err = Station::split(&mut db, &author, stop_id, &[], false).await;
+        assert!(matches!(err, Err(StationError::EmptySplit(id)) if id == stop_id));
+
+        let part = SplitPart::new(91.0, 16.67, Vec::new(), Vec::new(), Vec::new());
+        let err = Station::split(&mut db, &author, stop_id, &[part], false).await;
+        assert!(matches!(err, Err(StationError::InvalidPosition { .. })));
+
+        let part = SplitPart::new(
+            49.31,
+            16.67,
+            vec!["Blansko".to_string()],
+            Vec::new(),
+            Vec::new(),
+        );
+        let err = Station::split(&mut db, &author, stop_id, &[part], false).await;
+        assert!(matches!(err, Err(StationError::UnknownName { .. })));
+
+        let parts = [
+            bus_stop(neighbour),
+            SplitPart::new(
+                49.31,
+                16.67,
+                vec!["Adamov".to_string()],
+                Vec::new(),
+                Vec::new(),
+            ),
+        ];
+        let err = Station::split(&mut db, &author, stop_id, &parts, false).await;
+        assert!(matches!(err, Err(StationError::LastName(id)) if id == stop_id));
+
+        let position = ChainPosition::new("h1".to_string(), 0);
+        let part = SplitPart::new(49.31, 16.67, Vec::new(), vec![position], Vec::new());
+        let err = Station::split(&mut db, &author, stop_id, &[part], false).await;
+        assert!(matches!(
+            err,
+            Err(StationError::UnassignedPosition { pos: 0, .. })
+        ));
+
+        let position = ChainPosition::new("h1".to_string(), 1);
+        let parts = [
+            SplitPart::new(49.31, 16.67, Vec::new(), vec![position], Vec::new()),
+            SplitPart::new(49.32, 16.68, Vec::new(), Vec::new(), vec![neighbour]),
+        ];
+        let err = Station::split(&mut db, &author, stop_id, &parts, false).await;
+        assert!(matches!(
+            err,
+            Err(StationError::AmbiguousPosition { ref chain_hash, pos: 1 }) if chain_hash == "h1"
+        ));
+
+        let station = Station::require(&mut db, stop_id).await.unwrap();
+        assert_eq!(station.names(), ["Adamov", "Adamov,aut."]);
+        assert_eq!(
+            ChainStation::get_by_stop_id(&mut db, stop_id)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
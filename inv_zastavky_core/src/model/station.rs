@@ -1,12 +1,143 @@
 use crate::model::StopId;
 use crate::model::bbox::BBox;
-use serde::Serialize;
+use crate::model::chain_station::ChainStation;
+use crate::model::change::{Change, ChangeAction, ChangeAuthor};
+use crate::model::cluster::{Clustered, Grid, MAX_POINTS};
+use crate::model::stop_id;
+use crate::search;
+use serde::{Deserialize, Serialize};
 use sqlx::pool::PoolConnection;
 use sqlx::sqlite::SqliteRow;
-use sqlx::{Row, Sqlite, query};
-use std::collections::HashMap;
+use sqlx::{Connection, Row, Sqlite, SqliteConnection, query};
+use std::fmt::{Display, Formatter};
 
-#[derive(Serialize, Debug)]
+/// Violated invariant of a station mutation, nothing is written when it is returned.
+#[derive(Debug)]
+pub enum StationError {
+    Storage(sqlx::Error),
+    /// No station with the stop id has a position and a name.
+    UnknownStation(StopId),
+    /// The station does not have the name.
+    UnknownName {
+        stop_id: StopId,
+        name: String,
+    },
+    /// The station already has the name.
+    DuplicateName {
+        stop_id: StopId,
+        name: String,
+    },
+    /// Removing the name would leave the station without any name.
+    LastName(StopId),
+    /// Station names must not be blank.
+    EmptyName,
+    /// Station can not be merged into itself.
+    SelfMerge(StopId),
+    /// Station can not be removed while chain positions are assigned to it.
+    Assigned {
+        stop_id: StopId,
+        positions: usize,
+    },
+    /// Split does not create any station.
+    EmptySplit(StopId),
+    /// The chain position is not assigned to the station.
+    UnassignedPosition {
+        stop_id: StopId,
+        chain_hash: String,
+        pos: i32,
+    },
+    /// The chain position is selected for more than one station of a split.
+    AmbiguousPosition {
+        chain_hash: String,
+        pos: i32,
+    },
+    /// Position is outside of WGS 84 coordinate range.
+    InvalidPosition {
+        lat: f64,
+        lon: f64,
+    },
+}
+
+impl Display for StationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StationError::Storage(err) => write!(f, "{}", err),
+            StationError::UnknownStation(stop_id) => {
+                write!(f, "no station with stop id {}", stop_id)
+            }
+            StationError::UnknownName { stop_id, name } => {
+                write!(f, "station {} has no name {}", stop_id, name)
+            }
+            StationError::DuplicateName { stop_id, name } => {
+                write!(f, "station {} already has name {}", stop_id, name)
+            }
+            StationError::LastName(stop_id) => {
+                write!(f, "station {} must keep at least one name", stop_id)
+            }
+            StationError::EmptyName => write!(f, "station name must not be empty"),
+            StationError::SelfMerge(stop_id) => {
+                write!(f, "station {} can not be merged into itself", stop_id)
+            }
+            StationError::Assigned { stop_id, positions } => write!(
+                f,
+                "station {} is assigned to {} chain positions",
+                stop_id, positions
+            ),
+            StationError::EmptySplit(stop_id) => {
+                write!(f, "split of station {} creates no station", stop_id)
+            }
+            StationError::UnassignedPosition {
+                stop_id,
+                chain_hash,
+                pos,
+            } => write!(
+                f,
+                "position {} of chain {} is not assigned to station {}",
+                pos, chain_hash, stop_id
+            ),
+            StationError::AmbiguousPosition { chain_hash, pos } => write!(
+                f,
+                "position {} of chain {} is selected for more than one station",
+                pos, chain_hash
+            ),
+            StationError::InvalidPosition { lat, lon } => {
+                write!(f, "position {}, {} is out of range", lat, lon)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StationError {}
+
+impl From<sqlx::Error> for StationError {
+    fn from(err: sqlx::Error) -> Self {
+        StationError::Storage(err)
+    }
+}
+
+/// Position kept by a station merged from two stations.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MergePosition {
+    /// Position of the station which is kept.
+    #[default]
+    Keep,
+    /// Position of the station which is removed.
+    Remove,
+}
+
+/// What happens to chain positions assigned to a removed station.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeletePolicy {
+    /// Station is not removed while any chain position is assigned to it.
+    #[default]
+    Refuse,
+    /// Chain positions assigned to the station are unassigned.
+    Unassign,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Station {
     stop_id: StopId,
     names: Vec<String>,
@@ -40,7 +171,7 @@ impl Station {
         self.lon
     }
 
-    pub fn from_rows(stop_id: StopId, rows: &Vec<SqliteRow>) -> Result<Option<Self>, sqlx::Error> {
+    pub fn from_rows(stop_id: StopId, rows: &[SqliteRow]) -> Result<Option<Self>, sqlx::Error> {
         let mut loc: Option<(f64, f64)> = None;
         let mut names: Vec<String> = Vec::new();
         for row in rows {
@@ -66,43 +197,108 @@ impl Station {
         Ok(Self::new(stop_id, vec![name], lat, lon))
     }
 
+    /// Loads up to 500 stations in the bbox, ordered by stop id.
     pub async fn get_by_bbox(
         db: &mut PoolConnection<Sqlite>,
         bbox: &BBox,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        Self::load_bbox(db, bbox, Some(MAX_POINTS)).await
+    }
+
+    /// Loads all stations in the bbox, ordered by stop id.
+    pub async fn get_all_by_bbox(
+        db: &mut PoolConnection<Sqlite>,
+        bbox: &BBox,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        Self::load_bbox(db, bbox, None).await
+    }
+
+    async fn load_bbox(
+        db: &mut PoolConnection<Sqlite>,
+        bbox: &BBox,
+        limit: Option<usize>,
     ) -> Result<Vec<Self>, sqlx::Error> {
         let rows: Vec<SqliteRow> = query(
-            "SELECT stop_id, lat, lon, station_name FROM el_station_names
-    JOIN el_station_pos USING (stop_id) WHERE $1 <= lat AND lat <= $2 AND $3 <= lon AND lon <= $4 LIMIT 500;")
-            .bind(bbox.lat_from())
-            .bind(bbox.lat_to())
-            .bind(bbox.lon_from())
-            .bind(bbox.lon_to())
-            .fetch_all(&mut **db).await?;
-        let mut stations_names: HashMap<StopId, Vec<String>> = HashMap::new();
-        let mut stations_locs: HashMap<StopId, (f64, f64)> = HashMap::new();
+            "SELECT stop_id, lat, lon, station_name FROM el_station_pos
+    JOIN el_station_names USING (stop_id)
+WHERE stop_id IN (
+    SELECT el_station_pos.stop_id FROM el_station_pos_rtree
+        JOIN el_station_pos ON el_station_pos.stop_id = el_station_pos_rtree.stop_id
+    WHERE lat_max >= $1 AND lat_min <= $2 AND lon_max >= $3 AND lon_min <= $4
+        AND $1 <= lat AND lat <= $2 AND $3 <= lon AND lon <= $4
+    ORDER BY el_station_pos.stop_id LIMIT $5
+)
+ORDER BY stop_id, station_name;",
+        )
+        .bind(bbox.lat_from())
+        .bind(bbox.lat_to())
+        .bind(bbox.lon_from())
+        .bind(bbox.lon_to())
+        .bind(limit.map_or(-1, |limit| limit as i64))
+        .fetch_all(&mut **db)
+        .await?;
+        let mut stations: Vec<Self> = Vec::new();
         for row in rows {
             let stop_id: StopId = row.try_get(0)?;
-            if !stations_names.contains_key(&stop_id) {
-                stations_locs.insert(stop_id, (row.try_get(1)?, row.try_get(2)?));
-                stations_names.insert(stop_id, Vec::new());
+            match stations.last_mut() {
+                Some(station) if station.stop_id == stop_id => {
+                    station.names.push(row.try_get(3)?);
+                }
+                _ => stations.push(Self::from_row(row)?),
             }
-            stations_names
-                .get_mut(&stop_id)
-                .unwrap()
-                .push(row.try_get(3)?);
-        }
-        let mut stations = Vec::new();
-        for (stop_id, names) in stations_names {
-            stations.push(Station::new(
-                stop_id,
-                names,
-                stations_locs[&stop_id].0,
-                stations_locs[&stop_id].1,
-            ))
         }
         Ok(stations)
     }
 
+    /// Counter increased by every change of station positions or names, also by changes which are
+    /// not in the change log.
+    pub async fn data_version(db: &mut PoolConnection<Sqlite>) -> Result<i64, sqlx::Error> {
+        query("SELECT version FROM el_stations_version;")
+            .fetch_one(&mut **db)
+            .await?
+            .try_get(0)
+    }
+
+    /// Loads stations in the bbox, clustered for the zoom level if there are too many of them.
+    pub async fn get_clustered(
+        db: &mut PoolConnection<Sqlite>,
+        bbox: &BBox,
+        zoom: u8,
+    ) -> Result<Clustered<Self>, sqlx::Error> {
+        let count: i64 = query(
+            "SELECT count(*) FROM el_station_pos_rtree
+WHERE lat_max >= $1 AND lat_min <= $2 AND lon_max >= $3 AND lon_min <= $4;",
+        )
+        .bind(bbox.lat_from())
+        .bind(bbox.lat_to())
+        .bind(bbox.lon_from())
+        .bind(bbox.lon_to())
+        .fetch_one(&mut **db)
+        .await?
+        .try_get(0)?;
+        if count <= MAX_POINTS as i64 {
+            return Ok(Clustered::Points(Self::get_by_bbox(db, bbox).await?));
+        }
+        let rows = query(
+            "SELECT lat, lon FROM el_station_pos
+WHERE stop_id IN (
+    SELECT stop_id FROM el_station_pos_rtree
+    WHERE lat_max >= $1 AND lat_min <= $2 AND lon_max >= $3 AND lon_min <= $4
+) AND $1 <= lat AND lat <= $2 AND $3 <= lon AND lon <= $4;",
+        )
+        .bind(bbox.lat_from())
+        .bind(bbox.lat_to())
+        .bind(bbox.lon_from())
+        .bind(bbox.lon_to())
+        .fetch_all(&mut **db)
+        .await?;
+        let mut grid = Grid::new(zoom);
+        for row in rows {
+            grid.add(row.try_get(0)?, row.try_get(1)?);
+        }
+        Ok(Clustered::Clusters(grid.clusters()))
+    }
+
     pub async fn get_by_id(
         db: &mut PoolConnection<Sqlite>,
         stop_id: StopId,
@@ -135,110 +331,610 @@ impl Station {
         Ok(stations)
     }
 
-    pub async fn create_station(
-        db: &mut PoolConnection<Sqlite>,
+    /// Loads station from any connection, also from within a transaction.
+    pub(crate) async fn find(
+        conn: &mut SqliteConnection,
         stop_id: StopId,
-        lat: f64,
-        lon: f64,
-        station_name: &str,
     ) -> Result<Option<Self>, sqlx::Error> {
         let rows: Vec<SqliteRow> = query(
-            "INSERT INTO el_station_pos (stop_id, lat, lon) VALUES ($1, $2, $3);
-INSERT INTO el_station_names (stop_id, station_name) VALUES ($1, $4);
-SELECT stop_id, lat, lon, station_name FROM el_station_names
+            "SELECT stop_id, lat, lon, station_name FROM el_station_names
     JOIN el_station_pos USING (stop_id) WHERE stop_id == $1;",
+        )
+        .bind(stop_id)
+        .fetch_all(conn)
+        .await?;
+        Self::from_rows(stop_id, &rows)
+    }
+
+    /// Loads station which is expected to exist.
+    pub async fn require(
+        conn: &mut SqliteConnection,
+        stop_id: StopId,
+    ) -> Result<Self, StationError> {
+        Self::find(conn, stop_id)
+            .await?
+            .ok_or(StationError::UnknownStation(stop_id))
+    }
+
+    pub(crate) fn check_position(lat: f64, lon: f64) -> Result<(), StationError> {
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+            return Err(StationError::InvalidPosition { lat, lon });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_name(station_name: &str) -> Result<(), StationError> {
+        if station_name.trim().is_empty() {
+            return Err(StationError::EmptyName);
+        }
+        Ok(())
+    }
+
+    /// Creates station with a newly allocated stop id.
+    pub async fn create_station(
+        conn: &mut SqliteConnection,
+        author: &ChangeAuthor,
+        lat: f64,
+        lon: f64,
+        station_name: &str,
+    ) -> Result<Self, StationError> {
+        Self::check_position(lat, lon)?;
+        Self::check_name(station_name)?;
+        let mut tx = conn.begin().await?;
+        let stop_id = stop_id::allocate(&mut tx).await?;
+        query(
+            "INSERT INTO el_station_pos (stop_id, lat, lon, edited_by, edited_at) VALUES ($1, $2, $3, $5, CURRENT_TIMESTAMP);
+INSERT INTO el_station_names (stop_id, station_name, edited_by, edited_at) VALUES ($1, $4, $5, CURRENT_TIMESTAMP);",
         )
         .bind(stop_id)
         .bind(lat)
         .bind(lon)
         .bind(station_name)
-        .fetch_all(&mut **db)
+        .bind(author.user_id())
+        .execute(&mut *tx)
         .await?;
-        Self::from_rows(stop_id, &rows)
+        let station = Self::require(&mut tx, stop_id).await?;
+        Change::record_station(
+            &mut tx,
+            author,
+            ChangeAction::Create,
+            stop_id,
+            None,
+            Some(&station),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(station)
     }
 
+    /// Removes station, assigned chain positions are handled by `policy`.
     pub async fn remove_station(
-        db: &mut PoolConnection<Sqlite>,
+        conn: &mut SqliteConnection,
+        author: &ChangeAuthor,
         stop_id: StopId,
-    ) -> Result<(), sqlx::Error> {
+        policy: DeletePolicy,
+    ) -> Result<(), StationError> {
+        let mut tx = conn.begin().await?;
+        let station = Self::require(&mut tx, stop_id).await?;
+        let assignments = ChainStation::get_by_stop_id(&mut tx, stop_id).await?;
+        if !assignments.is_empty() && policy == DeletePolicy::Refuse {
+            return Err(StationError::Assigned {
+                stop_id,
+                positions: assignments.len(),
+            });
+        }
+        for assignment in &assignments {
+            query("DELETE FROM hl_stations WHERE chain_hash = $1 AND pos = $2;")
+                .bind(assignment.chain_hash())
+                .bind(assignment.pos())
+                .execute(&mut *tx)
+                .await?;
+            Change::record_assignment(
+                &mut tx,
+                author,
+                ChangeAction::Unassign,
+                assignment.chain_hash(),
+                assignment.pos(),
+                Some(assignment),
+                None,
+            )
+            .await?;
+        }
         query(
             "DELETE FROM el_station_pos WHERE stop_id == $1;
 DELETE FROM el_station_names WHERE stop_id == $1;",
         )
         .bind(stop_id)
-        .execute(&mut **db)
+        .execute(&mut *tx)
         .await?;
+        Change::record_station(
+            &mut tx,
+            author,
+            ChangeAction::Remove,
+            stop_id,
+            Some(&station),
+            None,
+        )
+        .await?;
+        tx.commit().await?;
         Ok(())
     }
 
     pub async fn move_station(
-        db: &mut PoolConnection<Sqlite>,
+        conn: &mut SqliteConnection,
+        author: &ChangeAuthor,
         stop_id: StopId,
         lat: f64,
         lon: f64,
-    ) -> Result<Option<Self>, sqlx::Error> {
-        let rows: Vec<SqliteRow> = query(
-            "UPDATE el_station_pos SET lat = $2, lon = $3 WHERE stop_id == $1;
-SELECT stop_id, lat, lon, station_name FROM el_station_names
-    JOIN el_station_pos USING (stop_id) WHERE stop_id == $1;",
+    ) -> Result<Self, StationError> {
+        Self::check_position(lat, lon)?;
+        let mut tx = conn.begin().await?;
+        let before = Self::require(&mut tx, stop_id).await?;
+        query(
+            "UPDATE el_station_pos SET lat = $2, lon = $3, edited_by = $4, edited_at = CURRENT_TIMESTAMP WHERE stop_id == $1;",
         )
         .bind(stop_id)
         .bind(lat)
         .bind(lon)
-        .fetch_all(&mut **db)
+        .bind(author.user_id())
+        .execute(&mut *tx)
         .await?;
-        Self::from_rows(stop_id, &rows)
+        let station = Self::require(&mut tx, stop_id).await?;
+        Change::record_station(
+            &mut tx,
+            author,
+            ChangeAction::Move,
+            stop_id,
+            Some(&before),
+            Some(&station),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(station)
     }
 
     pub async fn add_name(
-        db: &mut PoolConnection<Sqlite>,
+        conn: &mut SqliteConnection,
+        author: &ChangeAuthor,
         stop_id: StopId,
         station_name: &str,
-    ) -> Result<Option<Self>, sqlx::Error> {
-        let rows: Vec<SqliteRow> = query(
-            "INSERT INTO el_station_names (stop_id, station_name) VALUES ($1, $2);
-SELECT stop_id, lat, lon, station_name FROM el_station_names
-    JOIN el_station_pos USING (stop_id) WHERE stop_id == $1;",
+    ) -> Result<Self, StationError> {
+        Self::check_name(station_name)?;
+        let mut tx = conn.begin().await?;
+        let before = Self::require(&mut tx, stop_id).await?;
+        if before.names.iter().any(|name| name == station_name) {
+            return Err(StationError::DuplicateName {
+                stop_id,
+                name: station_name.to_string(),
+            });
+        }
+        query(
+            "INSERT INTO el_station_names (stop_id, station_name, edited_by, edited_at) VALUES ($1, $2, $3, CURRENT_TIMESTAMP);
+UPDATE el_station_pos SET edited_by = $3, edited_at = CURRENT_TIMESTAMP WHERE stop_id == $1;",
         )
         .bind(stop_id)
         .bind(station_name)
-        .fetch_all(&mut **db)
+        .bind(author.user_id())
+        .execute(&mut *tx)
         .await?;
-        Self::from_rows(stop_id, &rows)
+        let station = Self::require(&mut tx, stop_id).await?;
+        Change::record_station(
+            &mut tx,
+            author,
+            ChangeAction::AddName,
+            stop_id,
+            Some(&before),
+            Some(&station),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(station)
     }
 
+    /// Removes one of the names, the last name of a station can not be removed.
     pub async fn remove_station_name(
-        db: &mut PoolConnection<Sqlite>,
+        conn: &mut SqliteConnection,
+        author: &ChangeAuthor,
         stop_id: StopId,
         station_name: &str,
-    ) -> Result<Option<Self>, sqlx::Error> {
-        let rows: Vec<SqliteRow> = query(
+    ) -> Result<Self, StationError> {
+        let mut tx = conn.begin().await?;
+        let before = Self::require(&mut tx, stop_id).await?;
+        if !before.names.iter().any(|name| name == station_name) {
+            return Err(StationError::UnknownName {
+                stop_id,
+                name: station_name.to_string(),
+            });
+        }
+        if before.names.len() == 1 {
+            return Err(StationError::LastName(stop_id));
+        }
+        query(
             "DELETE FROM el_station_names WHERE stop_id == $1 AND station_name == $2;
-SELECT stop_id, lat, lon, station_name FROM el_station_names
-    JOIN el_station_pos USING (stop_id) WHERE stop_id == $1;",
+UPDATE el_station_pos SET edited_by = $3, edited_at = CURRENT_TIMESTAMP WHERE stop_id == $1;",
         )
         .bind(stop_id)
         .bind(station_name)
-        .fetch_all(&mut **db)
+        .bind(author.user_id())
+        .execute(&mut *tx)
         .await?;
-        Self::from_rows(stop_id, &rows)
+        let station = Self::require(&mut tx, stop_id).await?;
+        Change::record_station(
+            &mut tx,
+            author,
+            ChangeAction::RemoveName,
+            stop_id,
+            Some(&before),
+            Some(&station),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(station)
+    }
+
+    /// Merges station `remove` into station `keep`.
+    ///
+    /// The kept station gets names of both stations and chain positions assigned to the removed
+    /// station are assigned to the kept one.
+    pub async fn merge(
+        conn: &mut SqliteConnection,
+        author: &ChangeAuthor,
+        keep: StopId,
+        remove: StopId,
+        position: MergePosition,
+    ) -> Result<Self, StationError> {
+        if keep == remove {
+            return Err(StationError::SelfMerge(keep));
+        }
+        let mut tx = conn.begin().await?;
+        let kept = Self::require(&mut tx, keep).await?;
+        let removed = Self::require(&mut tx, remove).await?;
+
+        let assignments = query("SELECT chain_hash, pos FROM hl_stations WHERE stop_id == $1;")
+            .bind(remove)
+            .fetch_all(&mut *tx)
+            .await?;
+        for row in assignments {
+            let chain_hash: String = row.try_get(0)?;
+            let pos: i32 = row.try_get(1)?;
+            let before = ChainStation::get_assignment(&mut tx, &chain_hash, pos).await?;
+            query(
+                "UPDATE hl_stations SET stop_id = $3, edited_by = $4, edited_at = CURRENT_TIMESTAMP WHERE chain_hash = $1 AND pos = $2;",
+            )
+            .bind(&chain_hash)
+            .bind(pos)
+            .bind(keep)
+            .bind(author.user_id())
+            .execute(&mut *tx)
+            .await?;
+            let after = ChainStation::get_assignment(&mut tx, &chain_hash, pos).await?;
+            Change::record_assignment(
+                &mut tx,
+                author,
+                ChangeAction::Merge,
+                &chain_hash,
+                pos,
+                before.as_ref(),
+                after.as_ref(),
+            )
+            .await?;
+        }
+
+        let (lat, lon) = match position {
+            MergePosition::Keep => (kept.lat, kept.lon),
+            MergePosition::Remove => (removed.lat, removed.lon),
+        };
+        query(
+            "INSERT OR IGNORE INTO el_station_names (stop_id, station_name, edited_by, edited_at)
+    SELECT $1, station_name, $3, CURRENT_TIMESTAMP FROM el_station_names WHERE stop_id == $2;
+UPDATE el_station_pos SET lat = $4, lon = $5, edited_by = $3, edited_at = CURRENT_TIMESTAMP WHERE stop_id == $1;
+DELETE FROM el_station_pos WHERE stop_id == $2;
+DELETE FROM el_station_names WHERE stop_id == $2;",
+        )
+        .bind(keep)
+        .bind(remove)
+        .bind(author.user_id())
+        .bind(lat)
+        .bind(lon)
+        .execute(&mut *tx)
+        .await?;
+        let merged = Self::require(&mut tx, keep).await?;
+        Change::record_station(
+            &mut tx,
+            author,
+            ChangeAction::Merge,
+            remove,
+            Some(&removed),
+            None,
+        )
+        .await?;
+        Change::record_station(
+            &mut tx,
+            author,
+            ChangeAction::Merge,
+            keep,
+            Some(&kept),
+            Some(&merged),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(merged)
+    }
+
+    /// Replaces position and names of the station, `None` removes the station.
+    ///
+    /// The station is updated in place, so chain positions assigned to it stay valid.
+    pub(crate) async fn restore(
+        conn: &mut SqliteConnection,
+        author: &ChangeAuthor,
+        stop_id: StopId,
+        state: Option<&Self>,
+    ) -> Result<(), sqlx::Error> {
+        let Some(state) = state else {
+            query(
+                "DELETE FROM el_station_pos WHERE stop_id == $1;
+DELETE FROM el_station_names WHERE stop_id == $1;",
+            )
+            .bind(stop_id)
+            .execute(&mut *conn)
+            .await?;
+            return Ok(());
+        };
+        query(
+            "INSERT INTO el_station_pos (stop_id, lat, lon, edited_by, edited_at) VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP)
+    ON CONFLICT (stop_id) DO UPDATE SET lat = $2, lon = $3, edited_by = $4, edited_at = CURRENT_TIMESTAMP;",
+        )
+        .bind(stop_id)
+        .bind(state.lat)
+        .bind(state.lon)
+        .bind(author.user_id())
+        .execute(&mut *conn)
+        .await?;
+        for name in &state.names {
+            query(
+                "INSERT OR IGNORE INTO el_station_names (stop_id, station_name, edited_by, edited_at) VALUES ($1, $2, $3, CURRENT_TIMESTAMP);",
+            )
+            .bind(stop_id)
+            .bind(name)
+            .bind(author.user_id())
+            .execute(&mut *conn)
+            .await?;
+        }
+        let names = serde_json::Value::from(state.names.clone()).to_string();
+        query(
+            "DELETE FROM el_station_names WHERE stop_id == $1 AND station_name NOT IN (SELECT value FROM json_each($2));",
+        )
+        .bind(stop_id)
+        .bind(names)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
     }
 
+    /// Searches station names without diacritics, names starting with the text first.
     pub async fn search(
         db: &mut PoolConnection<Sqlite>,
         search: &str,
     ) -> Result<Vec<Self>, sqlx::Error> {
-        let rows: Vec<SqliteRow> = query(
-            "SELECT stop_id, lat, lon, station_name FROM el_station_names
-    JOIN el_station_pos USING (stop_id) WHERE station_name LIKE $1 LIMIT 50;",
+        let matches = search::search_stations(db, search, 50).await?;
+        Ok(matches
+            .into_iter()
+            .filter_map(|found| {
+                Some(Self::new(
+                    found.stop_id()?,
+                    vec![found.name().to_string()],
+                    found.lat()?,
+                    found.lon()?,
+                ))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{editor, migrated_db};
+
+    async fn assign(db: &mut PoolConnection<Sqlite>, chain_hash: &str, pos: i32, stop_id: StopId) {
+        query(
+            "INSERT INTO sl_chains (chain_hash, station_name, pos) VALUES ($1, 'Adamov', $2);
+INSERT INTO hl_stations (chain_hash, station_name, pos, stop_id) VALUES ($1, 'Adamov', $2, $3);",
         )
-        .bind(search)
-        .fetch_all(&mut **db)
-        .await?;
-        let mut stations = Vec::new();
-        for row in rows {
-            stations.push(Self::from_row(row)?);
-        }
-        Ok(stations)
+        .bind(chain_hash)
+        .bind(pos)
+        .bind(stop_id)
+        .execute(&mut **db)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn stations_are_created_renamed_moved_and_removed() {
+        let mut db = migrated_db().await;
+        let author = editor(&mut db).await;
+
+        let station = Station::create_station(&mut db, &author, 49.3, 16.66, "Adamov")
+            .await
+            .unwrap();
+        let stop_id = station.stop_id();
+        assert_eq!(station.names(), ["Adamov"]);
+
+        let station = Station::add_name(&mut db, &author, stop_id, "Adamov,žel.st.")
+            .await
+            .unwrap();
+        assert_eq!(station.names(), ["Adamov", "Adamov,žel.st."]);
+        let station = Station::remove_station_name(&mut db, &author, stop_id, "Adamov")
+            .await
+            .unwrap();
+        assert_eq!(station.names(), ["Adamov,žel.st."]);
+
+        let station = Station::move_station(&mut db, &author, stop_id, 49.29, 16.65)
+            .await
+            .unwrap();
+        assert_eq!((station.lat(), station.lon()), (49.29, 16.65));
+        let stored = Station::get_by_id(&mut db, stop_id).await.unwrap().unwrap();
+        assert_eq!((stored.lat(), stored.lon()), (49.29, 16.65));
+
+        Station::remove_station(&mut db, &author, stop_id, DeletePolicy::Refuse)
+            .await
+            .unwrap();
+        assert!(
+            Station::get_by_id(&mut db, stop_id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // stop ids are never handed out twice
+        let station = Station::create_station(&mut db, &author, 49.3, 16.66, "Adamov")
+            .await
+            .unwrap();
+        assert!(station.stop_id() > stop_id);
+    }
+
+    #[tokio::test]
+    async fn invalid_mutations_write_nothing() {
+        let mut db = migrated_db().await;
+        let author = editor(&mut db).await;
+        let stop_id = Station::create_station(&mut db, &author, 49.3, 16.66, "Adamov")
+            .await
+            .unwrap()
+            .stop_id();
+
+        let err = Station::create_station(&mut db, &author, 91.0, 16.66, "Adamov").await;
+        assert!(matches!(err, Err(StationError::InvalidPosition { .. })));
+        let err = Station::create_station(&mut db, &author, 49.3, 16.66, " ").await;
+        assert!(matches!(err, Err(StationError::EmptyName)));
+        let err = Station::move_station(&mut db, &author, stop_id, 49.3, 181.0).await;
+        assert!(matches!(err, Err(StationError::InvalidPosition { .. })));
+        let err = Station::move_station(&mut db, &author, stop_id + 1, 49.3, 16.66).await;
+        assert!(matches!(err, Err(StationError::UnknownStation(id)) if id == stop_id + 1));
+        let err = Station::add_name(&mut db, &author, stop_id, "Adamov").await;
+        assert!(matches!(err, Err(StationError::DuplicateName { .. })));
+        let err = Station::remove_station_name(&mut db, &author, stop_id, "Blansko").await;
+        assert!(matches!(err, Err(StationError::UnknownName { .. })));
+        let err = Station::remove_station_name(&mut db, &author, stop_id, "Adamov").await;
+        assert!(matches!(err, Err(StationError::LastName(id)) if id == stop_id));
+
+        let station = Station::get_by_id(&mut db, stop_id).await.unwrap().unwrap();
+        assert_eq!(station.names(), ["Adamov"]);
+        assert_eq!((station.lat(), station.lon()), (49.3, 16.66));
+        let changes: i64 = query("SELECT count(*) FROM el_changes;")
+            .fetch_one(&mut *db)
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(changes, 1);
+    }
+
+    #[tokio::test]
+    async fn assigned_stations_are_removed_by_policy() {
+        let mut db = migrated_db().await;
+        let author = editor(&mut db).await;
+        let stop_id = Station::create_station(&mut db, &author, 49.3, 16.66, "Adamov")
+            .await
+            .unwrap()
+            .stop_id();
+        assign(&mut db, "h1", 0, stop_id).await;
+        assign(&mut db, "h2", 3, stop_id).await;
+
+        let err = Station::remove_station(&mut db, &author, stop_id, DeletePolicy::Refuse).await;
+        assert!(matches!(
+            err,
+            Err(StationError::Assigned { positions: 2, .. })
+        ));
+        assert!(
+            Station::get_by_id(&mut db, stop_id)
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        Station::remove_station(&mut db, &author, stop_id, DeletePolicy::Unassign)
+            .await
+            .unwrap();
+        assert!(
+            Station::get_by_id(&mut db, stop_id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            ChainStation::get_by_stop_id(&mut db, stop_id)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let actions: Vec<String> = query("SELECT action FROM el_changes ORDER BY change_id;")
+            .fetch_all(&mut *db)
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        assert_eq!(actions, ["create", "unassign", "unassign", "remove"]);
+    }
+
+    #[tokio::test]
+    async fn merged_station_takes_names_and_positions_of_removed_one() {
+        let mut db = migrated_db().await;
+        let author = editor(&mut db).await;
+        let keep = Station::create_station(&mut db, &author, 49.3, 16.66, "Adamov")
+            .await
+            .unwrap()
+            .stop_id();
+        let remove = Station::create_station(&mut db, &author, 49.29, 16.65, "Adamov,žel.st.")
+            .await
+            .unwrap()
+            .stop_id();
+        Station::add_name(&mut db, &author, remove, "Adamov")
+            .await
+            .unwrap();
+        assign(&mut db, "h1", 0, remove).await;
+
+        let session = ChangeAuthor::new(author.user_id(), Some("s2".to_string()));
+        let merged = Station::merge(&mut db, &session, keep, remove, MergePosition::Remove)
+            .await
+            .unwrap();
+        assert_eq!(merged.stop_id(), keep);
+        assert_eq!(merged.names(), ["Adamov", "Adamov,žel.st."]);
+        assert_eq!((merged.lat(), merged.lon()), (49.29, 16.65));
+        assert!(Station::get_by_id(&mut db, remove).await.unwrap().is_none());
+        let assignment = ChainStation::get_assignment(&mut db, "h1", 0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(assignment.stop_id(), Some(keep));
+
+        Change::revert_session(&mut db, &author, "s2")
+            .await
+            .unwrap();
+        let kept = Station::get_by_id(&mut db, keep).await.unwrap().unwrap();
+        assert_eq!(kept.names(), ["Adamov"]);
+        assert_eq!((kept.lat(), kept.lon()), (49.3, 16.66));
+        let removed = Station::get_by_id(&mut db, remove).await.unwrap().unwrap();
+        assert_eq!(removed.names().len(), 2);
+        let assignment = ChainStation::get_assignment(&mut db, "h1", 0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(assignment.stop_id(), Some(remove));
+    }
+
+    #[tokio::test]
+    async fn station_is_not_merged_into_itself_or_a_missing_one() {
+        let mut db = migrated_db().await;
+        let author = editor(&mut db).await;
+        let keep = Station::create_station(&mut db, &author, 49.3, 16.66, "Adamov")
+            .await
+            .unwrap()
+            .stop_id();
+
+        let err = Station::merge(&mut db, &author, keep, keep, MergePosition::Keep).await;
+        assert!(matches!(err, Err(StationError::SelfMerge(id)) if id == keep));
+        let err = Station::merge(&mut db, &author, keep, keep + 1, MergePosition::Keep).await;
+        assert!(matches!(err, Err(StationError::UnknownStation(id)) if id == keep + 1));
+        assert!(Station::get_by_id(&mut db, keep).await.unwrap().is_some());
     }
 }
@@ -25,7 +25,7 @@ impl Stats {
         )
         .fetch_all(&mut **db)
         .await?;
-        let pos_count = rows.get(0).unwrap().try_get(0)?;
+        let pos_count = rows.first().unwrap().try_get(0)?;
         let names_count = rows.get(1).unwrap().try_get(0)?;
         Ok(Stats::new(pos_count, names_count))
     }
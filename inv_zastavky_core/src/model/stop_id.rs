@@ -0,0 +1,13 @@
+use crate::model::StopId;
+use sqlx::{Row, SqliteConnection, query};
+
+/// Allocates a new stop id, ids are sequential and never handed out twice.
+///
+/// The id is reserved in `el_stop_ids`, so it should be allocated in the same transaction
+/// which creates the station.
+pub async fn allocate(conn: &mut SqliteConnection) -> Result<StopId, sqlx::Error> {
+    let row = query("INSERT INTO el_stop_ids DEFAULT VALUES RETURNING stop_id;")
+        .fetch_one(conn)
+        .await?;
+    row.try_get(0)
+}
@@ -0,0 +1,295 @@
+use crate::error::Error;
+use crate::model::UserId;
+use base64::Engine;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::pool::PoolConnection;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Row, Sqlite, query};
+
+const PBKDF2_ROUNDS: u32 = 100_000;
+
+/// Local account of an editor.
+#[derive(Serialize, Debug, Clone)]
+pub struct User {
+    user_id: UserId,
+    username: String,
+}
+
+impl User {
+    pub fn new(user_id: UserId, username: String) -> Self {
+        Self { user_id, username }
+    }
+
+    pub fn user_id(&self) -> UserId {
+        self.user_id
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self::new(row.try_get(0)?, row.try_get(1)?))
+    }
+
+    pub async fn create(
+        db: &mut PoolConnection<Sqlite>,
+        username: &str,
+        password: &str,
+    ) -> Result<Self, Error> {
+        check_credentials(username, password)?;
+        if Self::get_by_name(db, username).await?.is_some() {
+            return Err(Error::conflict(
+                "username_taken",
+                format!("user {} already exists", username),
+            ));
+        }
+        query("INSERT INTO users (username, password_hash) VALUES ($1, $2);")
+            .bind(username)
+            .bind(hash_password(password))
+            .execute(&mut **db)
+            .await?;
+        Self::get_by_name(db, username)
+            .await?
+            .ok_or_else(|| Error::Storage(sqlx::Error::RowNotFound))
+    }
+
+    pub async fn set_password(
+        db: &mut PoolConnection<Sqlite>,
+        username: &str,
+        password: &str,
+    ) -> Result<(), Error> {
+        check_credentials(username, password)?;
+        let result = query("UPDATE users SET password_hash = $2 WHERE username = $1;")
+            .bind(username)
+            .bind(hash_password(password))
+            .execute(&mut **db)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(Error::not_found(
+                "user_not_found",
+                format!("no user {}", username),
+            ));
+        }
+        Ok(())
+    }
+
+    pub async fn get_by_name(
+        db: &mut PoolConnection<Sqlite>,
+        username: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let row = query("SELECT user_id, username FROM users WHERE username = $1;")
+            .bind(username)
+            .fetch_optional(&mut **db)
+            .await?;
+        row.as_ref().map(Self::from_row).transpose()
+    }
+
+    pub async fn list(db: &mut PoolConnection<Sqlite>) -> Result<Vec<Self>, sqlx::Error> {
+        let rows = query("SELECT user_id, username FROM users ORDER BY user_id;")
+            .fetch_all(&mut **db)
+            .await?;
+        rows.iter().map(Self::from_row).collect()
+    }
+
+    /// Returns the user with the stored password hash, the password is checked by
+    /// [`Credentials::verify`].
+    pub async fn get_credentials(
+        db: &mut PoolConnection<Sqlite>,
+        username: &str,
+    ) -> Result<Option<Credentials>, sqlx::Error> {
+        let row = query("SELECT user_id, username, password_hash FROM users WHERE username = $1;")
+            .bind(username)
+            .fetch_optional(&mut **db)
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        Ok(Some(Credentials {
+            user: Self::from_row(&row)?,
+            password_hash: row.try_get(2)?,
+        }))
+    }
+
+    /// Creates a new API token of the user, the token can not be recovered later.
+    pub async fn create_token(
+        db: &mut PoolConnection<Sqlite>,
+        user_id: UserId,
+        name: &str,
+    ) -> Result<String, sqlx::Error> {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let token = BASE64_URL_SAFE_NO_PAD.encode(bytes);
+        query("INSERT INTO user_tokens (token_hash, user_id, name) VALUES ($1, $2, $3);")
+            .bind(hash_token(&token))
+            .bind(user_id)
+            .bind(name)
+            .execute(&mut **db)
+            .await?;
+        Ok(token)
+    }
+
+    pub async fn get_by_token(
+        db: &mut PoolConnection<Sqlite>,
+        token: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let row = query(
+            "SELECT user_id, username FROM user_tokens JOIN users USING (user_id) WHERE token_hash = $1;",
+        )
+        .bind(hash_token(token))
+        .fetch_optional(&mut **db)
+        .await?;
+        row.as_ref().map(Self::from_row).transpose()
+    }
+
+    /// Removes all API tokens of the user and returns their count.
+    pub async fn revoke_tokens(
+        db: &mut PoolConnection<Sqlite>,
+        user_id: UserId,
+    ) -> Result<u64, sqlx::Error> {
+        let result = query("DELETE FROM user_tokens WHERE user_id = $1;")
+            .bind(user_id)
+            .execute(&mut **db)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+/// Stored password hash of a user.
+pub struct Credentials {
+    user: User,
+    password_hash: String,
+}
+
+impl Credentials {
+    /// Returns the user if the password matches. Hashing is slow by design, async callers should
+    /// run it on a blocking thread.
+    pub fn verify(self, password: &str) -> Option<User> {
+        verify_password(password, &self.password_hash).then_some(self.user)
+    }
+
+    /// Rejects password of a user who does not exist, after hashing it like [`Credentials::verify`]
+    /// does, so the response time does not tell which usernames exist.
+    pub fn reject(password: &str) -> Option<User> {
+        verify_password(password, &dummy_hash());
+        None
+    }
+}
+
+/// Well-formed hash no password matches, verifying against it costs as much as against a real one.
+fn dummy_hash() -> String {
+    format!(
+        "pbkdf2-sha256${}${}${}",
+        PBKDF2_ROUNDS,
+        BASE64_URL_SAFE_NO_PAD.encode([0u8; 16]),
+        BASE64_URL_SAFE_NO_PAD.encode([0u8; 32])
+    )
+}
+
+fn check_credentials(username: &str, password: &str) -> Result<(), Error> {
+    if username.trim().is_empty() || username.contains(':') {
+        return Err(Error::validation(
+            "invalid_username",
+            "username must not be empty or contain ':'",
+        ));
+    }
+    if password.len() < 8 {
+        return Err(Error::validation(
+            "weak_password",
+            "password must have at least 8 characters",
+        ));
+    }
+    Ok(())
+}
+
+/// Hashes password with PBKDF2-HMAC-SHA256 into `pbkdf2-sha256$rounds$salt$hash`.
+fn hash_password(password: &str) -> String {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let hash = pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(password.as_bytes(), &salt, PBKDF2_ROUNDS);
+    format!(
+        "pbkdf2-sha256${}${}${}",
+        PBKDF2_ROUNDS,
+        BASE64_URL_SAFE_NO_PAD.encode(salt),
+        BASE64_URL_SAFE_NO_PAD.encode(hash)
+    )
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    let parts: Vec<&str> = password_hash.split('$').collect();
+    let [scheme, rounds, salt, hash] = parts[..] else {
+        return false;
+    };
+    let (Ok(rounds), Ok(salt), Ok(hash)) = (
+        rounds.parse(),
+        BASE64_URL_SAFE_NO_PAD.decode(salt),
+        BASE64_URL_SAFE_NO_PAD.decode(hash),
+    ) else {
+        return false;
+    };
+    if scheme != "pbkdf2-sha256" || hash.len() != 32 {
+        return false;
+    }
+    let computed = pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(password.as_bytes(), &salt, rounds);
+    // compare in constant time
+    computed
+        .iter()
+        .zip(&hash)
+        .fold(0, |diff, (left, right)| diff | (left ^ right))
+        == 0
+}
+
+/// Tokens are random, so a plain SHA-256 is enough to not keep them in the database.
+fn hash_token(token: &str) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passwords_are_verified_against_their_hash() {
+        let user = User::new(1, "editor".to_string());
+        let credentials = |password_hash: String| Credentials {
+            user: user.clone(),
+            password_hash,
+        };
+        let password_hash = hash_password("correct horse");
+        assert!(
+            credentials(password_hash.clone())
+                .verify("correct horse")
+                .is_some()
+        );
+        assert!(
+            credentials(password_hash)
+                .verify("battery staple")
+                .is_none()
+        );
+        assert!(credentials("plain".to_string()).verify("plain").is_none());
+    }
+
+    #[test]
+    fn unknown_users_are_rejected_after_full_hashing() {
+        assert!(Credentials::reject("correct horse").is_none());
+        // the dummy hash is parsed like a real one, so the password is hashed with all rounds
+        let decoded_lengths = |password_hash: &str| {
+            let parts: Vec<String> = password_hash.split('$').map(String::from).collect();
+            let decode = |part: &str| BASE64_URL_SAFE_NO_PAD.decode(part).unwrap().len();
+            (
+                parts[0].clone(),
+                parts[1].clone(),
+                decode(&parts[2]),
+                decode(&parts[3]),
+            )
+        };
+        assert_eq!(
+            decoded_lengths(&dummy_hash()),
+            decoded_lengths(&hash_password("correct horse"))
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! Stop names of CIS JŘ, `Obec,Část obce,Bližší místo [okres]`.
+//!
+//! Names are parsed leniently, spaces around the parts are ignored and empty parts are missing:
+//!
+//! | name                           | municipality | part   | local            | district |
+//! |--------------------------------|--------------|--------|------------------|----------|
+//! | `Florenc`                      | `Florenc`    |        |                  |          |
+//! | `Praha,Florenc`                | `Praha`      |        | `Florenc`        |          |
+//! | `Kolín, nádraží`               | `Kolín`      |        | `nádraží`        |          |
+//! | `Brno,,Hlavní nádraží`         | `Brno`       |        | `Hlavní nádraží` |          |
+//! | `Město,Část,Zastávka`          | `Město`      | `Část` | `Zastávka`       |          |
+//! | `Město,Část,`                  | `Město`      | `Část` |                  |          |
+//! | `Praha,Florenc [AB]`           | `Praha`      |        | `Florenc`        | `AB`     |
+//! | `Lhota [ZL],rozc.`             | `Lhota`      |        | `rozc.`          | `ZL`     |
+//! | `Lhota,,rozc.[ZL]`             | `Lhota`      |        | `rozc.`          | `ZL`     |
+//! | `Nová Ves [PZ]`                | `Nová Ves`   |        |                  | `PZ`     |
+//! | `Ves,Část,Místo,u mostu`       | `Ves`        | `Část` | `Místo,u mostu`  |          |
+
+use serde::Serialize;
+
+/// Stop name split into its parts.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct StopName {
+    municipality: String,
+    /// Part of the municipality, only given by names with three parts.
+    part: Option<String>,
+    /// Name of the stop within the municipality or its part.
+    local: Option<String>,
+    /// Code of the district (okres) telling apart municipalities with the same name.
+    district: Option<String>,
+}
+
+impl StopName {
+    /// Parses the name, a name without commas is a municipality.
+    pub fn parse(name: &str) -> Self {
+        let (name, district) = split_district(name);
+        let mut parts = name.splitn(3, ',').map(str::trim);
+        let municipality = parts.next().unwrap_or_default().to_string();
+        let (part, local) = match (parts.next(), parts.next()) {
+            (Some(local), None) => (None, non_empty(local)),
+            (Some(part), Some(local)) => (non_empty(part), non_empty(local)),
+            _ => (None, None),
+        };
+        Self {
+            municipality,
+            part,
+            local,
+            district,
+        }
+    }
+
+    pub fn municipality(&self) -> &str {
+        &self.municipality
+    }
+
+    pub fn part(&self) -> Option<&str> {
+        self.part.as_deref()
+    }
+
+    pub fn local(&self) -> Option<&str> {
+        self.local.as_deref()
+    }
+
+    pub fn district(&self) -> Option<&str> {
+        self.district.as_deref()
+    }
+}
+
+/// Removes the first `[...]` from the name, returning its content as the district.
+fn split_district(name: &str) -> (String, Option<String>) {
+    let Some(start) = name.find('[') else {
+        return (name.to_string(), None);
+    };
+    let Some(len) = name[start..].find(']') else {
+        return (name.to_string(), None);
+    };
+    let district = non_empty(name[start + 1..start + len].trim());
+    let mut rest = name[..start].trim_end().to_string();
+    rest.push_str(&name[start + len + 1..]);
+    (rest, district)
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Parts<'a> = (&'a str, Option<&'a str>, Option<&'a str>, Option<&'a str>);
+
+    fn check(name: &str, expected: Parts) {
+        let parsed = StopName::parse(name);
+        let parts = (
+            parsed.municipality(),
+            parsed.part(),
+            parsed.local(),
+            parsed.district(),
+        );
+        assert_eq!(parts, expected, "{}", name);
+    }
+
+    #[test]
+    fn documented_names() {
+        let table = [
+            ("Florenc", ("Florenc", None, None, None)),
+            ("Praha,Florenc", ("Praha", None, Some("Florenc"), None)),
+            ("Kolín, nádraží", ("Kolín", None, Some("nádraží"), None)),
+            (
+                "Brno,,Hlavní nádraží",
+                ("Brno", None, Some("Hlavní nádraží"), None),
+            ),
+            (
+                "Město,Část,Zastávka",
+                ("Město", Some("Část"), Some("Zastávka"), None),
+            ),
+            ("Město,Část,", ("Město", Some("Část"), None, None)),
+            (
+                "Praha,Florenc [AB]",
+                ("Praha", None, Some("Florenc"), Some("AB")),
+            ),
+            (
+                "Lhota [ZL],rozc.",
+                ("Lhota", None, Some("rozc."), Some("ZL")),
+            ),
+            (
+                "Lhota,,rozc.[ZL]",
+                ("Lhota", None, Some("rozc."), Some("ZL")),
+            ),
+            ("Nová Ves [PZ]", ("Nová Ves", None, None, Some("PZ"))),
+            (
+                "Ves,Část,Místo,u mostu",
+                ("Ves", Some("Část"), Some("Místo,u mostu"), None),
+            ),
+        ];
+        for (name, expected) in table {
+            check(name, expected);
+        }
+    }
+
+    #[test]
+    fn unclosed_bracket_is_kept_in_the_name() {
+        check(
+            "Praha [AB,Florenc",
+            ("Praha [AB", None, Some("Florenc"), None),
+        );
+        check("Lhota ]ZL[", ("Lhota ]ZL[", None, None, None));
+    }
+
+    #[test]
+    fn empty_brackets_give_no_district() {
+        check("Lhota [ ],rozc.", ("Lhota", None, Some("rozc."), None));
+    }
+
+    #[test]
+    fn only_the_first_bracket_is_the_district() {
+        check(
+            "Lhota [ZL],rozc. [PZ]",
+            ("Lhota", None, Some("rozc. [PZ]"), Some("ZL")),
+        );
+    }
+
+    #[test]
+    fn empty_municipality() {
+        check(",Florenc", ("", None, Some("Florenc"), None));
+        check("", ("", None, None, None));
+        check("[AB]", ("", None, None, Some("AB")));
+    }
+
+    #[test]
+    fn whitespace_only_parts_are_missing() {
+        check("Praha,  ", ("Praha", None, None, None));
+        check("Praha , \t, ", ("Praha", None, None, None));
+        check(
+            "  Praha , Černý Most ,  Terminál ",
+            ("Praha", Some("Černý Most"), Some("Terminál"), None),
+        );
+    }
+
+    #[test]
+    fn extra_commas_stay_in_the_local_name() {
+        check(
+            "Ves,Část,Místo,",
+            ("Ves", Some("Část"), Some("Místo,"), None),
+        );
+        check("Ves,,,", ("Ves", None, Some(","), None));
+    }
+}
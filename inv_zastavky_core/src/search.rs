@@ -0,0 +1,379 @@
+use crate::model::StopId;
+use crate::names::StopName;
+use serde::Serialize;
+use sqlx::pool::PoolConnection;
+use sqlx::{Row, Sqlite, query};
+
+/// Table in which the name was found.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchSource {
+    /// Name of a station in `el_station_names`.
+    Station,
+    /// City from `sl_base_cities`.
+    City,
+    /// Station name used in chains in `sl_chains`.
+    Chain,
+}
+
+/// Name matching searched text, without diacritics and case.
+#[derive(Serialize, Debug)]
+pub struct SearchMatch {
+    name: String,
+    source: MatchSource,
+    /// The name starts with the searched text.
+    prefix: bool,
+    /// Parts of station and chain station names, cities are not parsed.
+    stop_name: Option<StopName>,
+    /// District code and region of cities, if they were imported.
+    district: Option<String>,
+    region: Option<String>,
+    /// RÚIAN codes of cities and of the municipality of municipality parts.
+    code: Option<i64>,
+    municipality_code: Option<i64>,
+    stop_id: Option<StopId>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+}
+
+impl SearchMatch {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> MatchSource {
+        self.source
+    }
+
+    pub fn prefix(&self) -> bool {
+        self.prefix
+    }
+
+    pub fn stop_name(&self) -> Option<&StopName> {
+        self.stop_name.as_ref()
+    }
+
+    pub fn district(&self) -> Option<&str> {
+        self.district.as_deref()
+    }
+
+    pub fn region(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+
+    pub fn code(&self) -> Option<i64> {
+        self.code
+    }
+
+    pub fn municipality_code(&self) -> Option<i64> {
+        self.municipality_code
+    }
+
+    pub fn stop_id(&self) -> Option<StopId> {
+        self.stop_id
+    }
+
+    pub fn lat(&self) -> Option<f64> {
+        self.lat
+    }
+
+    pub fn lon(&self) -> Option<f64> {
+        self.lon
+    }
+
+    /// Prefix matches first, then shorter names, so exact names come before longer ones.
+    fn rank(&self) -> (bool, usize, &str, MatchSource) {
+        (
+            !self.prefix,
+            self.name.chars().count(),
+            &self.name,
+            self.source,
+        )
+    }
+}
+
+/// FTS5 queries of the searched text, see https://www.sqlite.org/fts5.html#full_text_query_syntax.
+struct FtsQuery {
+    /// Names starting with the text, the last word may be incomplete.
+    prefix: String,
+    /// Names containing all words of the text as word prefixes.
+    any: String,
+}
+
+impl FtsQuery {
+    /// Returns `None` if the text has no words, wildcards and punctuation are ignored.
+    fn new(text: &str) -> Option<Self> {
+        let words: Vec<&str> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        if words.is_empty() {
+            return None;
+        }
+        let phrase: Vec<String> = words.iter().map(|word| format!("\"{}\"", word)).collect();
+        Some(Self {
+            prefix: format!("^{}*", phrase.join(" + ")),
+            any: phrase
+                .iter()
+                .map(|word| format!("{}*", word))
+                .collect::<Vec<_>>()
+                .join(" "),
+        })
+    }
+}
+
+/// Searches station names, cities and chain station names, best matches first.
+pub async fn search(
+    db: &mut PoolConnection<Sqlite>,
+    text: &str,
+    limit: u32,
+) -> Result<Vec<SearchMatch>, sqlx::Error> {
+    let mut matches = search_stations(db, text, limit).await?;
+    matches.extend(search_cities(db, text, limit).await?);
+    matches.extend(search_chains(db, text, limit).await?);
+    matches.sort_by(|left, right| left.rank().cmp(&right.rank()));
+    matches.truncate(limit as usize);
+    Ok(matches)
+}
+
+pub async fn search_stations(
+    db: &mut PoolConnection<Sqlite>,
+    text: &str,
+    limit: u32,
+) -> Result<Vec<SearchMatch>, sqlx::Error> {
+    let Some(fts) = FtsQuery::new(text) else {
+        return Ok(Vec::new());
+    };
+    let rows = query(
+        "SELECT el_station_names.stop_id, el_station_names.station_name, lat, lon,
+    el_station_names.id IN (
+        SELECT rowid FROM el_station_names_fts WHERE el_station_names_fts MATCH $1
+    ) AS prefix
+FROM el_station_names_fts
+    JOIN el_station_names ON el_station_names.id = el_station_names_fts.rowid
+    JOIN el_station_pos ON el_station_pos.stop_id = el_station_names.stop_id
+WHERE el_station_names_fts MATCH $2
+ORDER BY prefix DESC, length(el_station_names.station_name), el_station_names.station_name,
+    el_station_names.stop_id
+LIMIT $3;",
+    )
+    .bind(&fts.prefix)
+    .bind(&fts.any)
+    .bind(limit)
+    .fetch_all(&mut **db)
+    .await?;
+    let mut matches = Vec::new();
+    for row in rows {
+        let name: String = row.try_get(1)?;
+        matches.push(SearchMatch {
+            stop_id: row.try_get(0)?,
+            stop_name: Some(StopName::parse(&name)),
+            name,
+            lat: row.try_get(2)?,
+            lon: row.try_get(3)?,
+            prefix: row.try_get(4)?,
+            source: MatchSource::Station,
+            district: None,
+            region: None,
+            code: None,
+            municipality_code: None,
+        });
+    }
+    Ok(matches)
+}
+
+pub async fn search_cities(
+    db: &mut PoolConnection<Sqlite>,
+    text: &str,
+    limit: u32,
+) -> Result<Vec<SearchMatch>, sqlx::Error> {
+    let Some(fts) = FtsQuery::new(text) else {
+        return Ok(Vec::new());
+    };
+    let rows = query(
+        "SELECT sl_base_cities.city_name, lat, lon, district_code, region_name,
+    sl_base_cities.ruian_code, municipality_code,
+    sl_base_cities.id IN (
+        SELECT rowid FROM sl_base_cities_fts WHERE sl_base_cities_fts MATCH $1
+    ) AS prefix
+FROM sl_base_cities_fts
+    JOIN sl_base_cities ON sl_base_cities.id = sl_base_cities_fts.rowid
+    LEFT JOIN sl_districts USING (district_code)
+WHERE sl_base_cities_fts MATCH $2
+ORDER BY prefix DESC, length(sl_base_cities.city_name), sl_base_cities.city_name, lat, lon
+LIMIT $3;",
+    )
+    .bind(&fts.prefix)
+    .bind(&fts.any)
+    .bind(limit)
+    .fetch_all(&mut **db)
+    .await?;
+    let mut matches = Vec::new();
+    for row in rows {
+        matches.push(SearchMatch {
+            name: row.try_get(0)?,
+            lat: row.try_get(1)?,
+            lon: row.try_get(2)?,
+            district: row.try_get(3)?,
+            region: row.try_get(4)?,
+            code: row.try_get(5)?,
+            municipality_code: row.try_get(6)?,
+            prefix: row.try_get(7)?,
+            source: MatchSource::City,
+            stop_name: None,
+            stop_id: None,
+        });
+    }
+    Ok(matches)
+}
+
+/// Searches distinct station names of chains, chains themselves have no position.
+pub async fn search_chains(
+    db: &mut PoolConnection<Sqlite>,
+    text: &str,
+    limit: u32,
+) -> Result<Vec<SearchMatch>, sqlx::Error> {
+    let Some(fts) = FtsQuery::new(text) else {
+        return Ok(Vec::new());
+    };
+    let rows = query(
+        "SELECT sl_chains.station_name,
+    max(sl_chains.id IN (
+        SELECT rowid FROM sl_chains_fts WHERE sl_chains_fts MATCH $1
+    )) AS prefix
+FROM sl_chains_fts
+    JOIN sl_chains ON sl_chains.id = sl_chains_fts.rowid
+WHERE sl_chains_fts MATCH $2
+GROUP BY sl_chains.station_name
+ORDER BY prefix DESC, length(sl_chains.station_name), sl_chains.station_name
+LIMIT $3;",
+    )
+    .bind(&fts.prefix)
+    .bind(&fts.any)
+    .bind(limit)
+    .fetch_all(&mut **db)
+    .await?;
+    let mut matches = Vec::new();
+    for row in rows {
+        let name: String = row.try_get(0)?;
+        matches.push(SearchMatch {
+            stop_name: Some(StopName::parse(&name)),
+            name,
+            prefix: row.try_get(1)?,
+            source: MatchSource::Chain,
+            district: None,
+            region: None,
+            code: None,
+            municipality_code: None,
+            stop_id: None,
+            lat: None,
+            lon: None,
+        });
+    }
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::migrated_db;
+
+    async fn db_with_names() -> PoolConnection<Sqlite> {
+        let mut db = migrated_db().await;
+        query(
+            "INSERT INTO el_station_pos (stop_id, lat, lon) VALUES (1, 48.97, 14.47), (2, 49.2, 16.6);
+INSERT INTO el_station_names (stop_id, station_name) VALUES
+    (1, 'České Budějovice,aut.nádr.'), (2, 'Horní Brno'), (2, 'Brno,Hlavní nádraží');
+INSERT INTO sl_base_cities (city_name, lat, lon) VALUES
+    ('České Budějovice', 48.97, 14.47), ('Brno', 49.19, 16.61);
+INSERT INTO sl_chains (chain_hash, station_name, pos) VALUES
+    ('h1', 'Brno,Zvonařka', 0), ('h1', 'České Budějovice,aut.nádr.', 1),
+    ('h2', 'Brno,Zvonařka', 0);",
+        )
+        .execute(&mut *db)
+        .await
+        .unwrap();
+        db
+    }
+
+    fn names(matches: &[SearchMatch]) -> Vec<(&str, MatchSource, bool)> {
+        matches
+            .iter()
+            .map(|found| (found.name(), found.source(), found.prefix()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn diacritics_and_case_are_ignored() {
+        let mut db = db_with_names().await;
+        let matches = search(&mut db, "ceske BUDEJOVICE", 10).await.unwrap();
+        assert_eq!(
+            names(&matches),
+            [
+                ("České Budějovice", MatchSource::City, true),
+                ("České Budějovice,aut.nádr.", MatchSource::Station, true),
+                ("České Budějovice,aut.nádr.", MatchSource::Chain, true),
+            ]
+        );
+        assert_eq!(matches[1].stop_id(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn prefix_matches_rank_first() {
+        let mut db = db_with_names().await;
+        let matches = search(&mut db, "brn", 10).await.unwrap();
+        assert_eq!(
+            names(&matches),
+            [
+                ("Brno", MatchSource::City, true),
+                ("Brno,Zvonařka", MatchSource::Chain, true),
+                ("Brno,Hlavní nádraží", MatchSource::Station, true),
+                ("Horní Brno", MatchSource::Station, false),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn all_words_must_match() {
+        let mut db = db_with_names().await;
+        let matches = search_stations(&mut db, "brno hlav", 10).await.unwrap();
+        assert_eq!(
+            names(&matches),
+            [("Brno,Hlavní nádraží", MatchSource::Station, true)]
+        );
+        let matches = search_stations(&mut db, "hlavni", 10).await.unwrap();
+        assert_eq!(
+            names(&matches),
+            [("Brno,Hlavní nádraží", MatchSource::Station, false)]
+        );
+    }
+
+    #[tokio::test]
+    async fn text_without_words_finds_nothing() {
+        let mut db = db_with_names().await;
+        assert!(search(&mut db, " *\"^ ", 10).await.unwrap().is_empty());
+        assert!(FtsQuery::new("--").is_none());
+    }
+
+    #[tokio::test]
+    async fn index_follows_changed_and_removed_names() {
+        let mut db = db_with_names().await;
+        query(
+            "UPDATE el_station_names SET station_name = 'Brno,Křenová' WHERE station_name = 'Horní Brno';
+DELETE FROM sl_base_cities WHERE city_name = 'Brno';
+VACUUM;",
+        )
+        .execute(&mut *db)
+        .await
+        .unwrap();
+        let matches = search(&mut db, "brno", 10).await.unwrap();
+        assert_eq!(
+            names(&matches),
+            [
+                ("Brno,Křenová", MatchSource::Station, true),
+                ("Brno,Zvonařka", MatchSource::Chain, true),
+                ("Brno,Hlavní nádraží", MatchSource::Station, true),
+            ]
+        );
+    }
+}
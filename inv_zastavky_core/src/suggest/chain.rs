@@ -1,9 +1,11 @@
+use crate::error::Error;
 use crate::model::StopId;
 use crate::model::base_city::BaseCity;
 use crate::model::chain_station::ChainStation;
 use crate::model::station::Station;
+use crate::names::StopName;
 use crate::utils::geo::{approx_distance, approx_len};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::Sqlite;
 use sqlx::pool::PoolConnection;
 use std::collections::HashMap;
@@ -11,6 +13,7 @@ use std::collections::HashMap;
 #[derive(Debug, Serialize)]
 pub struct ChainStationsSuggestion {
     len: f64,
+    cost: f64,
     chain_hash: String,
     path: Vec<Option<(f64, f64, StopId)>>,
 }
@@ -19,179 +22,435 @@ pub struct ChainStationsSuggestion {
 pub struct ChainCitySuggestion {
     len: f64,
     chain_hash: String,
+    /// Located cities, consecutive positions in the same place share one point.
     path: Vec<(f64, f64)>,
+    /// Whether a city was found for each position of the chain.
+    matches: Vec<CityMatch>,
+    /// Index of the point of `path` for each position of the chain, missing if it has no city.
+    positions: Vec<Option<usize>>,
 }
 
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CityMatch {
+    Matched,
+    Missing,
+}
+
+/// Parameters of the best-path search done by [`chain_options`] and [`path_options`].
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ChainSearchParams {
+    /// Maximal number of returned suggestions.
+    pub top_k: usize,
+    /// Cost in kilometres added for every chain position without a candidate station.
+    pub unlocated_penalty: f64,
+}
+
+impl Default for ChainSearchParams {
+    fn default() -> Self {
+        ChainSearchParams {
+            top_k: 10,
+            unlocated_penalty: 5.0,
+        }
+    }
+}
+
+/// Finds the `top_k` shortest paths through the cities named by the chain stations.
+///
+/// Stations with a district code are matched to cities of that district only, the path picks
+/// between same-named cities of other stations. A station naming a part of its municipality is
+/// placed at the part, if the part is known. Consecutive stations in the same city are visited
+/// once and positions without a known city are left out of the path.
 pub async fn path_options(
     db_pool: &mut PoolConnection<Sqlite>,
     chain_hash: &str,
     city_remap: &HashMap<String, String>,
-) -> anyhow::Result<Vec<ChainCitySuggestion>> {
-    // get names of cities, if they are part of the name
+    params: &ChainSearchParams,
+) -> Result<Vec<ChainCitySuggestion>, Error> {
+    // get names, districts and parts of cities, if they are part of the name
     let mut city_chain = Vec::new();
-    for station in ChainStation::get_by_chain_hash(db_pool, chain_hash).await? {
-        let city_name =
-            if let Some(city_name) = station.name().split_once(" [").map(|(left, _)| left) {
-                city_name.to_string()
-            } else if let Some(city_name) = station.name().split_once(",").map(|(left, _)| left) {
-                city_name.to_string()
-            } else {
-                station.name().to_string()
-            };
-        if let Some(city_name) = city_remap.get(&city_name) {
-            city_chain.push(city_name.clone());
-        } else {
-            city_chain.push(city_name);
-        }
+    for station in chain_stations(db_pool, chain_hash).await? {
+        let stop_name = StopName::parse(station.name());
+        let city_name = stop_name.municipality();
+        let city_name = city_remap.get(city_name).map_or(city_name, String::as_str);
+        let part = stop_name.part();
+        city_chain.push((
+            city_name.to_string(),
+            stop_name.district().map(str::to_string),
+            part.map(str::to_string),
+        ));
     }
 
     // get position of cities
     let mut cities = HashMap::new();
     for chain_city in &city_chain {
-        let mut cities_pos: Vec<BaseCity> = Vec::new();
-        for city_pos in BaseCity::get_by_name(db_pool, chain_city.as_str()).await? {
-            if !cities_pos.iter().any(|other| {
-                approx_distance(other.lat(), other.lon(), city_pos.lat(), city_pos.lon()) < 0.5
-            }) {
-                cities_pos.push(city_pos);
-            }
+        if cities.contains_key(chain_city) {
+            continue;
         }
-        cities.insert(chain_city.to_string(), cities_pos);
-    }
-
-    let mut paths: Vec<Vec<(f64, f64)>> = Vec::new();
-    // try to get first city
-    if let Some(possible_cities) = cities.get(&city_chain[0]) {
-        let mut new_paths = Vec::new();
-        for city in possible_cities {
-            let mut new_path = Vec::new();
-            new_path.push((city.lat(), city.lon()));
-            new_paths.push(new_path);
+        let (city_name, district, part) = chain_city;
+        let mut cities_pos: Vec<(f64, f64)> = Vec::new();
+        for mut city_pos in BaseCity::get_by_name(db_pool, city_name, district.as_deref()).await? {
+            if let (Some(code), Some(part)) = (city_pos.code(), part)
+                && let Some(part_pos) = BaseCity::get_part(db_pool, code, part).await?
+            {
+                city_pos = part_pos;
+            }
+            if !cities_pos
+                .iter()
+                .any(|(lat, lon)| approx_distance(*lat, *lon, city_pos.lat(), city_pos.lon()) < 0.5)
+            {
+                cities_pos.push((city_pos.lat(), city_pos.lon()));
+            }
         }
-        paths = new_paths;
+        cities.insert(chain_city.clone(), cities_pos);
     }
 
-    for window in city_chain.windows(2) {
-        let prev = &window[0];
-        let cur = &window[1];
-        if prev == cur {
-            continue;
-        }
-        if let Some(possible_cities) = cities.get(cur.as_str()) {
-            let mut new_paths = Vec::new();
-            if paths.is_empty() {
-                for city in possible_cities {
-                    let mut new_path = Vec::new();
-                    new_path.push((city.lat(), city.lon()));
-                    new_paths.push(new_path);
-                }
+    let matches = city_chain
+        .iter()
+        .map(|city| {
+            if cities[city].is_empty() {
+                CityMatch::Missing
             } else {
-                for path in paths {
-                    for city in possible_cities {
-                        let mut new_path = path.clone();
-                        new_path.push((city.lat(), city.lon()));
-                        new_paths.push(new_path);
-                    }
-                }
+                CityMatch::Matched
             }
-            paths = new_paths;
-        }
-        // try to prevent OOM
-        if paths.len() > 100_000 {
-            break;
+        })
+        .collect::<Vec<_>>();
+
+    // stops of the same city, or of the same place of a city, are one layer
+    let mut layers: Vec<Vec<(f64, f64)>> = Vec::new();
+    let mut position_layers = Vec::with_capacity(city_chain.len());
+    for city in &city_chain {
+        if layers.last() != Some(&cities[city]) {
+            layers.push(cities[city].clone());
         }
+        position_layers.push(layers.len() - 1);
     }
 
-    let paths = paths
-        .iter()
-        .map(|path| ChainCitySuggestion {
-            len: approx_len(&path.iter().map(|(lat, lon)| (*lat, *lon)).collect()),
-            chain_hash: chain_hash.to_string(),
-            path: path.to_owned(),
+    let paths = best_paths(&layers, params.top_k, 0.0)
+        .into_iter()
+        .map(|(len, path)| {
+            // unlocated layers have no point in the path
+            let mut layer_points = Vec::with_capacity(path.len());
+            let mut points = 0;
+            for candidate in &path {
+                layer_points.push(candidate.map(|_| points));
+                points += usize::from(candidate.is_some());
+            }
+            ChainCitySuggestion {
+                len,
+                chain_hash: chain_hash.to_string(),
+                path: path.into_iter().flatten().collect(),
+                matches: matches.clone(),
+                positions: position_layers
+                    .iter()
+                    .map(|layer| layer_points[*layer])
+                    .collect(),
+            }
         })
         .collect::<Vec<_>>();
 
     Ok(paths)
 }
 
+/// Loads stations of the chain, fails if the chain does not exist.
+async fn chain_stations(
+    db_pool: &mut PoolConnection<Sqlite>,
+    chain_hash: &str,
+) -> Result<Vec<ChainStation>, Error> {
+    let stations = ChainStation::get_by_chain_hash(db_pool, chain_hash).await?;
+    if stations.is_empty() {
+        return Err(Error::not_found(
+            "chain_not_found",
+            format!("no chain with hash {}", chain_hash),
+        ));
+    }
+    Ok(stations)
+}
+
+/// Finds the `top_k` cheapest assignments of existing stations to the chain.
+///
+/// The cost of a path is its length plus `unlocated_penalty` for every position without any
+/// candidate station.
 pub async fn chain_options(
     db_pool: &mut PoolConnection<Sqlite>,
     chain_hash: &str,
-) -> anyhow::Result<Vec<ChainStationsSuggestion>> {
-    let station_chain = ChainStation::get_by_chain_hash(db_pool, chain_hash).await?;
+    params: &ChainSearchParams,
+) -> Result<Vec<ChainStationsSuggestion>, Error> {
+    let station_chain = chain_stations(db_pool, chain_hash).await?;
 
     // get position of stations
     let mut stations = HashMap::new();
     for chain_station in &station_chain {
-        stations.insert(
-            chain_station.name().to_string(),
-            Station::get_by_name(db_pool, chain_station.name()).await?,
-        );
+        if !stations.contains_key(chain_station.name()) {
+            let candidates = Station::get_by_name(db_pool, chain_station.name())
+                .await?
+                .iter()
+                .map(|station| (station.lat(), station.lon(), station.stop_id()))
+                .collect::<Vec<_>>();
+            stations.insert(chain_station.name().to_string(), candidates);
+        }
     }
 
-    let mut paths: Vec<Vec<Option<(f64, f64, StopId)>>> = Vec::new();
-    let mut chain_station_iter = station_chain.iter();
-    // try to get first city
-    if let Some(chain_station) = chain_station_iter.next() {
-        if let Some(possible_stations) = stations.get(chain_station.name()) {
-            for station in possible_stations {
-                let mut new_path = Vec::new();
-                new_path.push(Some((station.lat(), station.lon(), station.stop_id())));
-                paths.push(new_path);
-            }
-            if possible_stations.is_empty() {
-                let mut new_path = Vec::new();
-                new_path.push(None);
-                paths.push(new_path);
+    let layers = station_chain
+        .iter()
+        .map(|chain_station| stations[chain_station.name()].clone())
+        .collect::<Vec<_>>();
+
+    let paths = best_paths(&layers, params.top_k, params.unlocated_penalty)
+        .into_iter()
+        .map(|(cost, path)| {
+            let line = path
+                .iter()
+                .flatten()
+                .map(Located::position)
+                .collect::<Vec<_>>();
+            ChainStationsSuggestion {
+                len: approx_len(&line),
+                cost,
+                chain_hash: chain_hash.to_string(),
+                path,
             }
-        } else {
-            let mut new_path = Vec::new();
-            new_path.push(None);
-            paths.push(new_path);
+        })
+        .collect::<Vec<_>>();
+
+    Ok(paths)
+}
+
+/// Candidate of the best-path search, a position optionally followed by what is placed there.
+trait Located: Copy {
+    /// `(lat, lon)` of the candidate.
+    fn position(&self) -> (f64, f64);
+}
+
+impl Located for (f64, f64) {
+    fn position(&self) -> (f64, f64) {
+        *self
+    }
+}
+
+impl Located for (f64, f64, StopId) {
+    fn position(&self) -> (f64, f64) {
+        (self.0, self.1)
+    }
+}
+
+/// Candidate path ending in a state of the search, pointing to its predecessor.
+struct PathEntry {
+    cost: f64,
+    prev: Option<(usize, usize)>,
+}
+
+/// Node of the best-path search, one layer with one of its candidates.
+struct PathState<T> {
+    candidate: Option<T>,
+    /// Last located position on the paths going through this state.
+    anchor: Option<(f64, f64)>,
+    entries: Vec<PathEntry>,
+}
+
+impl<T: Located> PathState<T> {
+    fn location(&self) -> Option<(f64, f64)> {
+        self.candidate
+            .map(|candidate| candidate.position())
+            .or(self.anchor)
+    }
+}
+
+/// Finds the `top_k` cheapest paths picking one candidate from every layer.
+///
+/// The cost is the distance between consecutive located candidates plus `unlocated_penalty`
+/// for every layer without candidates. Each state keeps only its `top_k` best incoming paths,
+/// so the search is linear in the number of layers.
+fn best_paths<T: Located>(
+    layers: &[Vec<T>],
+    top_k: usize,
+    unlocated_penalty: f64,
+) -> Vec<(f64, Vec<Option<T>>)> {
+    let top_k = top_k.max(1);
+    let mut states: Vec<Vec<PathState<T>>> = Vec::with_capacity(layers.len());
+    for candidates in layers {
+        let layer = match states.last() {
+            None if candidates.is_empty() => vec![PathState {
+                candidate: None,
+                anchor: None,
+                entries: vec![PathEntry {
+                    cost: unlocated_penalty,
+                    prev: None,
+                }],
+            }],
+            None => candidates
+                .iter()
+                .map(|candidate| PathState {
+                    candidate: Some(*candidate),
+                    anchor: None,
+                    entries: vec![PathEntry {
+                        cost: 0.0,
+                        prev: None,
+                    }],
+                })
+                .collect(),
+            // unlocated layer, every state keeps its own last known location
+            Some(prev_layer) if candidates.is_empty() => prev_layer
+                .iter()
+                .enumerate()
+                .map(|(state_idx, state)| PathState {
+                    candidate: None,
+                    anchor: state.location(),
+                    entries: (0..state.entries.len())
+                        .map(|entry_idx| PathEntry {
+                            cost: state.entries[entry_idx].cost + unlocated_penalty,
+                            prev: Some((state_idx, entry_idx)),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            Some(prev_layer) => candidates
+                .iter()
+                .map(|candidate| {
+                    let mut entries = Vec::new();
+                    let (candidate_lat, candidate_lon) = candidate.position();
+                    for (state_idx, state) in prev_layer.iter().enumerate() {
+                        let step = state.location().map_or(0.0, |(lat, lon)| {
+                            approx_distance(lat, lon, candidate_lat, candidate_lon)
+                        });
+                        for (entry_idx, entry) in state.entries.iter().enumerate() {
+                            entries.push(PathEntry {
+                                cost: entry.cost + step,
+                                prev: Some((state_idx, entry_idx)),
+                            });
+                        }
+                    }
+                    entries.sort_by(|a, b| a.cost.total_cmp(&b.cost));
+                    entries.truncate(top_k);
+                    PathState {
+                        candidate: Some(*candidate),
+                        anchor: None,
+                        entries,
+                    }
+                })
+                .collect(),
+        };
+        states.push(layer);
+    }
+
+    let Some(last_layer) = states.last() else {
+        return Vec::new();
+    };
+    let mut ends = Vec::new();
+    for (state_idx, state) in last_layer.iter().enumerate() {
+        for (entry_idx, entry) in state.entries.iter().enumerate() {
+            ends.push((entry.cost, state_idx, entry_idx));
         }
     }
+    ends.sort_by(|a, b| a.0.total_cmp(&b.0));
+    ends.truncate(top_k);
 
-    for chain_station in chain_station_iter {
-        if let Some(possible_stations) = stations.get(chain_station.name()) {
-            let mut new_paths = Vec::new();
-            for path in paths {
-                for station in possible_stations {
-                    let mut new_path = path.clone();
-                    new_path.push(Some((station.lat(), station.lon(), station.stop_id())));
-                    new_paths.push(new_path);
-                }
-                if possible_stations.is_empty() {
-                    let mut new_path = path.clone();
-                    new_path.push(None);
-                    new_paths.push(new_path);
-                }
-            }
-            paths = new_paths;
-        } else {
-            let mut new_paths = Vec::new();
-            for path in paths {
-                let mut new_path = path.clone();
-                new_path.push(None);
-                new_paths.push(new_path);
-            }
-            paths = new_paths;
+    let mut paths = Vec::with_capacity(ends.len());
+    for (cost, state_idx, entry_idx) in ends {
+        let mut path = Vec::with_capacity(states.len());
+        let mut cursor = Some((state_idx, entry_idx));
+        for layer in states.iter().rev() {
+            let Some((state_idx, entry_idx)) = cursor else {
+                break;
+            };
+            let state = &layer[state_idx];
+            path.push(state.candidate);
+            cursor = state.entries[entry_idx].prev;
         }
+        path.reverse();
+        paths.push((cost, path));
     }
+    paths
+}
 
-    let paths = paths
-        .iter()
-        .map(|path| ChainStationsSuggestion {
-            len: approx_len(
-                &path
-                    .iter()
-                    .filter_map(|a| *a)
-                    .map(|(lat, lon, _)| (lat, lon))
-                    .collect(),
-            ),
-            chain_hash: chain_hash.to_string(),
-            path: path.to_owned(),
-        })
-        .collect::<Vec<_>>();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok(paths)
+    type Candidate = (f64, f64, StopId);
+
+    fn ids(path: &[Option<Candidate>]) -> Vec<Option<StopId>> {
+        path.iter()
+            .map(|candidate| candidate.map(|(_, _, id)| id))
+            .collect()
+    }
+
+    fn distance(from: Candidate, to: Candidate) -> f64 {
+        approx_distance(from.0, from.1, to.0, to.1)
+    }
+
+    fn assert_cost(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "cost {} instead of {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn paths_are_ordered_by_cost_and_truncated_to_top_k() {
+        let (a, b) = ((50.0, 14.0, 0), (50.0, 15.0, 1));
+        let (c, d) = ((50.0, 14.1, 2), (50.0, 15.3, 3));
+        let paths = best_paths(&[vec![a, b], vec![c, d]], 3, 0.0);
+
+        assert_eq!(paths.len(), 3);
+        assert_eq!(ids(&paths[0].1), [Some(0), Some(2)]);
+        assert_eq!(ids(&paths[1].1), [Some(1), Some(3)]);
+        assert_eq!(ids(&paths[2].1), [Some(1), Some(2)]);
+        assert_cost(paths[0].0, distance(a, c));
+        assert_cost(paths[1].0, distance(b, d));
+        assert_cost(paths[2].0, distance(b, c));
+    }
+
+    #[test]
+    fn zero_top_k_returns_the_best_path() {
+        let paths = best_paths(&[vec![(50.0, 14.0, 0), (50.0, 15.0, 1)]], 0, 0.0);
+        assert_eq!(paths.len(), 1);
+    }
+
+    #[test]
+    fn unlocated_layers_add_the_penalty() {
+        let (a, b) = ((50.0, 14.0, 0), (50.0, 14.1, 1));
+        let paths = best_paths(&[vec![a], vec![], vec![b]], 10, 5.0);
+
+        assert_eq!(paths.len(), 1);
+        assert_eq!(ids(&paths[0].1), [Some(0), None, Some(1)]);
+        assert_cost(paths[0].0, 5.0 + distance(a, b));
+    }
+
+    #[test]
+    fn unlocated_layers_carry_the_last_location() {
+        let (a, b) = ((50.0, 14.0, 0), (50.0, 15.0, 1));
+        let c = (50.0, 14.1, 2);
+        let paths = best_paths(&[vec![a, b], vec![], vec![], vec![c]], 10, 5.0);
+
+        // the distance is measured from the candidate before the unlocated layers
+        assert_eq!(paths.len(), 2);
+        assert_eq!(ids(&paths[0].1), [Some(0), None, None, Some(2)]);
+        assert_eq!(ids(&paths[1].1), [Some(1), None, None, Some(2)]);
+        assert_cost(paths[0].0, 10.0 + distance(a, c));
+        assert_cost(paths[1].0, 10.0 + distance(b, c));
+    }
+
+    #[test]
+    fn unlocated_first_layer() {
+        let (a, b) = ((50.0, 14.0, 0), (50.0, 14.1, 1));
+        let paths = best_paths(&[vec![], vec![a], vec![b]], 10, 5.0);
+
+        assert_eq!(paths.len(), 1);
+        assert_eq!(ids(&paths[0].1), [None, Some(0), Some(1)]);
+        assert_cost(paths[0].0, 5.0 + distance(a, b));
+
+        let paths = best_paths::<Candidate>(&[vec![], vec![]], 10, 5.0);
+        assert_eq!(paths.len(), 1);
+        assert_eq!(ids(&paths[0].1), [None, None]);
+        assert_cost(paths[0].0, 10.0);
+    }
+
+    #[test]
+    fn no_layers_give_no_path() {
+        assert!(best_paths::<Candidate>(&[], 10, 5.0).is_empty());
+    }
 }
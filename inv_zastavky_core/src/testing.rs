@@ -0,0 +1,36 @@
+use crate::migrations;
+use crate::model::change::ChangeAuthor;
+use sqlx::pool::PoolConnection;
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::{Row, Sqlite, query};
+
+/// Connection to a new in-memory database, one connection per pool keeps the database alive.
+pub async fn memory_db() -> PoolConnection<Sqlite> {
+    SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap()
+        .acquire()
+        .await
+        .unwrap()
+}
+
+/// In-memory database migrated to the latest version.
+pub async fn migrated_db() -> PoolConnection<Sqlite> {
+    let mut db = memory_db().await;
+    migrations::migrate(&mut db).await.unwrap();
+    db
+}
+
+/// Author of changes made by a newly created user, changes are made in session `s1`.
+pub async fn editor(db: &mut PoolConnection<Sqlite>) -> ChangeAuthor {
+    let user_id = query(
+        "INSERT INTO users (username, password_hash) VALUES ('editor', '') RETURNING user_id;",
+    )
+    .fetch_one(&mut **db)
+    .await
+    .unwrap()
+    .get(0);
+    ChangeAuthor::new(user_id, Some("s1".to_string()))
+}
@@ -13,8 +13,25 @@ pub fn approx_distance(first_lat: f64, first_lon: f64, second_lat: f64, second_l
             .asin()
 }
 
-pub fn approx_len(line: &Vec<(f64, f64)>) -> f64 {
+pub fn approx_len(line: &[(f64, f64)]) -> f64 {
     line.windows(2)
         .map(|w| approx_distance(w[0].0, w[0].1, w[1].0, w[1].1))
         .sum()
 }
+
+/// Whether the polygon of rings of `(lat, lon)` contains the point, holes are left out by the
+/// even-odd rule.
+pub fn polygon_contains(polygon: &[Vec<(f64, f64)>], lat: f64, lon: f64) -> bool {
+    let mut inside = false;
+    for ring in polygon {
+        for (index, &(lat_a, lon_a)) in ring.iter().enumerate() {
+            let (lat_b, lon_b) = ring[(index + 1) % ring.len()];
+            if (lat_a > lat) != (lat_b > lat)
+                && lon < (lon_b - lon_a) * (lat - lat_a) / (lat_b - lat_a) + lon_a
+            {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
@@ -1,8 +1,11 @@
 use crate::App;
+use geojson::feature::Id;
 use geojson::{Feature, FeatureCollection, GeoJson, Geometry, JsonObject, JsonValue, Value};
+use inv_zastavky_core::names::StopName;
 use sqlx::sqlite::SqliteRow;
-use sqlx::{query, Pool, Row, Sqlite};
+use sqlx::{Pool, Row, Sqlite, query};
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use std::fs::File;
 use std::io::Write;
 use std::path::PathBuf;
@@ -10,6 +13,7 @@ use std::path::PathBuf;
 type StopId = i32;
 
 struct Station {
+    stop_id: StopId,
     names: Vec<String>,
     lat: f64,
     lon: f64,
@@ -25,23 +29,24 @@ impl App {
         let mut stations_locs: HashMap<StopId, (f64, f64)> = HashMap::new();
         for row in rows {
             let stop_id: StopId = row.try_get(0)?;
-            if !stations_names.contains_key(&stop_id) {
-                stations_locs.insert(stop_id, (row.try_get(1)?, row.try_get(2)?));
-                stations_names.insert(stop_id, Vec::new());
+            if let Entry::Vacant(entry) = stations_locs.entry(stop_id) {
+                entry.insert((row.try_get(1)?, row.try_get(2)?));
             }
             stations_names
-                .get_mut(&stop_id)
-                .unwrap()
+                .entry(stop_id)
+                .or_default()
                 .push(row.try_get(3)?);
         }
         let mut stations = Vec::new();
         for (stop_id, names) in stations_names {
             stations.push(Station {
+                stop_id,
                 names,
                 lat: stations_locs[&stop_id].0,
                 lon: stations_locs[&stop_id].1,
             });
         }
+        stations.sort_by_key(|station| station.stop_id);
 
         let collection = GeoJson::FeatureCollection(FeatureCollection {
             bbox: None,
@@ -50,14 +55,35 @@ impl App {
                 .map(|station| {
                     let geometry = Geometry::new(Value::Point(vec![station.lon, station.lat]));
                     let mut properties = JsonObject::new();
+                    properties.insert(String::from("stop_id"), JsonValue::from(station.stop_id));
                     properties.insert(String::from("name"), JsonValue::from(station.names.clone()));
-                    return Feature {
+                    let stop_names = station
+                        .names
+                        .iter()
+                        .map(|name| {
+                            let stop_name = StopName::parse(name);
+                            let mut parts = JsonObject::new();
+                            parts.insert(
+                                String::from("municipality"),
+                                JsonValue::from(stop_name.municipality()),
+                            );
+                            parts.insert(String::from("part"), JsonValue::from(stop_name.part()));
+                            parts.insert(String::from("local"), JsonValue::from(stop_name.local()));
+                            parts.insert(
+                                String::from("district"),
+                                JsonValue::from(stop_name.district()),
+                            );
+                            JsonValue::Object(parts)
+                        })
+                        .collect::<Vec<_>>();
+                    properties.insert(String::from("stop_names"), JsonValue::Array(stop_names));
+                    Feature {
                         bbox: None,
                         geometry: Some(geometry),
-                        id: None,
+                        id: Some(Id::Number(station.stop_id.into())),
                         properties: Some(properties),
                         foreign_members: None,
-                    };
+                    }
                 })
                 .collect::<Vec<_>>(),
             foreign_members: None,
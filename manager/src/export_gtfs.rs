@@ -0,0 +1,456 @@
+use crate::import::chain_hash;
+use crate::netex::parse::{ParsedChain, ParsedNetex, ParsedPassingTime, ParsedServiceJourney};
+use crate::{netex, App};
+use anyhow::anyhow;
+use csv::Writer;
+use inv_zastavky_core::model::StopId;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{query, Pool, Row, Sqlite};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+const AGENCY_URL: &str = "https://portal.cisjr.cz";
+const AGENCY_TIMEZONE: &str = "Europe/Prague";
+const UNKNOWN_AGENCY: &str = "unknown";
+
+struct GtfsWriters {
+    agency: Writer<File>,
+    routes: Writer<File>,
+    trips: Writer<File>,
+    stop_times: Writer<File>,
+    agencies: HashSet<String>,
+    routes_done: HashSet<String>,
+    /// Days since 1970-01-01 on which each service runs.
+    services: BTreeMap<String, BTreeSet<i64>>,
+    used_stops: BTreeSet<StopId>,
+}
+
+#[derive(Default)]
+struct ExportReport {
+    exported: usize,
+    unlocated: usize,
+    incomplete: usize,
+}
+
+impl GtfsWriters {
+    fn create(output_dir: &Path) -> anyhow::Result<GtfsWriters> {
+        let mut agency = Writer::from_path(output_dir.join("agency.txt"))?;
+        agency.write_record(["agency_id", "agency_name", "agency_url", "agency_timezone"])?;
+        let mut routes = Writer::from_path(output_dir.join("routes.txt"))?;
+        routes.write_record([
+            "route_id",
+            "agency_id",
+            "route_short_name",
+            "route_long_name",
+            "route_type",
+        ])?;
+        let mut trips = Writer::from_path(output_dir.join("trips.txt"))?;
+        trips.write_record(["route_id", "service_id", "trip_id"])?;
+        let mut stop_times = Writer::from_path(output_dir.join("stop_times.txt"))?;
+        stop_times.write_record([
+            "trip_id",
+            "arrival_time",
+            "departure_time",
+            "stop_id",
+            "stop_sequence",
+        ])?;
+        Ok(GtfsWriters {
+            agency,
+            routes,
+            trips,
+            stop_times,
+            agencies: HashSet::new(),
+            routes_done: HashSet::new(),
+            services: BTreeMap::new(),
+            used_stops: BTreeSet::new(),
+        })
+    }
+}
+
+impl App {
+    /// Writes GTFS feed of journeys from NeTEx whose stops are all located.
+    pub async fn export_gtfs(
+        db_pool: &Pool<Sqlite>,
+        netex_path: PathBuf,
+        output_dir: PathBuf,
+    ) -> anyhow::Result<()> {
+        let rows: Vec<SqliteRow> = query("SELECT chain_hash, pos, stop_id FROM hl_stations")
+            .fetch_all(db_pool)
+            .await?;
+        let mut located: HashMap<(String, i32), StopId> = HashMap::new();
+        for row in rows {
+            located.insert((row.try_get(0)?, row.try_get(1)?), row.try_get(2)?);
+        }
+
+        let rows: Vec<SqliteRow> = query(
+            "SELECT stop_id, min(station_name), lat, lon FROM el_station_pos
+    JOIN el_station_names USING (stop_id) GROUP BY stop_id",
+        )
+        .fetch_all(db_pool)
+        .await?;
+        let mut stations: HashMap<StopId, (String, f64, f64)> = HashMap::new();
+        for row in rows {
+            stations.insert(
+                row.try_get(0)?,
+                (row.try_get(1)?, row.try_get(2)?, row.try_get(3)?),
+            );
+        }
+
+        std::fs::create_dir_all(&output_dir)?;
+        let mut writers = GtfsWriters::create(&output_dir)?;
+        let mut report = ExportReport::default();
+
+        let netex_file = File::open(netex_path)?;
+        let mut archive = zip::ZipArchive::new(netex_file)?;
+        for i in 0..archive.len() {
+            let file = archive.by_index(i)?;
+            let reader = quick_xml::Reader::from_reader(BufReader::new(file));
+            let parsed = netex::parse::parse_netex(reader)?;
+            // journeys refer to patterns of the same file
+            let chains: HashMap<&str, &ParsedChain> = parsed
+                .chains
+                .iter()
+                .map(|chain| (chain.pattern.as_str(), chain))
+                .collect();
+            for journey in &parsed.journeys {
+                let result = Self::export_gtfs_journey(
+                    &parsed,
+                    &chains,
+                    journey,
+                    &located,
+                    &stations,
+                    &mut writers,
+                );
+                match result {
+                    Ok(true) => report.exported += 1,
+                    Ok(false) => report.unlocated += 1,
+                    Err(err) => {
+                        println!("skipped trip {}: {}", journey.id, err);
+                        report.incomplete += 1;
+                    }
+                }
+            }
+            println!("{}/{}", i, archive.len());
+        }
+
+        let mut stops = Writer::from_path(output_dir.join("stops.txt"))?;
+        stops.write_record(["stop_id", "stop_name", "stop_lat", "stop_lon"])?;
+        for stop_id in &writers.used_stops {
+            let (name, lat, lon) = &stations[stop_id];
+            stops.write_record([
+                stop_id.to_string(),
+                name.clone(),
+                lat.to_string(),
+                lon.to_string(),
+            ])?;
+        }
+
+        let mut calendar = Writer::from_path(output_dir.join("calendar.txt"))?;
+        calendar.write_record([
+            "service_id",
+            "monday",
+            "tuesday",
+            "wednesday",
+            "thursday",
+            "friday",
+            "saturday",
+            "sunday",
+            "start_date",
+            "end_date",
+        ])?;
+        let mut calendar_dates = Writer::from_path(output_dir.join("calendar_dates.txt"))?;
+        calendar_dates.write_record(["service_id", "date", "exception_type"])?;
+        for (service_id, days) in &writers.services {
+            let (Some(first), Some(last)) = (days.first(), days.last()) else {
+                continue;
+            };
+            let mut record = vec![service_id.clone()];
+            record.extend((0..7).map(|_| String::from("0")));
+            record.push(format_date(*first));
+            record.push(format_date(*last));
+            calendar.write_record(&record)?;
+            for record in calendar_dates_records(service_id, days) {
+                calendar_dates.write_record(record)?;
+            }
+        }
+
+        writers.agency.flush()?;
+        writers.routes.flush()?;
+        writers.trips.flush()?;
+        writers.stop_times.flush()?;
+        stops.flush()?;
+        calendar.flush()?;
+        calendar_dates.flush()?;
+
+        println!("exported trips: {}", report.exported);
+        println!("skipped trips with unlocated stops: {}", report.unlocated);
+        println!("skipped incomplete trips: {}", report.incomplete);
+        Ok(())
+    }
+
+    /// Writes one journey, returns `false` if some of its stops are not located yet.
+    fn export_gtfs_journey(
+        netex: &ParsedNetex,
+        chains: &HashMap<&str, &ParsedChain>,
+        journey: &ParsedServiceJourney,
+        located: &HashMap<(String, i32), StopId>,
+        stations: &HashMap<StopId, (String, f64, f64)>,
+        writers: &mut GtfsWriters,
+    ) -> anyhow::Result<bool> {
+        let pattern = journey.pattern.as_ref().ok_or(anyhow!("missing pattern"))?;
+        let chain = chains
+            .get(pattern.as_str())
+            .ok_or(anyhow!("unknown pattern {}", pattern))?;
+        let points = &chain.points;
+        let hash = chain_hash(&chain.stations);
+        let stop_ids: Vec<Option<StopId>> = (0..chain.stations.len())
+            .map(|pos| {
+                located
+                    .get(&(hash.clone(), pos as i32))
+                    .filter(|stop_id| stations.contains_key(stop_id))
+                    .copied()
+            })
+            .collect();
+        if stop_ids.iter().any(Option::is_none) {
+            return Ok(false);
+        }
+
+        let line_id = journey.line.as_ref().ok_or(anyhow!("missing line"))?;
+        let line = netex
+            .lines
+            .get(line_id)
+            .ok_or(anyhow!("unknown line {}", line_id))?;
+
+        let mut days = BTreeSet::new();
+        for day_type in &journey.day_types {
+            for period_id in netex.day_types.get(day_type).into_iter().flatten() {
+                let period = netex
+                    .operating_periods
+                    .get(period_id)
+                    .ok_or(anyhow!("unknown operating period {}", period_id))?;
+                days.extend(operating_days(
+                    period.from_date.as_deref().unwrap_or_default(),
+                    period.valid_day_bits.as_deref().unwrap_or_default(),
+                )?);
+            }
+        }
+        if days.is_empty() {
+            return Err(anyhow!("no operating days"));
+        }
+
+        let mut times: Vec<(usize, &ParsedPassingTime)> = Vec::new();
+        for passing_time in &journey.passing_times {
+            let stop_point = passing_time
+                .stop_point
+                .as_ref()
+                .ok_or(anyhow!("passing time without stop point"))?;
+            let pos = points
+                .iter()
+                .position(|point| point == stop_point)
+                .ok_or(anyhow!("unknown stop point {}", stop_point))?;
+            times.push((pos, passing_time));
+        }
+        times.sort_by_key(|(pos, _)| *pos);
+        let mut stop_times = Vec::new();
+        for (pos, passing_time) in times {
+            let arrival = passing_time
+                .arrival
+                .as_ref()
+                .map(|time| format_time(time, passing_time.arrival_day_offset))
+                .transpose()?;
+            let departure = passing_time
+                .departure
+                .as_ref()
+                .map(|time| format_time(time, passing_time.departure_day_offset))
+                .transpose()?;
+            let (arrival, departure) = match (arrival, departure) {
+                (Some(arrival), Some(departure)) => (arrival, departure),
+                (Some(time), None) | (None, Some(time)) => (time.clone(), time),
+                (None, None) => return Err(anyhow!("passing time without time")),
+            };
+            stop_times.push([
+                journey.id.clone(),
+                arrival,
+                departure,
+                stop_ids[pos].unwrap().to_string(),
+                pos.to_string(),
+            ]);
+        }
+
+        let agency_id = line.operator.as_deref().unwrap_or(UNKNOWN_AGENCY);
+        if writers.agencies.insert(agency_id.to_string()) {
+            let agency_name = netex
+                .operators
+                .get(agency_id)
+                .cloned()
+                .flatten()
+                .unwrap_or(agency_id.to_string());
+            writers
+                .agency
+                .write_record([agency_id, &agency_name, AGENCY_URL, AGENCY_TIMEZONE])?;
+        }
+        if writers.routes_done.insert(line_id.clone()) {
+            writers.routes.write_record([
+                line_id.as_str(),
+                agency_id,
+                line.public_code
+                    .as_deref()
+                    .or(line.name.as_deref())
+                    .unwrap_or_default(),
+                line.name.as_deref().unwrap_or_default(),
+                route_type(line.transport_mode.as_deref()),
+            ])?;
+        }
+        let service_id = journey.day_types.join("+");
+        writers.trips.write_record([line_id, &service_id, &journey.id])?;
+        for stop_time in stop_times {
+            writers.stop_times.write_record(stop_time)?;
+        }
+        writers.services.entry(service_id).or_default().extend(days);
+        writers.used_stops.extend(stop_ids.into_iter().flatten());
+        Ok(true)
+    }
+}
+
+fn route_type(transport_mode: Option<&str>) -> &'static str {
+    match transport_mode {
+        Some("tram") => "0",
+        Some("metro") => "1",
+        Some("rail") => "2",
+        Some("water") => "4",
+        Some("cableway") => "6",
+        Some("funicular") => "7",
+        Some("trolleyBus") => "11",
+        _ => "3",
+    }
+}
+
+/// Formats NeTEx time `HH:MM:SS` shifted by whole days as GTFS time.
+fn format_time(time: &str, day_offset: u32) -> anyhow::Result<String> {
+    let mut parts = time.splitn(3, ':');
+    let hours: u32 = parts.next().unwrap_or_default().parse()?;
+    let minutes: u32 = parts.next().unwrap_or("0").parse()?;
+    let seconds: u32 = parts.next().unwrap_or("0").parse()?;
+    Ok(format!(
+        "{:02}:{:02}:{:02}",
+        hours + 24 * day_offset,
+        minutes,
+        seconds
+    ))
+}
+
+/// Lists days since 1970-01-01 marked in `valid_day_bits` counted from `from_date`.
+fn operating_days(from_date: &str, valid_day_bits: &str) -> anyhow::Result<Vec<i64>> {
+    let date = from_date.get(..10).ok_or(anyhow!("invalid date {}", from_date))?;
+    let mut parts = date.split('-');
+    let year: i64 = parts.next().unwrap_or_default().parse()?;
+    let month: i64 = parts.next().unwrap_or_default().parse()?;
+    let day: i64 = parts.next().unwrap_or_default().parse()?;
+    let first = days_from_civil(year, month, day);
+    Ok(valid_day_bits
+        .chars()
+        .enumerate()
+        .filter(|(_, bit)| *bit == '1')
+        .map(|(idx, _)| first + idx as i64)
+        .collect())
+}
+
+/// Rows of `calendar_dates.txt` adding each day to the service.
+fn calendar_dates_records(service_id: &str, days: &BTreeSet<i64>) -> Vec<[String; 3]> {
+    days.iter()
+        .map(|day| [service_id.to_string(), format_date(*day), String::from("1")])
+        .collect()
+}
+
+fn format_date(days: i64) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{:04}{:02}{:02}", year, month, day)
+}
+
+// Conversion between dates and days since 1970-01-01, see
+// https://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_round_trip_across_leap_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        for (year, month, day) in [
+            (2024, 2, 28),
+            (2024, 2, 29),
+            (2024, 3, 1),
+            (2023, 2, 28),
+            (2023, 3, 1),
+            (2000, 2, 29),
+            (1900, 3, 1),
+        ] {
+            let days = days_from_civil(year, month, day);
+            assert_eq!(civil_from_days(days), (year, month, day));
+        }
+        let leap_day = days_from_civil(2024, 2, 29);
+        assert_eq!(civil_from_days(leap_day - 1), (2024, 2, 28));
+        assert_eq!(civil_from_days(leap_day + 1), (2024, 3, 1));
+        // 1900 is not a leap year
+        assert_eq!(
+            civil_from_days(days_from_civil(1900, 2, 28) + 1),
+            (1900, 3, 1)
+        );
+        assert_eq!(format_date(leap_day), "20240229");
+    }
+
+    #[test]
+    fn times_after_midnight_continue_the_service_day() {
+        assert_eq!(format_time("07:30:15", 0).unwrap(), "07:30:15");
+        assert_eq!(format_time("00:00:00", 1).unwrap(), "24:00:00");
+        assert_eq!(format_time("23:59:00", 1).unwrap(), "47:59:00");
+        assert_eq!(format_time("01:05:00", 2).unwrap(), "49:05:00");
+        assert_eq!(format_time("5:7", 0).unwrap(), "05:07:00");
+        assert!(format_time("noon", 0).is_err());
+    }
+
+    #[test]
+    fn valid_day_bits_expand_to_calendar_dates() {
+        let days = operating_days("2024-02-27T00:00:00", "1101001").unwrap();
+        let days = days.into_iter().collect::<BTreeSet<_>>();
+        let records = calendar_dates_records("S1", &days);
+
+        assert_eq!(
+            records,
+            [
+                ["S1", "20240227", "1"],
+                ["S1", "20240228", "1"],
+                ["S1", "20240301", "1"],
+                ["S1", "20240304", "1"],
+            ]
+            .map(|record| record.map(String::from))
+        );
+        assert!(operating_days("2024-02-27", "000").unwrap().is_empty());
+        assert!(operating_days("2024", "1").is_err());
+    }
+}
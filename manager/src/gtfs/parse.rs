@@ -0,0 +1,247 @@
+use anyhow::anyhow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Read, Seek};
+use std::str::FromStr;
+use zip::ZipArchive;
+
+#[derive(Debug)]
+pub struct ParsedStop {
+    pub id: String,
+    pub parent_id: Option<String>,
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedGtfs {
+    pub chains: Vec<Vec<String>>,
+    pub stops: Vec<ParsedStop>,
+}
+
+/// Parses a GTFS feed into unique sequences of station names and located stops.
+///
+/// Stops belonging to a station are named after their `parent_station`.
+pub fn parse_gtfs<R: Read + Seek>(archive: &mut ZipArchive<R>) -> anyhow::Result<ParsedGtfs> {
+    let mut all_stops = HashMap::new();
+    read_records(
+        archive,
+        "stops.txt",
+        &[
+            "stop_id",
+            "stop_name",
+            "stop_lat",
+            "stop_lon",
+            "location_type",
+            "parent_station",
+        ],
+        |record| {
+            let location_type = if record[4].is_empty() {
+                0
+            } else {
+                u8::from_str(record[4])?
+            };
+            let parent_id = Some(record[5].to_string()).filter(|parent| !parent.is_empty());
+            let location = f64::from_str(record[2])
+                .and_then(|lat| Ok((lat, f64::from_str(record[3])?)))
+                .ok();
+            all_stops.insert(
+                record[0].to_string(),
+                (record[1].to_string(), location, location_type, parent_id),
+            );
+            Ok(())
+        },
+    )?;
+
+    let station_name = |stop_id: &str| -> Option<String> {
+        let (name, _, _, parent_id) = all_stops.get(stop_id)?;
+        match parent_id.as_ref().and_then(|parent_id| all_stops.get(parent_id)) {
+            Some((parent_name, _, _, _)) => Some(parent_name.clone()),
+            None => Some(name.clone()),
+        }
+    };
+
+    let mut stops = Vec::new();
+    for (id, (_, location, location_type, parent_id)) in &all_stops {
+        // only stops, platforms and stations, not entrances or nodes
+        if *location_type > 1 {
+            continue;
+        }
+        if let Some((lat, lon)) = location {
+            stops.push(ParsedStop {
+                id: id.clone(),
+                parent_id: parent_id.clone(),
+                name: station_name(id).unwrap(),
+                lat: *lat,
+                lon: *lon,
+            });
+        }
+    }
+
+    let mut trip_ids = HashSet::new();
+    read_records(archive, "trips.txt", &["trip_id"], |record| {
+        trip_ids.insert(record[0].to_string());
+        Ok(())
+    })?;
+
+    // trip -> stop sequence -> station name
+    let mut trips: HashMap<String, BTreeMap<u32, String>> = HashMap::new();
+    read_records(
+        archive,
+        "stop_times.txt",
+        &["trip_id", "stop_sequence", "stop_id"],
+        |record| {
+            if !trip_ids.contains(record[0]) {
+                return Ok(());
+            }
+            let name = station_name(record[2])
+                .ok_or(anyhow!("unknown stop {} in stop_times.txt", record[2]))?;
+            trips
+                .entry(record[0].to_string())
+                .or_default()
+                .insert(u32::from_str(record[1])?, name);
+            Ok(())
+        },
+    )?;
+
+    let chains: HashSet<Vec<String>> = trips
+        .into_values()
+        .map(|stops| stops.into_values().collect())
+        .collect();
+    Ok(ParsedGtfs {
+        chains: chains.into_iter().collect(),
+        stops,
+    })
+}
+
+/// Calls `handle` with values of `columns` for every record of a file in the feed.
+///
+/// Missing optional columns are passed as empty strings.
+fn read_records<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    file_name: &str,
+    columns: &[&str],
+    mut handle: impl FnMut(&[&str]) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let file = archive
+        .by_name(file_name)
+        .map_err(|err| anyhow!("missing {} in GTFS feed: {}", file_name, err))?;
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(file);
+    let headers = reader.headers()?.clone();
+    let indices: Vec<Option<usize>> = columns
+        .iter()
+        .map(|column| {
+            headers
+                .iter()
+                .position(|header| header.trim_start_matches('\u{feff}').trim() == *column)
+        })
+        .collect();
+
+    for record in reader.records() {
+        let record = record?;
+        let values: Vec<&str> = indices
+            .iter()
+            .map(|idx| idx.and_then(|idx| record.get(idx)).unwrap_or("").trim())
+            .collect();
+        handle(&values)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+
+    fn feed(files: &[(&str, &str)]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    const STOPS: &str = "\u{feff}stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station
+U1,Praha hl.n.,50.0831,14.4353,1,
+U1Z1,Praha hl.n. - nást. 1,50.0832,14.4354,0,U1
+U1E1,Praha hl.n. - vchod,50.0830,14.4350,2,U1
+U2,Kolín,,,,
+U3,Pardubice hl.n.,50.0322,15.7563,,
+";
+
+    #[test]
+    fn stops_are_named_after_their_parent_station() {
+        let mut archive = feed(&[
+            ("stops.txt", STOPS),
+            (
+                "trips.txt",
+                "route_id,service_id,trip_id\nR1,S1,T1\nR1,S1,T2\n",
+            ),
+            (
+                "stop_times.txt",
+                "trip_id,arrival_time,departure_time,stop_id,stop_sequence
+T1,08:00:00,08:00:00,U1Z1,1
+T1,08:40:00,08:41:00,U2,2
+T1,09:00:00,09:00:00,U3,3
+T2,10:40:00,10:41:00,U2,20
+T2,10:00:00,10:00:00,U1,10
+T3,11:00:00,11:00:00,unknown,1
+",
+            ),
+        ]);
+
+        let parsed = parse_gtfs(&mut archive).unwrap();
+
+        let mut chains = parsed.chains;
+        chains.sort();
+        assert_eq!(
+            chains,
+            [
+                vec!["Praha hl.n.", "Kolín"],
+                vec!["Praha hl.n.", "Kolín", "Pardubice hl.n."],
+            ]
+        );
+
+        // entrances and stops without coordinates are not located
+        let mut stops = parsed.stops;
+        stops.sort_by(|a, b| a.id.cmp(&b.id));
+        let stops: Vec<_> = stops
+            .iter()
+            .map(|stop| {
+                (
+                    stop.id.as_str(),
+                    stop.parent_id.as_deref(),
+                    stop.name.as_str(),
+                    stop.lat,
+                    stop.lon,
+                )
+            })
+            .collect();
+        assert_eq!(
+            stops,
+            [
+                ("U1", None, "Praha hl.n.", 50.0831, 14.4353),
+                ("U1Z1", Some("U1"), "Praha hl.n.", 50.0832, 14.4354),
+                ("U3", None, "Pardubice hl.n.", 50.0322, 15.7563),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_stops_of_trips_are_rejected() {
+        let mut archive = feed(&[
+            ("stops.txt", STOPS),
+            ("trips.txt", "trip_id\nT1\n"),
+            ("stop_times.txt", "trip_id,stop_sequence,stop_id\nT1,1,U9\n"),
+        ]);
+        let err = parse_gtfs(&mut archive).unwrap_err();
+        assert_eq!(err.to_string(), "unknown stop U9 in stop_times.txt");
+
+        let mut archive = feed(&[("stops.txt", STOPS)]);
+        assert!(parse_gtfs(&mut archive).is_err());
+    }
+}
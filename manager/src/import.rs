@@ -1,40 +1,79 @@
-use crate::{netex, App};
+use crate::gtfs::parse::ParsedStop;
+use crate::netex::parse::{ParsedNetex, ParsedStopPlace};
+use crate::ruian::parse::ParsedRuian;
+use crate::{gtfs, jdf, netex, ruian, App};
+use anyhow::anyhow;
 use base64::Engine;
-use geojson::{FeatureCollection, Value};
+use geojson::{Feature, FeatureCollection, Geometry, JsonValue, Value};
 use md5::{Digest, Md5};
-use sqlx::{Pool, QueryBuilder, Sqlite};
-use std::collections::HashMap;
+use sqlx::{Pool, QueryBuilder, Row, Sqlite, SqliteConnection};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Cursor, Read};
 use std::path::PathBuf;
 
 impl App {
-    pub async fn import(db_pool: &Pool<Sqlite>, netex_path: Option<PathBuf>) -> anyhow::Result<()> {
-        let netex_path = netex_path.unwrap();
-        let netex_file = File::open(netex_path)?;
-        let mut archive = zip::ZipArchive::new(netex_file)?;
-
-        let mut station_chains = Vec::new();
-        for i in 0..archive.len() {
-            let file = archive.by_index(i)?;
-            let buf_reader = BufReader::new(file);
-            let reader = quick_xml::Reader::from_reader(buf_reader);
-            station_chains.extend(netex::parse::parse_netex(reader)?);
-            println!("{}/{}", i, archive.len());
+    pub async fn import(
+        db_pool: &Pool<Sqlite>,
+        netex_path: Option<PathBuf>,
+        jdf_path: Option<PathBuf>,
+        gtfs_path: Option<PathBuf>,
+    ) -> anyhow::Result<()> {
+        let mut sources = Vec::new();
+        if let Some(netex_path) = netex_path {
+            let netex = Self::read_netex(netex_path)?;
+            let chains = netex.chains.into_iter().map(|chain| chain.stations);
+            sources.push(("netex", chains.collect()));
+            Self::import_netex_stations(db_pool, netex.stop_places).await?;
         }
-        println!("{}", station_chains.len());
+        if let Some(jdf_path) = jdf_path {
+            sources.push(("jdf", Self::read_jdf(jdf_path)?));
+        }
+        if let Some(gtfs_path) = gtfs_path {
+            let gtfs_file = File::open(gtfs_path)?;
+            let gtfs = gtfs::parse::parse_gtfs(&mut zip::ZipArchive::new(gtfs_file)?)?;
+            sources.push(("gtfs", gtfs.chains));
+            Self::import_gtfs_stations(db_pool, gtfs.stops).await?;
+        }
+        Self::import_chains(db_pool, sources).await
+    }
 
+    /// Replaces chains of the given sources, chains of other sources are kept.
+    async fn import_chains(
+        db_pool: &Pool<Sqlite>,
+        sources: Vec<(&str, Vec<Vec<String>>)>,
+    ) -> anyhow::Result<()> {
+        let mut tx = db_pool.begin().await?;
         let mut stations = HashMap::new();
-        for chain in station_chains {
-            let name = chain.join("|");
-            let hash = Md5::digest(name);
-            stations.insert(base64::prelude::BASE64_STANDARD.encode(hash), chain);
+        for (source, station_chains) in sources {
+            println!("{}: {}", source, station_chains.len());
+            sqlx::query("DELETE FROM sl_chain_sources WHERE source = $1 OR source = 'unknown';")
+                .bind(source)
+                .execute(&mut *tx)
+                .await?;
+            let mut hashes = HashSet::new();
+            for chain in station_chains {
+                let hash = chain_hash(&chain);
+                hashes.insert(hash.clone());
+                stations.insert(hash, chain);
+            }
+            let hashes: Vec<_> = hashes.into_iter().collect();
+            for hashes in hashes.chunks(1000) {
+                let mut builder =
+                    QueryBuilder::new("INSERT INTO sl_chain_sources(chain_hash, source)");
+                builder.push_values(hashes, |mut b, hash| {
+                    b.push_bind(hash).push_bind(source);
+                });
+                builder.build().execute(&mut *tx).await?;
+            }
         }
         println!("{}", stations.len());
 
-        sqlx::query("DELETE FROM sl_chains;")
-            .execute(db_pool)
-            .await?;
+        sqlx::query(
+            "DELETE FROM sl_chains WHERE chain_hash NOT IN (SELECT chain_hash FROM sl_chain_sources);",
+        )
+        .execute(&mut *tx)
+        .await?;
         let stations: Vec<_> = stations
             .iter()
             .fold(Vec::new(), |mut acc, (hash, stations)| {
@@ -49,77 +88,771 @@ impl App {
 
         for stations in stations.chunks(1000) {
             let mut builder =
-                QueryBuilder::new("INSERT INTO sl_chains(chain_hash, station_name, pos)");
+                QueryBuilder::new("INSERT OR IGNORE INTO sl_chains(chain_hash, station_name, pos)");
             builder.push_values(stations, |mut b, station| {
                 b.push_bind(station.0)
                     .push_bind(station.1)
                     .push_bind(station.2 as i32);
             });
-            builder.build().execute(db_pool).await?;
+            builder.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    fn read_netex(netex_path: PathBuf) -> anyhow::Result<ParsedNetex> {
+        let netex_file = File::open(netex_path)?;
+        let mut archive = zip::ZipArchive::new(netex_file)?;
+
+        let mut netex = ParsedNetex::default();
+        for i in 0..archive.len() {
+            let file = archive.by_index(i)?;
+            let buf_reader = BufReader::new(file);
+            let reader = quick_xml::Reader::from_reader(buf_reader);
+            let parsed = netex::parse::parse_netex(reader)?;
+            netex.chains.extend(parsed.chains);
+            netex.stop_places.extend(parsed.stop_places);
+            println!("{}/{}", i, archive.len());
+        }
+        Ok(netex)
+    }
+
+    /// Reads a ZIP of one JDF batch or a ZIP of zipped JDF batches.
+    fn read_jdf(jdf_path: PathBuf) -> anyhow::Result<Vec<Vec<String>>> {
+        let jdf_file = File::open(jdf_path)?;
+        let mut archive = zip::ZipArchive::new(jdf_file)?;
+
+        let batches: Vec<usize> = (0..archive.len())
+            .filter(|idx| {
+                archive
+                    .name_for_index(*idx)
+                    .is_some_and(|name| name.to_lowercase().ends_with(".zip"))
+            })
+            .collect();
+        if batches.is_empty() {
+            return jdf::parse::parse_jdf(&mut archive);
+        }
+
+        let mut station_chains = Vec::new();
+        for (i, idx) in batches.iter().enumerate() {
+            let mut bytes = Vec::new();
+            archive.by_index(*idx)?.read_to_end(&mut bytes)?;
+            let mut batch = zip::ZipArchive::new(Cursor::new(bytes))?;
+            station_chains.extend(jdf::parse::parse_jdf(&mut batch)?);
+            println!("{}/{}", i, batches.len());
+        }
+        Ok(station_chains)
+    }
+
+    /// Stores located stop places and their quays as candidate station positions.
+    async fn import_netex_stations(
+        db_pool: &Pool<Sqlite>,
+        stop_places: HashMap<String, ParsedStopPlace>,
+    ) -> anyhow::Result<()> {
+        let mut bases: Vec<(String, Option<String>, String, f64, f64)> = Vec::new();
+        for (id, stop_place) in stop_places {
+            let Some(name) = stop_place.name else {
+                continue;
+            };
+            if let (Some(lat), Some(lon)) = (stop_place.lat, stop_place.lon) {
+                bases.push((id.clone(), None, name.clone(), lat, lon));
+            }
+            for (quay_id, location) in stop_place.quays {
+                if let (Some(lat), Some(lon)) = location {
+                    bases.push((quay_id, Some(id.clone()), name.clone(), lat, lon));
+                }
+            }
+        }
+        println!("{}", bases.len());
+
+        let mut tx = db_pool.begin().await?;
+        sqlx::query("DELETE FROM sl_netex_stations;")
+            .execute(&mut *tx)
+            .await?;
+        for stations in bases.chunks(1000) {
+            let mut builder = QueryBuilder::new(
+                "INSERT INTO sl_netex_stations(netex_id, parent_id, station_name, lat, lon)",
+            );
+            builder.push_values(stations, |mut b, station| {
+                b.push_bind(&station.0)
+                    .push_bind(&station.1)
+                    .push_bind(&station.2)
+                    .push_bind(station.3)
+                    .push_bind(station.4);
+            });
+            builder.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Stores located GTFS stops and stations as candidate station positions.
+    async fn import_gtfs_stations(
+        db_pool: &Pool<Sqlite>,
+        stops: Vec<ParsedStop>,
+    ) -> anyhow::Result<()> {
+        println!("{}", stops.len());
+        let mut tx = db_pool.begin().await?;
+        sqlx::query("DELETE FROM sl_gtfs_stations;")
+            .execute(&mut *tx)
+            .await?;
+        for stops in stops.chunks(1000) {
+            let mut builder = QueryBuilder::new(
+                "INSERT INTO sl_gtfs_stations(gtfs_id, parent_id, station_name, lat, lon)",
+            );
+            builder.push_values(stops, |mut b, stop| {
+                b.push_bind(&stop.id)
+                    .push_bind(&stop.parent_id)
+                    .push_bind(&stop.name)
+                    .push_bind(stop.lat)
+                    .push_bind(stop.lon);
+            });
+            builder.build().execute(&mut *tx).await?;
         }
+        tx.commit().await?;
         Ok(())
     }
 
+    /// Imports base stations named by the `name_key` property, identified by the `id_key` one.
     pub async fn import_base_stations(
         db_pool: &Pool<Sqlite>,
         base_stations: PathBuf,
+        name_key: &str,
+        id_key: Option<&str>,
     ) -> anyhow::Result<()> {
-        let gjson = geojson::GeoJson::from_reader(File::open(base_stations)?)?;
-        let collection = FeatureCollection::try_from(gjson)?;
-        let mut bases: Vec<(f64, f64, String)> = Vec::new();
-        for feature in &collection.features {
-            if let Value::Point(coords) = &feature.geometry.clone().unwrap().value {
-                bases.push((
-                    coords[1], // lat
-                    coords[0], // lon
-                    feature.property("name").unwrap().to_string(),
-                ));
-            }
+        let features = Self::read_features(base_stations)?;
+        let mut bases: Vec<(f64, f64, String, Option<String>)> = Vec::new();
+        let mut skipped = 0;
+        for (index, feature) in features.iter().enumerate() {
+            let base = BaseFeature::read(feature, name_key);
+            let base = match base {
+                Ok(base) => base,
+                Err(reason) => {
+                    println!("skipped feature {}: {}", index, reason);
+                    skipped += 1;
+                    continue;
+                }
+            };
+            let id = id_key.and_then(|key| property_text(feature, key));
+            bases.push((base.lat, base.lon, base.name, id));
         }
+        let mut tx = db_pool.begin().await?;
         sqlx::query("DELETE FROM sl_base_stations;")
-            .execute(db_pool)
+            .execute(&mut *tx)
             .await?;
         for stations in bases.chunks(1000) {
-            let mut builder =
-                QueryBuilder::new("INSERT INTO sl_base_stations(lat, lon, station_name)");
+            let mut builder = QueryBuilder::new(
+                "INSERT INTO sl_base_stations(lat, lon, station_name, source_id)",
+            );
             builder.push_values(stations, |mut b, station| {
                 b.push_bind(station.0)
                     .push_bind(station.1)
-                    .push_bind(&station.2);
+                    .push_bind(&station.2)
+                    .push_bind(&station.3);
             });
-            builder.build().execute(db_pool).await?;
+            builder.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        println!("imported base stations: {}", bases.len());
+        if skipped > 0 {
+            println!("skipped features: {}", skipped);
         }
         Ok(())
     }
 
+    /// Imports cities named by the `name_key` property with their district, given by the
+    /// `district_key` property as a name or a CIS JŘ code. Polygon features are also imported as
+    /// municipality boundaries.
     pub async fn import_base_cities(
         db_pool: &Pool<Sqlite>,
         base_cities: PathBuf,
+        name_key: &str,
+        district_key: &str,
     ) -> anyhow::Result<()> {
-        let gjson = geojson::GeoJson::from_reader(File::open(base_cities)?)?;
-        let collection = FeatureCollection::try_from(gjson)?;
-        let mut bases: Vec<(String, f64, f64)> = Vec::new();
-        for feature in &collection.features {
-            if let Value::Point(coords) = &feature.geometry.clone().unwrap().value {
-                bases.push((
-                    feature.property("Jméno").unwrap().to_string(),
-                    coords[1], // lat
-                    coords[0], // lon
+        let mut districts = HashMap::new();
+        for row in sqlx::query("SELECT district_code, district_name FROM sl_districts;")
+            .fetch_all(db_pool)
+            .await?
+        {
+            let code: String = row.try_get(0)?;
+            districts.insert(row.try_get::<String, _>(1)?, code.clone());
+            districts.insert(code.clone(), code);
+        }
+
+        let features = Self::read_features(base_cities)?;
+        let mut bases: Vec<(String, Option<String>, f64, f64)> = Vec::new();
+        let mut centroids = Vec::new();
+        let mut boundaries = Vec::new();
+        let mut unknown_districts = 0;
+        let mut skipped = 0;
+        for (index, feature) in features.iter().enumerate() {
+            let base = match BaseFeature::read(feature, name_key) {
+                Ok(base) => base,
+                Err(reason) => {
+                    println!("skipped feature {}: {}", index, reason);
+                    skipped += 1;
+                    continue;
+                }
+            };
+            let district = property_text(feature, district_key);
+            let district_code = district
+                .as_ref()
+                .and_then(|district| districts.get(district).cloned());
+            if district.is_some() && district_code.is_none() {
+                unknown_districts += 1;
+            }
+            if base.point {
+                bases.push((base.name, district_code, base.lat, base.lon));
+                continue;
+            }
+            if let Some(geometry) = &feature.geometry {
+                boundaries.extend(MunicipalityBoundary::new(
+                    base.name.clone(),
+                    district_code.clone(),
+                    None,
+                    geometry.value.clone(),
                 ));
             }
+            centroids.push((base.name, district_code, base.lat, base.lon));
         }
-        sqlx::query("DELETE FROM sl_base_cities;")
-            .execute(db_pool)
+        // boundaries may come along with points of the same cities
+        let points: HashSet<_> = bases
+            .iter()
+            .map(|(name, district, _, _)| (name.clone(), district.clone()))
+            .collect();
+        bases.extend(
+            centroids.into_iter().filter(|(name, district, _, _)| {
+                !points.contains(&(name.clone(), district.clone()))
+            }),
+        );
+
+        let mut tx = db_pool.begin().await?;
+        sqlx::query("DELETE FROM sl_base_cities; DELETE FROM sl_municipality_boundaries;")
+            .execute(&mut *tx)
             .await?;
         for stations in bases.chunks(1000) {
-            let mut builder = QueryBuilder::new("INSERT INTO sl_base_cities(city_name, lat, lon)");
+            let mut builder =
+                QueryBuilder::new("INSERT INTO sl_base_cities(city_name, district_code, lat, lon)");
             builder.push_values(stations, |mut b, station| {
-                b.push_bind(station.0.replace("\"", ""))
-                    .push_bind(station.1)
-                    .push_bind(station.2);
+                b.push_bind(&station.0)
+                    .push_bind(&station.1)
+                    .push_bind(station.2)
+                    .push_bind(station.3);
             });
-            builder.build().execute(db_pool).await?;
+            builder.build().execute(&mut *tx).await?;
+        }
+        MunicipalityBoundary::insert(&mut tx, &boundaries).await?;
+        tx.commit().await?;
+        println!("imported cities: {}", bases.len());
+        println!("imported boundaries: {}", boundaries.len());
+        if unknown_districts > 0 {
+            println!("cities in unknown districts: {}", unknown_districts);
+        }
+        if skipped > 0 {
+            println!("skipped features: {}", skipped);
         }
         Ok(())
     }
+
+    fn read_features(path: PathBuf) -> anyhow::Result<Vec<Feature>> {
+        let gjson = geojson::GeoJson::from_reader(BufReader::new(File::open(path)?))?;
+        Ok(FeatureCollection::try_from(gjson)?.features)
+    }
+
+    /// Replaces cities by municipalities and their parts from a RÚIAN exchange format file, plain
+    /// or zipped XML.
+    pub async fn import_ruian(db_pool: &Pool<Sqlite>, ruian_path: PathBuf) -> anyhow::Result<()> {
+        let ruian = Self::read_ruian(ruian_path)?;
+        let mut tx = db_pool.begin().await?;
+
+        for district in &ruian.districts {
+            // the capital is a district of its own named after the city
+            let name = match district.name.as_str() {
+                "Hlavní město Praha" => "Praha",
+                name => name,
+            };
+            sqlx::query("UPDATE sl_districts SET ruian_code = $1 WHERE district_name = $2;")
+                .bind(district.code)
+                .bind(name)
+                .execute(&mut *tx)
+                .await?;
+        }
+        let mut districts = HashMap::new();
+        for row in sqlx::query(
+            "SELECT ruian_code, district_code FROM sl_districts WHERE ruian_code IS NOT NULL;",
+        )
+        .fetch_all(&mut *tx)
+        .await?
+        {
+            districts.insert(row.try_get::<i64, _>(0)?, row.try_get::<String, _>(1)?);
+        }
+
+        let mut cities: Vec<RuianCity> = Vec::new();
+        let mut municipality_districts = HashMap::new();
+        let mut without_point = 0;
+        for municipality in &ruian.municipalities {
+            let district_code = municipality
+                .district
+                .and_then(|district| districts.get(&district).cloned());
+            municipality_districts.insert(municipality.code, district_code.clone());
+            let Some(point) = municipality.point else {
+                without_point += 1;
+                continue;
+            };
+            cities.push((
+                &municipality.name,
+                district_code,
+                municipality.code,
+                None,
+                point,
+            ));
+        }
+        let parts_start = cities.len();
+        for part in &ruian.parts {
+            let district_code = part
+                .municipality
+                .and_then(|municipality| municipality_districts.get(&municipality).cloned())
+                .flatten();
+            let Some(point) = part.point else {
+                without_point += 1;
+                continue;
+            };
+            cities.push((
+                &part.name,
+                district_code,
+                part.code,
+                part.municipality,
+                point,
+            ));
+        }
+        let boundaries: Vec<MunicipalityBoundary> = ruian
+            .municipalities
+            .iter()
+            .filter_map(|municipality| {
+                let polygons = municipality
+                    .polygons
+                    .iter()
+                    .map(|polygon| {
+                        polygon
+                            .iter()
+                            .map(|ring| ring.iter().map(|(lat, lon)| vec![*lon, *lat]).collect())
+                            .collect()
+                    })
+                    .collect();
+                MunicipalityBoundary::new(
+                    municipality.name.clone(),
+                    municipality_districts[&municipality.code].clone(),
+                    Some(municipality.code),
+                    Value::MultiPolygon(polygons),
+                )
+            })
+            .collect();
+
+        sqlx::query("DELETE FROM sl_base_cities; DELETE FROM sl_municipality_boundaries;")
+            .execute(&mut *tx)
+            .await?;
+        for chunk in cities.chunks(1000) {
+            let mut builder = QueryBuilder::new(
+                "INSERT INTO sl_base_cities(city_name, district_code, ruian_code, municipality_code, lat, lon)",
+            );
+            builder.push_values(chunk, |mut b, city| {
+                b.push_bind(city.0)
+                    .push_bind(&city.1)
+                    .push_bind(city.2)
+                    .push_bind(city.3)
+                    .push_bind(city.4.0)
+                    .push_bind(city.4.1);
+            });
+            builder.build().execute(&mut *tx).await?;
+        }
+        MunicipalityBoundary::insert(&mut tx, &boundaries).await?;
+        tx.commit().await?;
+
+        println!("imported municipalities: {}", parts_start);
+        println!(
+            "imported municipality parts: {}",
+            cities.len() - parts_start
+        );
+        println!("imported boundaries: {}", boundaries.len());
+        if without_point > 0 {
+            println!("skipped without definition point: {}", without_point);
+        }
+        Ok(())
+    }
+
+    fn read_ruian(ruian_path: PathBuf) -> anyhow::Result<ParsedRuian> {
+        if ruian_path
+            .extension()
+            .is_some_and(|extension| extension == "zip")
+        {
+            let mut archive = zip::ZipArchive::new(File::open(&ruian_path)?)?;
+            for i in 0..archive.len() {
+                let file = archive.by_index(i)?;
+                if file.name().ends_with(".xml") {
+                    let reader = quick_xml::Reader::from_reader(BufReader::new(file));
+                    return ruian::parse::parse_ruian(reader);
+                }
+            }
+            return Err(anyhow!("no XML file in {}", ruian_path.display()));
+        }
+        let reader = quick_xml::Reader::from_reader(BufReader::new(File::open(ruian_path)?));
+        ruian::parse::parse_ruian(reader)
+    }
+}
+
+/// Name and position of a GeoJSON feature of a base import.
+struct BaseFeature {
+    name: String,
+    lat: f64,
+    lon: f64,
+    /// Whether the position is the point geometry itself rather than a centroid.
+    point: bool,
+}
+
+impl BaseFeature {
+    /// Reads the feature, returning why it has to be skipped if it is malformed.
+    fn read(feature: &Feature, name_key: &str) -> Result<Self, String> {
+        let name = property_text(feature, name_key)
+            .ok_or_else(|| format!("no name in property `{}`", name_key))?;
+        let Some(geometry) = &feature.geometry else {
+            return Err(format!("{} has no geometry", name));
+        };
+        let (lat, lon) = centroid(&geometry.value)
+            .ok_or_else(|| format!("{} has invalid {}", name, geometry.value.type_name()))?;
+        Ok(Self {
+            name,
+            lat,
+            lon,
+            point: matches!(geometry.value, Value::Point(_)),
+        })
+    }
+}
+
+/// Text of a string or number property, `None` if it is missing, empty or of another type.
+fn property_text(feature: &Feature, key: &str) -> Option<String> {
+    match feature.property(key)? {
+        JsonValue::String(text) if !text.trim().is_empty() => Some(text.trim().to_string()),
+        JsonValue::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+/// `(lat, lon)` of a GeoJSON position, `None` if it is out of range.
+fn lat_lon(position: &[f64]) -> Option<(f64, f64)> {
+    let [lon, lat, ..] = position[..] else {
+        return None;
+    };
+    ((-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon)).then_some((lat, lon))
+}
+
+/// Average of the positions, `None` if there are none or any of them is invalid.
+fn mean<'a>(positions: impl IntoIterator<Item = &'a Vec<f64>>) -> Option<(f64, f64)> {
+    let mut count = 0;
+    let (mut lat_sum, mut lon_sum) = (0.0, 0.0);
+    for position in positions {
+        let (lat, lon) = lat_lon(position)?;
+        lat_sum += lat;
+        lon_sum += lon;
+        count += 1;
+    }
+    (count > 0).then(|| (lat_sum / count as f64, lon_sum / count as f64))
+}
+
+/// Position of a geometry, `(lat, lon)`: a point itself, the centroid of the area of polygons, the
+/// centroid of lines weighted by their length and the average of other geometries.
+fn centroid(value: &Value) -> Option<(f64, f64)> {
+    match value {
+        Value::Point(position) => lat_lon(position),
+        Value::MultiPoint(positions) => mean(positions),
+        Value::LineString(positions) => lines_centroid(std::slice::from_ref(positions)),
+        Value::MultiLineString(lines) => lines_centroid(lines),
+        Value::Polygon(polygon) => polygons_centroid(std::slice::from_ref(polygon)),
+        Value::MultiPolygon(polygons) => polygons_centroid(polygons),
+        Value::GeometryCollection(geometries) => {
+            let centroids = geometries
+                .iter()
+                .map(|geometry| centroid(&geometry.value).map(|(lat, lon)| vec![lon, lat]))
+                .collect::<Option<Vec<_>>>()?;
+            mean(&centroids)
+        }
+    }
+}
+
+/// Centroid of lines by the midpoints of their segments weighted by segment length, coordinates
+/// are taken as planar. Lines without length give the average of their vertices.
+fn lines_centroid(lines: &[Vec<Vec<f64>>]) -> Option<(f64, f64)> {
+    let (mut length, mut lat_moment, mut lon_moment) = (0.0, 0.0, 0.0);
+    for line in lines {
+        let points = line
+            .iter()
+            .map(|position| lat_lon(position))
+            .collect::<Option<Vec<_>>>()?;
+        for (a, b) in points.iter().zip(points.iter().skip(1)) {
+            let segment = f64::hypot(b.0 - a.0, b.1 - a.1);
+            length += segment;
+            lat_moment += (a.0 + b.0) / 2.0 * segment;
+            lon_moment += (a.1 + b.1) / 2.0 * segment;
+        }
+    }
+    if length < 1e-12 {
+        return mean(lines.iter().flatten());
+    }
+    Some((lat_moment / length, lon_moment / length))
+}
+
+/// Vertices of a ring without the closing one, which repeats the first vertex.
+fn open_ring(ring: &[Vec<f64>]) -> &[Vec<f64>] {
+    match ring {
+        [first, .., last] if first == last => &ring[..ring.len() - 1],
+        _ => ring,
+    }
+}
+
+/// Centroid of polygons with holes, coordinates are taken as planar, which is close enough for
+/// areas of municipalities. Degenerate polygons without area give the average of the vertices of
+/// their outer rings.
+fn polygons_centroid(polygons: &[Vec<Vec<Vec<f64>>>]) -> Option<(f64, f64)> {
+    let origin = lat_lon(polygons.first()?.first()?.first()?)?;
+    let (mut area, mut lat_moment, mut lon_moment) = (0.0, 0.0, 0.0);
+    for polygon in polygons {
+        for (i, ring) in polygon.iter().enumerate() {
+            let points = ring
+                .iter()
+                .map(|position| {
+                    lat_lon(position).map(|(lat, lon)| (lat - origin.0, lon - origin.1))
+                })
+                .collect::<Option<Vec<_>>>()?;
+            let (mut ring_area, mut ring_lat, mut ring_lon) = (0.0, 0.0, 0.0);
+            for (a, b) in points.iter().zip(points.iter().cycle().skip(1)) {
+                let cross = a.1 * b.0 - b.1 * a.0;
+                ring_area += cross / 2.0;
+                ring_lat += (a.0 + b.0) * cross / 6.0;
+                ring_lon += (a.1 + b.1) * cross / 6.0;
+            }
+            // the outer ring adds to the area, holes subtract from it whatever their orientation
+            let sign = ring_area.signum() * if i == 0 { 1.0 } else { -1.0 };
+            area += sign * ring_area;
+            lat_moment += sign * ring_lat;
+            lon_moment += sign * ring_lon;
+        }
+    }
+    if area.abs() < 1e-12 {
+        return mean(
+            polygons
+                .iter()
+                .filter_map(|polygon| polygon.first())
+                .flat_map(|ring| open_ring(ring)),
+        );
+    }
+    Some((origin.0 + lat_moment / area, origin.1 + lon_moment / area))
+}
+
+/// Boundary of a municipality with its bounds, as stored in `sl_municipality_boundaries`.
+struct MunicipalityBoundary {
+    name: String,
+    district_code: Option<String>,
+    ruian_code: Option<i64>,
+    lat: [f64; 2],
+    lon: [f64; 2],
+    geometry: String,
+}
+
+impl MunicipalityBoundary {
+    /// Returns `None` for geometries other than non-empty polygons.
+    fn new(
+        name: String,
+        district_code: Option<String>,
+        ruian_code: Option<i64>,
+        value: Value,
+    ) -> Option<Self> {
+        let polygons = match &value {
+            Value::Polygon(polygon) => std::slice::from_ref(polygon),
+            Value::MultiPolygon(polygons) => &polygons[..],
+            _ => return None,
+        };
+        let mut bounds: Option<([f64; 2], [f64; 2])> = None;
+        for position in polygons.iter().flatten().flatten() {
+            let [lon, lat, ..] = position[..] else {
+                continue;
+            };
+            let (lats, lons) = bounds.get_or_insert(([lat, lat], [lon, lon]));
+            *lats = [lats[0].min(lat), lats[1].max(lat)];
+            *lons = [lons[0].min(lon), lons[1].max(lon)];
+        }
+        let (lat, lon) = bounds?;
+        Some(Self {
+            name,
+            district_code,
+            ruian_code,
+            lat,
+            lon,
+            geometry: Geometry::new(value).to_string(),
+        })
+    }
+
+    async fn insert(
+        conn: &mut SqliteConnection,
+        boundaries: &[MunicipalityBoundary],
+    ) -> anyhow::Result<()> {
+        for chunk in boundaries.chunks(100) {
+            let mut builder = QueryBuilder::new(
+                "INSERT INTO sl_municipality_boundaries(municipality_name, district_code, ruian_code, lat_min, lat_max, lon_min, lon_max, geometry)",
+            );
+            builder.push_values(chunk, |mut b, boundary| {
+                b.push_bind(&boundary.name)
+                    .push_bind(&boundary.district_code)
+                    .push_bind(boundary.ruian_code)
+                    .push_bind(boundary.lat[0])
+                    .push_bind(boundary.lat[1])
+                    .push_bind(boundary.lon[0])
+                    .push_bind(boundary.lon[1])
+                    .push_bind(&boundary.geometry);
+            });
+            builder.build().execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Name, district code, RÚIAN code, municipality code and definition point of a city.
+type RuianCity<'a> = (&'a str, Option<String>, i64, Option<i64>, (f64, f64));
+
+/// Identifies chain of stations by its station names.
+pub fn chain_hash(chain: &[String]) -> String {
+    let name = chain.join("|");
+    let hash = Md5::digest(name);
+    base64::prelude::BASE64_STANDARD.encode(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feature(properties: &str) -> Feature {
+        format!(
+            r#"{{"type": "Feature", "geometry": null, "properties": {}}}"#,
+            properties
+        )
+        .parse()
+        .unwrap()
+    }
+
+    fn assert_close(actual: Option<(f64, f64)>, expected: (f64, f64)) {
+        let (lat, lon) = actual.unwrap();
+        assert!(
+            (lat - expected.0).abs() < 1e-9 && (lon - expected.1).abs() < 1e-9,
+            "{:?} is not {:?}",
+            (lat, lon),
+            expected
+        );
+    }
+
+    /// Closed ring of `(lon, lat)` corners.
+    fn ring(corners: &[(f64, f64)]) -> Vec<Vec<f64>> {
+        corners
+            .iter()
+            .chain(corners.first())
+            .map(|(lon, lat)| vec![*lon, *lat])
+            .collect()
+    }
+
+    #[test]
+    fn property_text_trims_strings_and_formats_numbers() {
+        let feature = feature(
+            r#"{"name": "  Kolín ", "code": 533165, "ratio": 1.5, "empty": " ", "flag": true, "none": null}"#,
+        );
+        assert_eq!(property_text(&feature, "name").as_deref(), Some("Kolín"));
+        assert_eq!(property_text(&feature, "code").as_deref(), Some("533165"));
+        assert_eq!(property_text(&feature, "ratio").as_deref(), Some("1.5"));
+        assert_eq!(property_text(&feature, "empty"), None);
+        assert_eq!(property_text(&feature, "flag"), None);
+        assert_eq!(property_text(&feature, "none"), None);
+        assert_eq!(property_text(&feature, "missing"), None);
+    }
+
+    #[test]
+    fn lat_lon_swaps_axes_and_checks_ranges() {
+        assert_eq!(lat_lon(&[14.4, 50.1]), Some((50.1, 14.4)));
+        assert_eq!(lat_lon(&[14.4, 50.1, 230.0]), Some((50.1, 14.4)));
+        assert_eq!(lat_lon(&[-180.0, -90.0]), Some((-90.0, -180.0)));
+        assert_eq!(lat_lon(&[50.1, 91.0]), None);
+        assert_eq!(lat_lon(&[180.5, 50.1]), None);
+        assert_eq!(lat_lon(&[14.4]), None);
+        assert_eq!(lat_lon(&[]), None);
+    }
+
+    #[test]
+    fn point_is_its_own_centroid() {
+        assert_close(centroid(&Value::Point(vec![14.4, 50.1])), (50.1, 14.4));
+        assert_eq!(centroid(&Value::Point(vec![14.4, 95.0])), None);
+    }
+
+    #[test]
+    fn polygon_centroid_is_weighted_by_area() {
+        // an L shape, the average of its vertices would be (1.0, 1.25)
+        let polygon = vec![ring(&[
+            (0.0, 0.0),
+            (3.0, 0.0),
+            (3.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 3.0),
+            (0.0, 3.0),
+        ])];
+        assert_close(centroid(&Value::Polygon(polygon)), (1.1, 1.1));
+    }
+
+    #[test]
+    fn holes_are_subtracted_whatever_their_orientation() {
+        let outer = ring(&[(0.0, 0.0), (4.0, 0.0), (4.0, 2.0), (0.0, 2.0)]);
+        let hole = ring(&[(2.0, 0.0), (4.0, 0.0), (4.0, 2.0), (2.0, 2.0)]);
+        let reversed: Vec<_> = hole.iter().rev().cloned().collect();
+        for hole in [hole, reversed] {
+            let polygon = vec![outer.clone(), hole];
+            assert_close(centroid(&Value::Polygon(polygon)), (1.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn multipolygon_centroid_is_weighted_by_area() {
+        let small = vec![ring(&[(10.0, 0.0), (11.0, 0.0), (11.0, 1.0), (10.0, 1.0)])];
+        let large = vec![ring(&[(0.0, 0.0), (3.0, 0.0), (3.0, 3.0), (0.0, 3.0)])];
+        // areas 1 and 9 around (0.5, 10.5) and (1.5, 1.5)
+        assert_close(
+            centroid(&Value::MultiPolygon(vec![small, large])),
+            (1.4, 2.4),
+        );
+    }
+
+    #[test]
+    fn degenerate_polygon_does_not_count_the_closing_vertex_twice() {
+        let polygon = vec![ring(&[(0.0, 0.0), (3.0, 0.0), (6.0, 0.0)])];
+        assert_close(centroid(&Value::Polygon(polygon)), (0.0, 3.0));
+    }
+
+    #[test]
+    fn line_centroid_is_weighted_by_length() {
+        // one long segment and two short ones, the vertex average would be (0.5, 6.25)
+        let line = vec![
+            vec![0.0, 0.0],
+            vec![8.0, 0.0],
+            vec![8.0, 1.0],
+            vec![9.0, 1.0],
+        ];
+        assert_close(centroid(&Value::LineString(line.clone())), (0.15, 4.85));
+        let lines = vec![line, vec![vec![20.0, 5.0], vec![20.0, 5.0]]];
+        assert_close(centroid(&Value::MultiLineString(lines)), (0.15, 4.85));
+        assert_close(
+            centroid(&Value::LineString(vec![vec![2.0, 1.0], vec![2.0, 1.0]])),
+            (1.0, 2.0),
+        );
+    }
+
+    #[test]
+    fn invalid_positions_give_no_centroid() {
+        let line = vec![vec![0.0, 0.0], vec![200.0, 0.0]];
+        assert_eq!(centroid(&Value::LineString(line)), None);
+        assert_eq!(centroid(&Value::MultiPoint(Vec::new())), None);
+        assert_eq!(centroid(&Value::Polygon(Vec::new())), None);
+    }
 }
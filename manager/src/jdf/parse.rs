@@ -0,0 +1,230 @@
+use anyhow::{anyhow, bail};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Seek};
+use std::str::FromStr;
+use zip::ZipArchive;
+
+const SUPPORTED_VERSIONS: [&str; 2] = ["1.10", "1.11"];
+
+/// Parses one JDF batch into sequences of station names, one for each trip.
+pub fn parse_jdf<R: Read + Seek>(archive: &mut ZipArchive<R>) -> anyhow::Result<Vec<Vec<String>>> {
+    let version = read_records(archive, "VerzeJDF.txt")?
+        .first()
+        .and_then(|record| record.first().cloned())
+        .unwrap_or_default();
+    if !SUPPORTED_VERSIONS.contains(&version.as_str()) {
+        bail!("unsupported JDF version {}", version);
+    }
+
+    let mut stop_names = HashMap::new();
+    for record in read_records(archive, "Zastavky.txt")? {
+        if record.len() < 4 {
+            bail!("malformed record in Zastavky.txt: {:?}", record);
+        }
+        stop_names.insert(
+            record[0].clone(),
+            station_name(&record[1], &record[2], &record[3]),
+        );
+    }
+
+    // (line, line distinction, trip) -> tariff number -> station name
+    let mut trips: HashMap<(String, String, String), BTreeMap<i32, String>> = HashMap::new();
+    for record in read_records(archive, "Zasspoje.txt")? {
+        if record.len() < 12 {
+            bail!("malformed record in Zasspoje.txt: {:?}", record);
+        }
+        // skip stops the trip passes or does not go through
+        if !is_time(&record[10]) && !is_time(&record[11]) {
+            continue;
+        }
+        let line_distinction = if record.len() > 12 {
+            record[record.len() - 1].clone()
+        } else {
+            String::new()
+        };
+        let name = stop_names
+            .get(&record[3])
+            .ok_or(anyhow!("unknown stop {} in Zasspoje.txt", record[3]))?;
+        trips
+            .entry((record[0].clone(), line_distinction, record[1].clone()))
+            .or_default()
+            .insert(i32::from_str(&record[2])?, name.clone());
+    }
+
+    let mut stop_chains = Vec::new();
+    for ((_, _, trip), stops) in trips {
+        let mut stop_chain: Vec<String> = stops.into_values().collect();
+        // even trips go against the order of tariff numbers
+        if u32::from_str(&trip)? % 2 == 0 {
+            stop_chain.reverse();
+        }
+        stop_chains.push(stop_chain);
+    }
+    Ok(stop_chains)
+}
+
+/// Joins parts of the stop name the same way as CIS JŘ does.
+fn station_name(municipality: &str, part: &str, place: &str) -> String {
+    match (part.is_empty(), place.is_empty()) {
+        (true, true) => municipality.to_string(),
+        (false, true) => format!("{},{}", municipality, part),
+        (true, false) => format!("{},,{}", municipality, place),
+        (false, false) => format!("{},{},{}", municipality, part, place),
+    }
+}
+
+fn is_time(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Reads records of a file in the batch, file names are matched case-insensitively.
+fn read_records<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    file_name: &str,
+) -> anyhow::Result<Vec<Vec<String>>> {
+    let idx = (0..archive.len())
+        .find(|idx| {
+            archive
+                .name_for_index(*idx)
+                .is_some_and(|name| name.eq_ignore_ascii_case(file_name))
+        })
+        .ok_or(anyhow!("missing {} in JDF batch", file_name))?;
+    let mut bytes = Vec::new();
+    archive.by_index(idx)?.read_to_end(&mut bytes)?;
+    let (text, _, _) = encoding_rs::WINDOWS_1250.decode(&bytes);
+    Ok(parse_records(&text))
+}
+
+/// Splits JDF text into records of fields, fields are quoted and records end with `;`.
+fn parse_records(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            _ if quoted => field.push(c),
+            ',' => record.push(std::mem::take(&mut field)),
+            ';' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            '\r' | '\n' => {}
+            _ => field.push(c),
+        }
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+
+    /// Packs files into a JDF batch, their content encoded in windows-1250.
+    fn batch(files: &[(&str, &str)]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            let (bytes, _, _) = encoding_rs::WINDOWS_1250.encode(content);
+            writer.write_all(&bytes).unwrap();
+        }
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    const STOPS: &str = "\"1\",\"Brno\",\"Hlavní nádraží\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\";\r\n\
+\"2\",\"Šlapanice\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\";\r\n\
+\"3\",\"Újezd u Brna\",\"\",\"nám.\",\"\",\"\",\"\",\"\",\"\",\"\",\"\";\r\n";
+
+    #[test]
+    fn version_1_10_trips_follow_tariff_numbers() {
+        let mut archive = batch(&[
+            (
+                "VerzeJDF.txt",
+                "\"1.10\",\"\",\"\",\"\",\"01012025\",\"\";\r\n",
+            ),
+            ("Zastavky.txt", STOPS),
+            (
+                "Zasspoje.txt",
+                "\"100\",\"1\",\"1\",\"1\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"0600\";\r\n\
+\"100\",\"1\",\"2\",\"2\",\"\",\"\",\"\",\"\",\"\",\"\",\"|\",\"|\";\r\n\
+\"100\",\"1\",\"3\",\"3\",\"\",\"\",\"\",\"\",\"\",\"\",\"0630\",\"\";\r\n\
+\"100\",\"2\",\"1\",\"1\",\"\",\"\",\"\",\"\",\"\",\"\",\"0730\",\"\";\r\n\
+\"100\",\"2\",\"2\",\"2\",\"\",\"\",\"\",\"\",\"\",\"\",\"0715\",\"0716\";\r\n\
+\"100\",\"2\",\"3\",\"3\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"0700\";\r\n",
+            ),
+        ]);
+
+        let mut chains = parse_jdf(&mut archive).unwrap();
+        chains.sort();
+        assert_eq!(
+            chains,
+            [
+                vec!["Brno,Hlavní nádraží", "Újezd u Brna,,nám."],
+                vec!["Újezd u Brna,,nám.", "Šlapanice", "Brno,Hlavní nádraží"],
+            ]
+        );
+    }
+
+    #[test]
+    fn version_1_11_tells_lines_apart_by_distinction() {
+        let mut archive = batch(&[
+            (
+                "verzejdf.txt",
+                "\"1.11\",\"\",\"\",\"\",\"01012025\",\"\";\r\n",
+            ),
+            ("zastavky.txt", STOPS),
+            (
+                "zasspoje.txt",
+                "\"100\",\"1\",\"1\",\"1\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"0600\",\"1\";\r\n\
+\"100\",\"1\",\"2\",\"2\",\"\",\"\",\"\",\"\",\"\",\"\",\"0610\",\"\",\"1\";\r\n\
+\"100\",\"1\",\"1\",\"1\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"0800\",\"2\";\r\n\
+\"100\",\"1\",\"3\",\"3\",\"\",\"\",\"\",\"\",\"\",\"\",\"0820\",\"\",\"2\";\r\n",
+            ),
+        ]);
+
+        let mut chains = parse_jdf(&mut archive).unwrap();
+        chains.sort();
+        assert_eq!(
+            chains,
+            [
+                vec!["Brno,Hlavní nádraží", "Újezd u Brna,,nám."],
+                vec!["Brno,Hlavní nádraží", "Šlapanice"],
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported_versions_are_rejected() {
+        let mut archive = batch(&[
+            (
+                "VerzeJDF.txt",
+                "\"1.9\",\"\",\"\",\"\",\"01012025\",\"\";\r\n",
+            ),
+            ("Zastavky.txt", STOPS),
+            ("Zasspoje.txt", ""),
+        ]);
+        let err = parse_jdf(&mut archive).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported JDF version 1.9");
+
+        let mut archive = batch(&[("Zastavky.txt", STOPS)]);
+        assert!(parse_jdf(&mut archive).is_err());
+    }
+
+    #[test]
+    fn quoted_fields_may_contain_separators() {
+        assert_eq!(
+            parse_records("\"a,b\",\"c;d\",\"e\"\"f\";\r\n\"\";"),
+            [vec!["a,b", "c;d", "e\"f"], vec![""]]
+        );
+    }
+}
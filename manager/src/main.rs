@@ -1,11 +1,19 @@
-mod database;
 mod export;
+mod export_gtfs;
+mod gtfs;
 mod import;
+mod jdf;
 mod netex;
+mod ruian;
+mod stations;
 mod stats;
+mod users;
 
 use clap::{Parser, Subcommand};
-use inv_zastavky_core::suggest::chain::{chain_options, path_options};
+use inv_zastavky_core::migrations;
+use inv_zastavky_core::model::StopId;
+use inv_zastavky_core::model::station::MergePosition;
+use inv_zastavky_core::suggest::chain::{chain_options, path_options, ChainSearchParams};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
@@ -28,18 +36,63 @@ enum Commands {
             help = "Path pointing to ZIP of Netex files"
         )]
         netex_path: Option<PathBuf>,
+        #[arg(
+            long,
+            value_name = "JDF FILE",
+            help = "Path pointing to ZIP of JDF batch or ZIP of zipped JDF batches"
+        )]
+        jdf_path: Option<PathBuf>,
+        #[arg(
+            long,
+            value_name = "GTFS FILE",
+            help = "Path pointing to ZIP of GTFS feed"
+        )]
+        gtfs_path: Option<PathBuf>,
         #[arg(
             long,
             value_name = "GEOJSON FILE",
             help = "Path pointing to GeoJSON encoded list of base stations"
         )]
         base_stations: Option<PathBuf>,
+        #[arg(
+            long,
+            value_name = "KEY",
+            default_value = "name",
+            help = "Property of base stations with their name"
+        )]
+        base_stations_name: String,
+        #[arg(
+            long,
+            value_name = "KEY",
+            help = "Property of base stations with their ID, e.g. of the source data set"
+        )]
+        base_stations_id: Option<String>,
         #[arg(
             long,
             value_name = "GEOJSON FILE",
             help = "Path pointing to GeoJSON encoded list of cities"
         )]
         base_cities: Option<PathBuf>,
+        #[arg(
+            long,
+            value_name = "KEY",
+            default_value = "Jméno",
+            help = "Property of cities with their name"
+        )]
+        base_cities_name: String,
+        #[arg(
+            long,
+            value_name = "KEY",
+            default_value = "Okres",
+            help = "Property of cities with their district name or CIS JŘ code"
+        )]
+        base_cities_district: String,
+        #[arg(
+            long,
+            value_name = "VFR FILE",
+            help = "Path pointing to RÚIAN exchange format XML (or its ZIP) with municipalities, their parts and districts"
+        )]
+        ruian: Option<PathBuf>,
     },
     Export {
         #[arg(
@@ -48,47 +101,174 @@ enum Commands {
         )]
         output_file: PathBuf,
     },
+    ExportGtfs {
+        #[arg(
+            long,
+            value_name = "NETEX FILE",
+            help = "Path pointing to ZIP of Netex files"
+        )]
+        netex_path: PathBuf,
+        #[arg(
+            value_name = "OUTPUT DIRECTORY",
+            help = "Directory to write GTFS files into"
+        )]
+        output_dir: PathBuf,
+    },
     Stats {},
+    /// Merges station REMOVE into station KEEP, chain positions of REMOVE are moved to KEEP.
+    Merge {
+        #[arg(value_name = "KEEP")]
+        keep: StopId,
+        #[arg(value_name = "REMOVE")]
+        remove: StopId,
+        #[arg(long, help = "Keep position of the removed station")]
+        removed_position: bool,
+        #[arg(long, value_name = "USERNAME", help = "User the change is attributed to")]
+        user: String,
+    },
+    /// Removes chain assignments and station rows which do not belong to an existing station.
+    Repair {
+        #[arg(long, help = "Only report what would be repaired")]
+        dry_run: bool,
+    },
+    /// Reports stations placed outside of the municipality they name.
+    Qa {},
+    User {
+        #[command(subcommand)]
+        command: UserCommands,
+    },
     Dev {
         #[arg()]
         chain_hash: String,
     },
 }
 
+/// Management of editor accounts, passwords are read from `INV_ZASTAVKY_PASSWORD` or stdin.
+#[derive(Subcommand)]
+enum UserCommands {
+    Add {
+        #[arg()]
+        username: String,
+    },
+    Passwd {
+        #[arg()]
+        username: String,
+    },
+    Token {
+        #[arg()]
+        username: String,
+        #[arg(long, default_value = "cli", help = "Name to recognize the token by")]
+        name: String,
+    },
+    Revoke {
+        #[arg()]
+        username: String,
+    },
+    List {},
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
     let db_pool = sqlx::sqlite::SqlitePool::connect(&cli.db_url).await?;
-    database::ensure_tables(&db_pool).await?;
+    migrations::migrate(&mut db_pool.acquire().await?).await?;
     match cli.command {
         Commands::Import {
             netex_path,
+            jdf_path,
+            gtfs_path,
             base_stations,
+            base_stations_name,
+            base_stations_id,
             base_cities,
+            base_cities_name,
+            base_cities_district,
+            ruian,
         } => {
-            if netex_path.is_some() {
-                App::import(&db_pool, netex_path).await?;
+            if netex_path.is_some() || jdf_path.is_some() || gtfs_path.is_some() {
+                App::import(&db_pool, netex_path, jdf_path, gtfs_path).await?;
             }
-            if base_stations.is_some() {
-                App::import_base_stations(&db_pool, base_stations.unwrap()).await?;
+            if let Some(base_stations) = base_stations {
+                App::import_base_stations(
+                    &db_pool,
+                    base_stations,
+                    &base_stations_name,
+                    base_stations_id.as_deref(),
+                )
+                .await?;
             }
-            if base_cities.is_some() {
-                App::import_base_cities(&db_pool, base_cities.unwrap()).await?;
+            if let Some(base_cities) = base_cities {
+                App::import_base_cities(
+                    &db_pool,
+                    base_cities,
+                    &base_cities_name,
+                    &base_cities_district,
+                )
+                .await?;
+            }
+            if let Some(ruian) = ruian {
+                App::import_ruian(&db_pool, ruian).await?;
             }
         }
         Commands::Export { output_file } => {
             App::export(&db_pool, output_file).await?;
         }
+        Commands::ExportGtfs {
+            netex_path,
+            output_dir,
+        } => {
+            App::export_gtfs(&db_pool, netex_path, output_dir).await?;
+        }
         Commands::Stats {} => {
             App::print_stats(&db_pool).await?;
         }
+        Commands::Merge {
+            keep,
+            remove,
+            removed_position,
+            user,
+        } => {
+            let position = if removed_position {
+                MergePosition::Remove
+            } else {
+                MergePosition::Keep
+            };
+            App::merge_stations(&db_pool, &user, keep, remove, position).await?;
+        }
+        Commands::Repair { dry_run } => {
+            App::repair(&db_pool, dry_run).await?;
+        }
+        Commands::Qa {} => {
+            App::qa(&db_pool).await?;
+        }
+        Commands::User { command } => match command {
+            UserCommands::Add { username } => App::add_user(&db_pool, username).await?,
+            UserCommands::Passwd { username } => {
+                App::set_user_password(&db_pool, username).await?
+            }
+            UserCommands::Token { username, name } => {
+                App::create_user_token(&db_pool, username, name).await?
+            }
+            UserCommands::Revoke { username } => {
+                App::revoke_user_tokens(&db_pool, username).await?
+            }
+            UserCommands::List {} => App::list_users(&db_pool).await?,
+        },
         Commands::Dev { chain_hash } => {
-            for suggestion in
-                path_options(&mut db_pool.acquire().await?, &chain_hash, &HashMap::new()).await?
+            let params = ChainSearchParams::default();
+            for suggestion in path_options(
+                &mut db_pool.acquire().await?,
+                &chain_hash,
+                &HashMap::new(),
+                &params,
+            )
+            .await?
             {
                 println!("cities: {:?}", suggestion)
             }
-            for suggestion in chain_options(&mut db_pool.acquire().await?, &chain_hash).await? {
+            for suggestion in
+                chain_options(&mut db_pool.acquire().await?, &chain_hash, &params).await?
+            {
                 println!("stations: {:?}", suggestion)
             }
         }
@@ -1,7 +1,8 @@
-use quick_xml::events::Event;
+use quick_xml::events::{BytesStart, Event};
 use quick_xml::Reader;
 use std::collections::{BTreeMap, HashMap};
 use std::io::BufRead;
+use anyhow::anyhow;
 use std::str::FromStr;
 
 #[derive(Debug)]
@@ -10,6 +11,68 @@ struct ParsedJourneyPattern {
     points: HashMap<String, String>,
 }
 
+#[derive(Debug, Default)]
+pub struct ParsedStopPlace {
+    pub name: Option<String>,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    pub quays: BTreeMap<String, (Option<f64>, Option<f64>)>,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedLine {
+    pub name: Option<String>,
+    pub public_code: Option<String>,
+    pub transport_mode: Option<String>,
+    pub operator: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedPassingTime {
+    pub stop_point: Option<String>,
+    pub arrival: Option<String>,
+    pub departure: Option<String>,
+    pub arrival_day_offset: u32,
+    pub departure_day_offset: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedServiceJourney {
+    pub id: String,
+    pub pattern: Option<String>,
+    pub line: Option<String>,
+    pub day_types: Vec<String>,
+    pub passing_times: Vec<ParsedPassingTime>,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedOperatingPeriod {
+    pub from_date: Option<String>,
+    pub valid_day_bits: Option<String>,
+}
+
+/// Stops of a journey pattern.
+#[derive(Debug, Default)]
+pub struct ParsedChain {
+    /// Id of the journey pattern, unique within one NeTEx file only.
+    pub pattern: String,
+    pub stations: Vec<String>,
+    /// Stop points in the same order as `stations`.
+    pub points: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedNetex {
+    pub chains: Vec<ParsedChain>,
+    pub stop_places: HashMap<String, ParsedStopPlace>,
+    pub operators: HashMap<String, Option<String>>,
+    pub lines: HashMap<String, ParsedLine>,
+    pub journeys: Vec<ParsedServiceJourney>,
+    /// Operating periods assigned to day types, keyed by id of the day type.
+    pub day_types: HashMap<String, Vec<String>>,
+    pub operating_periods: HashMap<String, ParsedOperatingPeriod>,
+}
+
 macro_rules! netex_frames {
     // taken from vec! macro
     ($($x:expr),+ $(,)?) => (
@@ -19,18 +82,28 @@ macro_rules! netex_frames {
     );
 }
 
-pub fn parse_netex<R: BufRead>(mut reader: Reader<R>) -> anyhow::Result<Vec<Vec<String>>> {
+pub fn parse_netex<R: BufRead>(mut reader: Reader<R>) -> anyhow::Result<ParsedNetex> {
     let mut path = Vec::with_capacity(64);
     let mut buffer = Vec::new();
 
     let mut id = None;
     let mut id_pattern = None;
+    let mut id_quay = None;
+    let mut id_operator = None;
+    let mut id_line = None;
+    let mut id_period = None;
 
-    let mut stop_place2name_type: HashMap<String, Option<String>> = HashMap::new();
+    let mut stop_places: HashMap<String, ParsedStopPlace> = HashMap::new();
     let mut passenger_stops: Vec<(Option<String>, Option<String>)> = Vec::new();
 
     let mut journey_patterns: HashMap<String, ParsedJourneyPattern> = HashMap::new();
 
+    let mut operators: HashMap<String, Option<String>> = HashMap::new();
+    let mut lines: HashMap<String, ParsedLine> = HashMap::new();
+    let mut journeys: Vec<ParsedServiceJourney> = Vec::new();
+    let mut operating_periods: HashMap<String, ParsedOperatingPeriod> = HashMap::new();
+    let mut day_type_assignments: Vec<(Option<String>, Option<String>)> = Vec::new();
+
     loop {
         match reader.read_event_into(&mut buffer) {
             Ok(Event::Start(ref e)) => {
@@ -84,7 +157,7 @@ pub fn parse_netex<R: BufRead>(mut reader: Reader<R>) -> anyhow::Result<Vec<Vec<
                             .to_string(),
                     );
                     let order =
-                        i32::from_str(&*e.try_get_attribute("order")?.unwrap().unescape_value()?)?;
+                        i32::from_str(&e.try_get_attribute("order")?.unwrap().unescape_value()?)?;
                     journey_patterns
                         .get_mut(&id_pattern.clone().unwrap())
                         .unwrap()
@@ -98,7 +171,76 @@ pub fn parse_netex<R: BufRead>(mut reader: Reader<R>) -> anyhow::Result<Vec<Vec<
                             .unescape_value()?
                             .to_string(),
                     );
-                    stop_place2name_type.insert(id.clone().unwrap().clone(), None);
+                    stop_places.insert(id.clone().unwrap().clone(), ParsedStopPlace::default());
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames!["SiteFrame", "stopPlaces", "StopPlace", "quays", "Quay"],
+                ) {
+                    id_quay = Some(
+                        e.try_get_attribute("id")?
+                            .unwrap()
+                            .unescape_value()?
+                            .to_string(),
+                    );
+                    stop_places
+                        .get_mut(&id.clone().unwrap())
+                        .unwrap()
+                        .quays
+                        .insert(id_quay.clone().unwrap(), (None, None));
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames!["ResourceFrame", "organisations", "Operator"],
+                ) {
+                    id_operator = Some(attribute(e, "id")?);
+                    operators.insert(id_operator.clone().unwrap(), None);
+                } else if path_vec_eq(&path, netex_frames!["ServiceFrame", "lines", "Line"]) {
+                    id_line = Some(attribute(e, "id")?);
+                    lines.insert(id_line.clone().unwrap(), ParsedLine::default());
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames!["TimetableFrame", "vehicleJourneys", "ServiceJourney"],
+                ) {
+                    journeys.push(ParsedServiceJourney {
+                        id: attribute(e, "id")?,
+                        ..ParsedServiceJourney::default()
+                    });
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames![
+                        "TimetableFrame",
+                        "vehicleJourneys",
+                        "ServiceJourney",
+                        "passingTimes",
+                        "TimetabledPassingTime"
+                    ],
+                ) {
+                    journeys
+                        .last_mut()
+                        .unwrap()
+                        .passing_times
+                        .push(ParsedPassingTime::default());
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames![
+                        "ServiceCalendarFrame",
+                        "operatingPeriods",
+                        "UicOperatingPeriod"
+                    ],
+                ) {
+                    id_period = Some(attribute(e, "id")?);
+                    operating_periods.insert(
+                        id_period.clone().unwrap(),
+                        ParsedOperatingPeriod::default(),
+                    );
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames![
+                        "ServiceCalendarFrame",
+                        "dayTypeAssignments",
+                        "DayTypeAssignment"
+                    ],
+                ) {
+                    day_type_assignments.push((None, None));
                 }
             }
             Ok(Event::Empty(e)) => {
@@ -155,6 +297,89 @@ pub fn parse_netex<R: BufRead>(mut reader: Reader<R>) -> anyhow::Result<Vec<Vec<
                             .unescape_value()?
                             .to_string(),
                     );
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames!["ServiceFrame", "lines", "Line", "OperatorRef"],
+                ) {
+                    lines.get_mut(&id_line.clone().unwrap()).unwrap().operator =
+                        Some(attribute(&e, "ref")?);
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames![
+                        "TimetableFrame",
+                        "vehicleJourneys",
+                        "ServiceJourney",
+                        "dayTypes",
+                        "DayTypeRef"
+                    ],
+                ) {
+                    journeys
+                        .last_mut()
+                        .unwrap()
+                        .day_types
+                        .push(attribute(&e, "ref")?);
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames![
+                        "TimetableFrame",
+                        "vehicleJourneys",
+                        "ServiceJourney",
+                        "ServiceJourneyPatternRef"
+                    ],
+                ) || path_vec_eq(
+                    &path,
+                    netex_frames![
+                        "TimetableFrame",
+                        "vehicleJourneys",
+                        "ServiceJourney",
+                        "JourneyPatternRef"
+                    ],
+                ) {
+                    journeys.last_mut().unwrap().pattern = Some(attribute(&e, "ref")?);
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames![
+                        "TimetableFrame",
+                        "vehicleJourneys",
+                        "ServiceJourney",
+                        "LineRef"
+                    ],
+                ) {
+                    journeys.last_mut().unwrap().line = Some(attribute(&e, "ref")?);
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames![
+                        "TimetableFrame",
+                        "vehicleJourneys",
+                        "ServiceJourney",
+                        "passingTimes",
+                        "TimetabledPassingTime",
+                        "StopPointInJourneyPatternRef"
+                    ],
+                ) {
+                    let journey = journeys.last_mut().unwrap();
+                    journey.passing_times.last_mut().unwrap().stop_point =
+                        Some(attribute(&e, "ref")?);
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames![
+                        "ServiceCalendarFrame",
+                        "dayTypeAssignments",
+                        "DayTypeAssignment",
+                        "OperatingPeriodRef"
+                    ],
+                ) {
+                    day_type_assignments.last_mut().unwrap().0 = Some(attribute(&e, "ref")?);
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames![
+                        "ServiceCalendarFrame",
+                        "dayTypeAssignments",
+                        "DayTypeAssignment",
+                        "DayTypeRef"
+                    ],
+                ) {
+                    day_type_assignments.last_mut().unwrap().1 = Some(attribute(&e, "ref")?);
                 }
                 path.pop();
             }
@@ -166,14 +391,152 @@ pub fn parse_netex<R: BufRead>(mut reader: Reader<R>) -> anyhow::Result<Vec<Vec<
                     &path,
                     netex_frames!["SiteFrame", "stopPlaces", "StopPlace", "Name"],
                 ) {
-                    stop_place2name_type
-                        .insert(id.clone().unwrap(), Some(e.unescape()?.to_string()));
+                    stop_places.get_mut(&id.clone().unwrap()).unwrap().name =
+                        Some(e.unescape()?.to_string());
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames![
+                        "SiteFrame",
+                        "stopPlaces",
+                        "StopPlace",
+                        "Centroid",
+                        "Location",
+                        "Latitude"
+                    ],
+                ) {
+                    stop_places.get_mut(&id.clone().unwrap()).unwrap().lat =
+                        Some(f64::from_str(e.unescape()?.trim())?);
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames![
+                        "SiteFrame",
+                        "stopPlaces",
+                        "StopPlace",
+                        "Centroid",
+                        "Location",
+                        "Longitude"
+                    ],
+                ) {
+                    stop_places.get_mut(&id.clone().unwrap()).unwrap().lon =
+                        Some(f64::from_str(e.unescape()?.trim())?);
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames![
+                        "SiteFrame",
+                        "stopPlaces",
+                        "StopPlace",
+                        "quays",
+                        "Quay",
+                        "Centroid",
+                        "Location",
+                        "Latitude"
+                    ],
+                ) {
+                    let stop_place = stop_places.get_mut(&id.clone().unwrap()).unwrap();
+                    stop_place
+                        .quays
+                        .get_mut(&id_quay.clone().unwrap())
+                        .unwrap()
+                        .0 = Some(f64::from_str(e.unescape()?.trim())?);
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames![
+                        "SiteFrame",
+                        "stopPlaces",
+                        "StopPlace",
+                        "quays",
+                        "Quay",
+                        "Centroid",
+                        "Location",
+                        "Longitude"
+                    ],
+                ) {
+                    let stop_place = stop_places.get_mut(&id.clone().unwrap()).unwrap();
+                    stop_place
+                        .quays
+                        .get_mut(&id_quay.clone().unwrap())
+                        .unwrap()
+                        .1 = Some(f64::from_str(e.unescape()?.trim())?);
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames!["ResourceFrame", "organisations", "Operator", "Name"],
+                ) {
+                    operators.insert(id_operator.clone().unwrap(), Some(e.unescape()?.to_string()));
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames!["ServiceFrame", "lines", "Line", "Name"],
+                ) {
+                    lines.get_mut(&id_line.clone().unwrap()).unwrap().name =
+                        Some(e.unescape()?.to_string());
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames!["ServiceFrame", "lines", "Line", "PublicCode"],
+                ) {
+                    lines.get_mut(&id_line.clone().unwrap()).unwrap().public_code =
+                        Some(e.unescape()?.to_string());
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames!["ServiceFrame", "lines", "Line", "TransportMode"],
+                ) {
+                    lines.get_mut(&id_line.clone().unwrap()).unwrap().transport_mode =
+                        Some(e.unescape()?.trim().to_string());
+                } else if path.len() == 10
+                    && path_vec_eq(
+                        &path[..9],
+                        netex_frames![
+                            "TimetableFrame",
+                            "vehicleJourneys",
+                            "ServiceJourney",
+                            "passingTimes",
+                            "TimetabledPassingTime"
+                        ],
+                    )
+                {
+                    let journey = journeys.last_mut().unwrap();
+                    let passing_time = journey.passing_times.last_mut().unwrap();
+                    let value = e.unescape()?.trim().to_string();
+                    match path[9].as_str() {
+                        "ArrivalTime" => passing_time.arrival = Some(value),
+                        "DepartureTime" => passing_time.departure = Some(value),
+                        "ArrivalDayOffset" => passing_time.arrival_day_offset = u32::from_str(&value)?,
+                        "DepartureDayOffset" => {
+                            passing_time.departure_day_offset = u32::from_str(&value)?
+                        }
+                        _ => {}
+                    }
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames![
+                        "ServiceCalendarFrame",
+                        "operatingPeriods",
+                        "UicOperatingPeriod",
+                        "FromDate"
+                    ],
+                ) {
+                    operating_periods
+                        .get_mut(&id_period.clone().unwrap())
+                        .unwrap()
+                        .from_date = Some(e.unescape()?.trim().to_string());
+                } else if path_vec_eq(
+                    &path,
+                    netex_frames![
+                        "ServiceCalendarFrame",
+                        "operatingPeriods",
+                        "UicOperatingPeriod",
+                        "ValidDayBits"
+                    ],
+                ) {
+                    operating_periods
+                        .get_mut(&id_period.clone().unwrap())
+                        .unwrap()
+                        .valid_day_bits = Some(e.unescape()?.trim().to_string());
                 }
             }
             Ok(Event::Eof) => break,
             Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
             Ok(_) => {}
         }
+        buffer.clear();
     }
 
     let mut new_stops = Vec::new();
@@ -181,28 +544,113 @@ pub fn parse_netex<R: BufRead>(mut reader: Reader<R>) -> anyhow::Result<Vec<Vec<
     for (sched_stop_ref, stop_place_ref) in &passenger_stops {
         idx_stops.insert(sched_stop_ref.clone().unwrap(), new_stops.len());
         new_stops.push(
-            stop_place2name_type[&stop_place_ref.clone().unwrap()]
+            stop_places[&stop_place_ref.clone().unwrap()]
+                .name
                 .clone()
-                .unwrap()
-                .clone(),
+                .unwrap(),
         );
     }
 
     let mut stop_chains = Vec::new();
-    for (_, pattern) in journey_patterns {
-        let mut stop_chain = Vec::new();
+    for (id, pattern) in journey_patterns {
+        let mut stations = Vec::new();
+        let mut points = Vec::new();
         for (_, stop_point) in pattern.order {
-            stop_chain.push(new_stops[idx_stops[&pattern.points[&stop_point]]].clone());
+            stations.push(new_stops[idx_stops[&pattern.points[&stop_point]]].clone());
+            points.push(stop_point);
         }
-        stop_chains.push(stop_chain);
+        stop_chains.push(ParsedChain {
+            pattern: id,
+            stations,
+            points,
+        });
     }
 
-    Ok(stop_chains)
+    let mut day_types: HashMap<String, Vec<String>> = HashMap::new();
+    for (period_ref, day_type_ref) in day_type_assignments {
+        if let (Some(period_ref), Some(day_type_ref)) = (period_ref, day_type_ref) {
+            day_types.entry(day_type_ref).or_default().push(period_ref);
+        }
+    }
+
+    Ok(ParsedNetex {
+        chains: stop_chains,
+        stop_places,
+        operators,
+        lines,
+        journeys,
+        day_types,
+        operating_periods,
+    })
+}
+
+fn attribute(e: &BytesStart, key: &str) -> anyhow::Result<String> {
+    Ok(e.try_get_attribute(key)?
+        .ok_or(anyhow!("missing attribute {}", key))?
+        .unescape_value()?
+        .to_string())
 }
 
-fn path_vec_eq(left_path: &Vec<String>, rigth_path: Vec<&str>) -> bool {
+fn path_vec_eq(left_path: &[String], rigth_path: Vec<&str>) -> bool {
     if left_path.len() != rigth_path.len() {
         return false;
     }
     left_path.iter().zip(rigth_path.iter()).all(|(a, b)| a == b)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NETEX: &str = r#"<PublicationDelivery><dataObjects><CompositeFrame><frames>
+<SiteFrame><stopPlaces>
+<StopPlace id="SP1"><Name>Adamov,žel.st.</Name>
+<Centroid><Location><Longitude> 16.6553 </Longitude><Latitude>49.2981</Latitude></Location></Centroid>
+<quays>
+<Quay id="Q1"><Centroid><Location><Longitude>16.6551</Longitude><Latitude>49.2980</Latitude></Location></Centroid></Quay>
+<Quay id="Q2"></Quay>
+</quays>
+</StopPlace>
+<StopPlace id="SP2"><Name>Blansko,nádr.</Name></StopPlace>
+</stopPlaces></SiteFrame>
+<ServiceFrame>
+<scheduledStopPoints><ScheduledStopPoint id="S1"/><ScheduledStopPoint id="S2"></ScheduledStopPoint></scheduledStopPoints>
+<stopAssignments>
+<PassengerStopAssignment><ScheduledStopPointRef ref="S1"/><StopPlaceRef ref="SP1"/></PassengerStopAssignment>
+<PassengerStopAssignment><ScheduledStopPointRef ref="S2"/><StopPlaceRef ref="SP2"/></PassengerStopAssignment>
+</stopAssignments>
+<journeyPatterns><ServiceJourneyPattern id="J1"><pointsInSequence>
+<StopPointInJourneyPattern id="P2" order="2"><ScheduledStopPointRef ref="S1"/></StopPointInJourneyPattern>
+<StopPointInJourneyPattern id="P1" order="1"><ScheduledStopPointRef ref="S2"/></StopPointInJourneyPattern>
+</pointsInSequence></ServiceJourneyPattern></journeyPatterns>
+</ServiceFrame>
+</frames></CompositeFrame></dataObjects></PublicationDelivery>"#;
+
+    #[test]
+    fn stop_places_keep_coordinates_of_centroid_and_quays() {
+        let parsed = parse_netex(Reader::from_str(NETEX)).unwrap();
+
+        let adamov = &parsed.stop_places["SP1"];
+        assert_eq!(adamov.name.as_deref(), Some("Adamov,žel.st."));
+        assert_eq!((adamov.lat, adamov.lon), (Some(49.2981), Some(16.6553)));
+        assert_eq!(adamov.quays.len(), 2);
+        assert_eq!(adamov.quays["Q1"], (Some(49.2980), Some(16.6551)));
+        assert_eq!(adamov.quays["Q2"], (None, None));
+
+        let blansko = &parsed.stop_places["SP2"];
+        assert_eq!(blansko.name.as_deref(), Some("Blansko,nádr."));
+        assert_eq!((blansko.lat, blansko.lon), (None, None));
+        assert!(blansko.quays.is_empty());
+    }
+
+    #[test]
+    fn chains_follow_the_order_of_stop_points() {
+        let parsed = parse_netex(Reader::from_str(NETEX)).unwrap();
+
+        assert_eq!(parsed.chains.len(), 1);
+        let chain = &parsed.chains[0];
+        assert_eq!(chain.pattern, "J1");
+        assert_eq!(chain.stations, ["Blansko,nádr.", "Adamov,žel.st."]);
+        assert_eq!(chain.points, ["P1", "P2"]);
+    }
+}
@@ -0,0 +1,117 @@
+//! Conversion of S-JTSK coordinates used by RÚIAN (EPSG:5514) to WGS 84.
+
+use std::f64::consts::PI;
+
+/// Converts `gml:pos` of EPSG:5514, `-Y -X` of S-JTSK, to `(lat, lon)` of WGS 84.
+pub fn to_wgs84(east: f64, north: f64) -> (f64, f64) {
+    let (lat, lon) = krovak_inverse(-north, -east);
+    bessel_to_wgs84(lat, lon)
+}
+
+/// Geodetic `(lat, lon)` on the Bessel ellipsoid in radians from S-JTSK `X`, `Y`.
+fn krovak_inverse(x: f64, y: f64) -> (f64, f64) {
+    let e: f64 = 0.081_696_831_215_303;
+    let n: f64 = 0.979_924_704_620_83;
+    let ro_0: f64 = 12_310_230.127_970_36;
+    let (sin_uq, cos_uq) = (0.863_499_969_506_341, 0.504_348_889_819_882);
+    let (sin_vq, cos_vq) = (0.420_215_144_586_493, 0.907_424_504_992_097);
+    let alpha: f64 = 1.000_597_498_371_542;
+    let k: f64 = 1.003_419_163_966_575;
+
+    let ro = f64::sqrt(x * x + y * y);
+    let epsilon = 2.0 * f64::atan(y / (ro + x));
+    let d = epsilon / n;
+    let s = 2.0 * f64::atan(f64::exp(1.0 / n * f64::ln(ro_0 / ro))) - PI / 2.0;
+    let sin_u = sin_uq * s.sin() - cos_uq * s.cos() * d.cos();
+    let cos_u = f64::sqrt(1.0 - sin_u * sin_u);
+    let sin_dv = d.sin() * s.cos() / cos_u;
+    let cos_dv = f64::sqrt(1.0 - sin_dv * sin_dv);
+    let sin_v = sin_vq * cos_dv - cos_vq * sin_dv;
+    let cos_v = cos_vq * cos_dv + sin_vq * sin_dv;
+    let lon = 2.0 * f64::atan(sin_v / (1.0 + cos_v)) / alpha;
+
+    let t = f64::exp(2.0 / alpha * f64::ln((1.0 + sin_u) / cos_u / k));
+    let mut sin_lat = (t - 1.0) / (t + 1.0);
+    for _ in 0..100 {
+        let next = t * f64::exp(e * f64::ln((1.0 + e * sin_lat) / (1.0 - e * sin_lat)));
+        let next = (next - 1.0) / (next + 1.0);
+        let done = (next - sin_lat).abs() < 1e-15;
+        sin_lat = next;
+        if done {
+            break;
+        }
+    }
+    (f64::asin(sin_lat), lon)
+}
+
+/// Moves a point of the Bessel ellipsoid to WGS 84 by the seven-parameter Helmert transformation,
+/// returns degrees.
+fn bessel_to_wgs84(lat: f64, lon: f64) -> (f64, f64) {
+    // points are assumed to be at an average height of the country
+    let height = 245.0;
+    let a = 6_377_397.155_08;
+    let f_1 = 299.152_812_853;
+    let e2 = 1.0 - (1.0 - 1.0 / f_1) * (1.0 - 1.0 / f_1);
+    let ro = a / f64::sqrt(1.0 - e2 * lat.sin() * lat.sin());
+    let x = (ro + height) * lat.cos() * lon.cos();
+    let y = (ro + height) * lat.cos() * lon.sin();
+    let z = ((1.0 - e2) * ro + height) * lat.sin();
+
+    let (dx, dy, dz) = (570.69, 85.69, 462.84);
+    let arc_second = PI / 180.0 / 3600.0;
+    let (wx, wy, wz) = (
+        -4.998_21 * arc_second,
+        -1.586_76 * arc_second,
+        -5.2611 * arc_second,
+    );
+    let m = 3.543e-6;
+    let xn = dx + (1.0 + m) * (x + wz * y - wy * z);
+    let yn = dy + (1.0 + m) * (-wz * x + y + wx * z);
+    let zn = dz + (1.0 + m) * (wy * x - wx * y + z);
+
+    let a = 6_378_137.0;
+    let f_1 = 298.257_223_563;
+    let a_b = f_1 / (f_1 - 1.0);
+    let e2 = 1.0 - (1.0 - 1.0 / f_1) * (1.0 - 1.0 / f_1);
+    let p = f64::sqrt(xn * xn + yn * yn);
+    let theta = f64::atan(zn * a_b / p);
+    let t = (zn + e2 * a_b * a * theta.sin().powi(3)) / (p - e2 * a * theta.cos().powi(3));
+    let lat = f64::atan(t);
+    let lon = 2.0 * f64::atan(yn / (p + xn));
+    (lat.to_degrees(), lon.to_degrees())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near((lat, lon): (f64, f64), expected: (f64, f64), tolerance: f64) {
+        assert!(
+            (lat - expected.0).abs() < tolerance && (lon - expected.1).abs() < tolerance,
+            "({}, {}) != {:?}",
+            lat,
+            lon,
+            expected
+        );
+    }
+
+    #[test]
+    fn krovak_matches_epsg_example() {
+        // EPSG Guidance Note 7-2, 50°12'32.442"N 16°50'59.179"E on the Bessel ellipsoid
+        let (lat, lon) = krovak_inverse(1_050_538.63, 568_991.00);
+        assert_near(
+            (lat.to_degrees(), lon.to_degrees()),
+            (50.209_012, 16.849_772),
+            1e-6,
+        );
+    }
+
+    #[test]
+    fn prague_old_town_square_is_converted_to_wgs84() {
+        assert_near(
+            to_wgs84(-742_851.25, -1_043_008.86),
+            (50.087_451, 14.420_671),
+            1e-5,
+        );
+    }
+}
@@ -0,0 +1,2 @@
+pub mod jtsk;
+pub mod parse;
@@ -0,0 +1,270 @@
+use crate::ruian::jtsk;
+use anyhow::anyhow;
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use std::io::BufRead;
+use std::str::FromStr;
+
+/// Ring of `(lat, lon)` points, the first one is repeated at its end.
+pub type Ring = Vec<(f64, f64)>;
+
+/// Outer ring of a polygon followed by its holes.
+pub type Polygon = Vec<Ring>;
+
+#[derive(Debug, Default)]
+pub struct ParsedDistrict {
+    pub code: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedMunicipality {
+    pub code: i64,
+    pub name: String,
+    /// RÚIAN code of the district.
+    pub district: Option<i64>,
+    /// Definition point, `(lat, lon)`.
+    pub point: Option<(f64, f64)>,
+    /// Boundary, empty if the export has no boundaries.
+    pub polygons: Vec<Polygon>,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedMunicipalityPart {
+    pub code: i64,
+    pub name: String,
+    pub municipality: Option<i64>,
+    pub point: Option<(f64, f64)>,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedRuian {
+    pub districts: Vec<ParsedDistrict>,
+    pub municipalities: Vec<ParsedMunicipality>,
+    pub parts: Vec<ParsedMunicipalityPart>,
+}
+
+/// Element of `vf:Data` being read.
+enum Item {
+    District(ParsedDistrict),
+    Municipality(ParsedMunicipality),
+    Part(ParsedMunicipalityPart),
+}
+
+/// Parses districts (okresy), municipalities (obce) and their parts (části obcí) of a RÚIAN
+/// exchange format (VFR) file, other elements are skipped.
+///
+/// Namespace prefixes are ignored, coordinates are converted to WGS 84.
+pub fn parse_ruian<R: BufRead>(mut reader: Reader<R>) -> anyhow::Result<ParsedRuian> {
+    let mut path: Vec<String> = Vec::with_capacity(32);
+    let mut buffer = Vec::new();
+    let mut ruian = ParsedRuian::default();
+    let mut item = None;
+
+    loop {
+        match reader.read_event_into(&mut buffer) {
+            Ok(Event::Start(ref e)) => {
+                path.push(String::from_utf8(Vec::from(e.local_name().as_ref()))?);
+                if path.len() == 4 && path[..2] == ["VymennyFormat", "Data"] {
+                    item = match path[3].as_str() {
+                        "Okres" => Some(Item::District(ParsedDistrict::default())),
+                        "Obec" => Some(Item::Municipality(ParsedMunicipality::default())),
+                        "CastObce" => Some(Item::Part(ParsedMunicipalityPart::default())),
+                        _ => None,
+                    };
+                } else if let Some(Item::Municipality(municipality)) = &mut item {
+                    let boundary = path.len() > 6 && is_boundary(&path[4..6]);
+                    match path.last().map(String::as_str) {
+                        Some("Polygon") if boundary => municipality.polygons.push(Vec::new()),
+                        Some("exterior" | "interior") if boundary => {
+                            if let Some(polygon) = municipality.polygons.last_mut() {
+                                polygon.push(Vec::new());
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Ok(Event::End(_)) => {
+                if path.len() == 4 {
+                    match item.take() {
+                        Some(Item::District(district)) => ruian.districts.push(district),
+                        Some(Item::Municipality(municipality)) => {
+                            ruian.municipalities.push(municipality)
+                        }
+                        Some(Item::Part(part)) => ruian.parts.push(part),
+                        None => {}
+                    }
+                }
+                path.pop();
+            }
+            Ok(Event::Text(e)) => {
+                let Some(item) = &mut item else {
+                    buffer.clear();
+                    continue;
+                };
+                let field = &path[4..];
+                let text = e.unescape()?;
+                let text = text.trim();
+                match item {
+                    Item::District(district) => match field {
+                        [name] if name == "Kod" => district.code = i64::from_str(text)?,
+                        [name] if name == "Nazev" => district.name = text.to_string(),
+                        _ => {}
+                    },
+                    Item::Municipality(municipality) => match field {
+                        [name] if name == "Kod" => municipality.code = i64::from_str(text)?,
+                        [name] if name == "Nazev" => municipality.name = text.to_string(),
+                        [parent, name] if parent == "Okres" && name == "Kod" => {
+                            municipality.district = Some(i64::from_str(text)?)
+                        }
+                        [geometry, point, .., pos]
+                            if geometry == "Geometrie"
+                                && point == "DefinicniBod"
+                                && pos == "pos"
+                                && municipality.point.is_none() =>
+                        {
+                            municipality.point = Some(position(text)?);
+                        }
+                        [_, _, .., pos]
+                            if is_boundary(&field[..2]) && (pos == "posList" || pos == "pos") =>
+                        {
+                            if let Some(ring) = municipality
+                                .polygons
+                                .last_mut()
+                                .and_then(|polygon| polygon.last_mut())
+                            {
+                                ring.extend(positions(text)?);
+                            }
+                        }
+                        _ => {}
+                    },
+                    Item::Part(part) => match field {
+                        [name] if name == "Kod" => part.code = i64::from_str(text)?,
+                        [name] if name == "Nazev" => part.name = text.to_string(),
+                        [parent, name] if parent == "Obec" && name == "Kod" => {
+                            part.municipality = Some(i64::from_str(text)?)
+                        }
+                        [geometry, point, .., pos]
+                            if geometry == "Geometrie"
+                                && point == "DefinicniBod"
+                                && pos == "pos"
+                                && part.point.is_none() =>
+                        {
+                            part.point = Some(position(text)?);
+                        }
+                        _ => {}
+                    },
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(e.into()),
+            _ => (),
+        }
+        buffer.clear();
+    }
+    Ok(ruian)
+}
+
+/// Whether the path relative to an item leads into its boundary, original or generalized.
+fn is_boundary(path: &[String]) -> bool {
+    path.len() == 2
+        && path[0] == "Geometrie"
+        && (path[1] == "OriginalniHranice" || path[1] == "GeneralizovaneHranice")
+}
+
+fn position(text: &str) -> anyhow::Result<(f64, f64)> {
+    positions(text)?
+        .into_iter()
+        .next()
+        .ok_or(anyhow!("empty position"))
+}
+
+/// Converts `east north` pairs of S-JTSK to `(lat, lon)`.
+fn positions(text: &str) -> anyhow::Result<Vec<(f64, f64)>> {
+    let values = text
+        .split_whitespace()
+        .map(f64::from_str)
+        .collect::<Result<Vec<_>, _>>()?;
+    if values.len() % 2 != 0 {
+        return Err(anyhow!("odd number of coordinates: {}", text));
+    }
+    Ok(values
+        .chunks(2)
+        .map(|pair| jtsk::to_wgs84(pair[0], pair[1]))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VFR: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<vf:VymennyFormat xmlns:vf="urn:cz:isvs:ruian:schemas:VymennyFormatTypy:v1" xmlns:gml="http://www.opengis.net/gml/3.2" xmlns:oki="urn:cz:isvs:ruian:schemas:OkrIntTypy:v1" xmlns:obi="urn:cz:isvs:ruian:schemas:ObecIntTypy:v1" xmlns:coi="urn:cz:isvs:ruian:schemas:CobceIntTypy:v1">
+<vf:Hlavicka><vf:VerzeVFR>3</vf:VerzeVFR></vf:Hlavicka>
+<vf:Data>
+<vf:Okresy><vf:Okres gml:id="OK.3100"><oki:Kod>3100</oki:Kod><oki:Nazev>Hlavní město Praha</oki:Nazev></vf:Okres></vf:Okresy>
+<vf:Obce>
+<vf:Obec gml:id="OB.554782"><obi:Kod>554782</obi:Kod><obi:Nazev>Praha</obi:Nazev><obi:Okres><oki:Kod>3100</oki:Kod></obi:Okres>
+<obi:Geometrie>
+<obi:DefinicniBod><gml:Point gml:id="DOB.554782"><gml:pos>-742851.25 -1043008.86</gml:pos></gml:Point></obi:DefinicniBod>
+<obi:OriginalniHranice><gml:MultiSurface gml:id="x"><gml:surfaceMember><gml:Polygon gml:id="y">
+<gml:exterior><gml:LinearRing><gml:posList>-760000 -1060000 -720000 -1060000 -720000 -1030000 -760000 -1060000</gml:posList></gml:LinearRing></gml:exterior>
+<gml:interior><gml:LinearRing><gml:posList>-745000 -1045000 -744000 -1045000 -744000 -1044000 -745000 -1045000</gml:posList></gml:LinearRing></gml:interior>
+</gml:Polygon></gml:surfaceMember></gml:MultiSurface></obi:OriginalniHranice>
+</obi:Geometrie>
+</vf:Obec>
+<vf:Obec gml:id="OB.1"><obi:Kod>1</obi:Kod><obi:Nazev>Bez bodu</obi:Nazev></vf:Obec>
+</vf:Obce>
+<vf:CastiObci>
+<vf:CastObce gml:id="CO.490067"><coi:Kod>490067</coi:Kod><coi:Nazev>Karlín</coi:Nazev><coi:Obec><obi:Kod>554782</obi:Kod></coi:Obec>
+<coi:Geometrie><coi:DefinicniBod><gml:Point gml:id="q"><gml:pos>-740500.00 -1042500.00</gml:pos></gml:Point></coi:DefinicniBod></coi:Geometrie></vf:CastObce>
+</vf:CastiObci>
+</vf:Data>
+</vf:VymennyFormat>"#;
+
+    #[test]
+    fn districts_municipalities_and_parts_are_parsed() {
+        let ruian = parse_ruian(Reader::from_str(VFR)).unwrap();
+
+        assert_eq!(ruian.districts.len(), 1);
+        assert_eq!(ruian.districts[0].code, 3100);
+        assert_eq!(ruian.districts[0].name, "Hlavní město Praha");
+
+        assert_eq!(ruian.municipalities.len(), 2);
+        let praha = &ruian.municipalities[0];
+        assert_eq!((praha.code, praha.name.as_str()), (554782, "Praha"));
+        assert_eq!(praha.district, Some(3100));
+        let (lat, lon) = praha.point.unwrap();
+        assert!((lat - 50.087_451).abs() < 1e-5 && (lon - 14.420_671).abs() < 1e-5);
+        let without_point = &ruian.municipalities[1];
+        assert_eq!(without_point.name, "Bez bodu");
+        assert!(without_point.point.is_none() && without_point.polygons.is_empty());
+
+        assert_eq!(ruian.parts.len(), 1);
+        let karlin = &ruian.parts[0];
+        assert_eq!((karlin.code, karlin.name.as_str()), (490067, "Karlín"));
+        assert_eq!(karlin.municipality, Some(554782));
+        assert!(karlin.point.is_some());
+    }
+
+    #[test]
+    fn boundary_rings_are_converted_in_order() {
+        let ruian = parse_ruian(Reader::from_str(VFR)).unwrap();
+        let polygons = &ruian.municipalities[0].polygons;
+
+        assert_eq!(polygons.len(), 1);
+        let rings: Vec<usize> = polygons[0].iter().map(Vec::len).collect();
+        assert_eq!(rings, [4, 4]);
+        let exterior = &polygons[0][0];
+        assert_eq!(exterior[0], jtsk::to_wgs84(-760000.0, -1060000.0));
+        assert_eq!(exterior[1], jtsk::to_wgs84(-720000.0, -1060000.0));
+        assert_eq!(exterior.first(), exterior.last());
+    }
+
+    #[test]
+    fn odd_coordinates_are_rejected() {
+        assert!(positions("-742851.25 -1043008.86 -740500.00").is_err());
+        assert!(position("").is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::App;
+use inv_zastavky_core::model::StopId;
+use inv_zastavky_core::model::bbox::BBox;
+use inv_zastavky_core::model::boundary::BoundaryChecker;
+use inv_zastavky_core::model::integrity;
+use inv_zastavky_core::model::station::{MergePosition, Station};
+use sqlx::{Pool, Sqlite};
+
+impl App {
+    pub async fn merge_stations(
+        db_pool: &Pool<Sqlite>,
+        username: &str,
+        keep: StopId,
+        remove: StopId,
+        position: MergePosition,
+    ) -> anyhow::Result<()> {
+        let author = App::author(db_pool, username).await?;
+        let station = Station::merge(
+            &mut *db_pool.acquire().await?,
+            &author,
+            keep,
+            remove,
+            position,
+        )
+        .await?;
+        println!(
+            "merged station {} into {}: {} at {}, {}",
+            remove,
+            station.stop_id(),
+            station.names().join(" | "),
+            station.lat(),
+            station.lon()
+        );
+        Ok(())
+    }
+
+    pub async fn repair(db_pool: &Pool<Sqlite>, dry_run: bool) -> anyhow::Result<()> {
+        let repair = integrity::repair(&mut *db_pool.acquire().await?, dry_run).await?;
+        if repair.is_empty() {
+            println!("no dangling references found");
+            return Ok(());
+        }
+        if dry_run {
+            println!("dry run, nothing was changed");
+        }
+        println!(
+            "stations named after their chain positions: {}",
+            repair.named_stations()
+        );
+        println!(
+            "positions of stations without name removed: {}",
+            repair.removed_positions()
+        );
+        println!(
+            "chain positions of missing stations unassigned: {}",
+            repair.unassigned_positions()
+        );
+        println!(
+            "names of stations without position removed: {}",
+            repair.removed_names()
+        );
+        Ok(())
+    }
+
+    pub async fn qa(db_pool: &Pool<Sqlite>) -> anyhow::Result<()> {
+        let mut db = db_pool.acquire().await?;
+        let mut stations =
+            Station::get_all_by_bbox(&mut db, &BBox::new([-90.0, 90.0], [-180.0, 180.0])).await?;
+        stations.sort_by_key(|station| station.stop_id());
+        let mut checker = BoundaryChecker::default();
+        let mut misplaced = 0;
+        let mut warnings = 0;
+        for station in &stations {
+            let station_warnings = checker.check(&mut db, station).await?;
+            if !station_warnings.is_empty() {
+                misplaced += 1;
+            }
+            for warning in &station_warnings {
+                let found = if warning.found().is_empty() {
+                    String::from("no municipality")
+                } else {
+                    warning.found().join(", ")
+                };
+                println!(
+                    "station {} at {}, {}: {} is not in {}, found {}",
+                    warning.stop_id(),
+                    station.lat(),
+                    station.lon(),
+                    warning.name(),
+                    warning.municipality(),
+                    found
+                );
+            }
+            warnings += station_warnings.len();
+        }
+        println!("checked stations: {}", stations.len());
+        println!("stations outside of their municipality: {}", misplaced);
+        println!("names outside of their municipality: {}", warnings);
+        Ok(())
+    }
+}
@@ -14,7 +14,7 @@ SELECT count(*) FROM hl_stations;
         )
         .fetch_all(db_pool)
         .await?;
-        let station_names: u32 = rows.get(0).ok_or(anyhow!(""))?.try_get(0)?;
+        let station_names: u32 = rows.first().ok_or(anyhow!(""))?.try_get(0)?;
         let station_poses: u32 = rows.get(1).ok_or(anyhow!(""))?.try_get(0)?;
         let stations_done: u32 = rows.get(2).ok_or(anyhow!(""))?.try_get(0)?;
         println!("station names: {}", station_names);
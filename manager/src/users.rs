@@ -0,0 +1,81 @@
+use crate::App;
+use anyhow::anyhow;
+use inv_zastavky_core::model::change::ChangeAuthor;
+use inv_zastavky_core::model::user::User;
+use sqlx::{Pool, Sqlite};
+use std::io::BufRead;
+
+/// Reads password from `INV_ZASTAVKY_PASSWORD` or the first line of standard input.
+fn read_password() -> anyhow::Result<String> {
+    if let Ok(password) = std::env::var("INV_ZASTAVKY_PASSWORD") {
+        return Ok(password);
+    }
+    eprintln!("password:");
+    let mut password = String::new();
+    std::io::stdin().lock().read_line(&mut password)?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+impl App {
+    /// Author of changes made from the command line by an existing user.
+    pub async fn author(db_pool: &Pool<Sqlite>, username: &str) -> anyhow::Result<ChangeAuthor> {
+        let user = User::get_by_name(&mut db_pool.acquire().await?, username)
+            .await?
+            .ok_or(anyhow!("no user {}", username))?;
+        Ok(ChangeAuthor::new(user.user_id(), None))
+    }
+
+    pub async fn add_user(db_pool: &Pool<Sqlite>, username: String) -> anyhow::Result<()> {
+        let password = read_password()?;
+        let user = User::create(&mut db_pool.acquire().await?, &username, &password).await?;
+        println!(
+            "created user {} with id {}",
+            user.username(),
+            user.user_id()
+        );
+        Ok(())
+    }
+
+    pub async fn set_user_password(db_pool: &Pool<Sqlite>, username: String) -> anyhow::Result<()> {
+        let password = read_password()?;
+        User::set_password(&mut db_pool.acquire().await?, &username, &password).await?;
+        println!("password of {} changed", username);
+        Ok(())
+    }
+
+    pub async fn create_user_token(
+        db_pool: &Pool<Sqlite>,
+        username: String,
+        name: String,
+    ) -> anyhow::Result<()> {
+        let mut db = db_pool.acquire().await?;
+        let user = User::get_by_name(&mut db, &username)
+            .await?
+            .ok_or(anyhow!("no user {}", username))?;
+        println!(
+            "{}",
+            User::create_token(&mut db, user.user_id(), &name).await?
+        );
+        Ok(())
+    }
+
+    pub async fn revoke_user_tokens(
+        db_pool: &Pool<Sqlite>,
+        username: String,
+    ) -> anyhow::Result<()> {
+        let mut db = db_pool.acquire().await?;
+        let user = User::get_by_name(&mut db, &username)
+            .await?
+            .ok_or(anyhow!("no user {}", username))?;
+        let count = User::revoke_tokens(&mut db, user.user_id()).await?;
+        println!("revoked {} tokens of {}", count, username);
+        Ok(())
+    }
+
+    pub async fn list_users(db_pool: &Pool<Sqlite>) -> anyhow::Result<()> {
+        for user in User::list(&mut db_pool.acquire().await?).await? {
+            println!("{}\t{}", user.user_id(), user.username());
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,10 @@
 use crate::database::MainDB;
+use crate::error::ApiError;
 use inv_zastavky_core::model::base_city::BaseCity;
 use inv_zastavky_core::model::base_station::BaseStation;
 use inv_zastavky_core::model::bbox::BBox;
+use inv_zastavky_core::model::cluster::Clustered;
+use rocket::Either;
 use rocket::serde::json::Json;
 use rocket::{get, FromForm};
 use rocket_db_pools::Connection;
@@ -14,32 +17,39 @@ pub struct BBoxParams {
     lon_to: f64,
 }
 
-impl Into<BBox> for BBoxParams {
-    fn into(self) -> BBox {
-        BBox::new([self.lat_from, self.lat_to], [self.lon_from, self.lon_to])
+impl From<BBoxParams> for BBox {
+    fn from(params: BBoxParams) -> Self {
+        BBox::new(
+            [params.lat_from, params.lat_to],
+            [params.lon_from, params.lon_to],
+        )
     }
 }
 
-#[get("/sl_base_stations?<bbox..>")]
+/// Base stations in the bbox, with `zoom` they are clustered when there are too many of them.
+#[get("/sl_base_stations?<zoom>&<bbox..>")]
 pub async fn get_base_stations_by_bbox(
     mut db: Connection<MainDB>,
     bbox: BBoxParams,
-) -> Result<Json<Vec<BaseStation>>, String> {
-    let stations = BaseStation::get_by_bbox(&mut db, &bbox.into()).await;
-    match stations {
-        Ok(stations) => Ok(Json(stations)),
-        Err(err) => Err(format!("{}", err)),
-    }
+    zoom: Option<u8>,
+) -> Result<Either<Json<Vec<BaseStation>>, Json<Clustered<BaseStation>>>, ApiError> {
+    let bbox = bbox.into();
+    Ok(match zoom {
+        None => Either::Left(Json(BaseStation::get_by_bbox(&mut db, &bbox).await?)),
+        Some(zoom) => Either::Right(Json(
+            BaseStation::get_clustered(&mut db, &bbox, zoom).await?,
+        )),
+    })
 }
 
 #[get("/sl_base_city?<query>")]
 pub async fn search_base_city(
     mut db: Connection<MainDB>,
     query: String,
-) -> Result<Json<Vec<BaseCity>>, String> {
+) -> Result<Json<Vec<BaseCity>>, ApiError> {
     let cities = BaseCity::search(&mut db, &query).await;
     match cities {
         Ok(cities) => Ok(Json(cities)),
-        Err(err) => Err(format!("{}", err)),
+        Err(err) => Err(err.into()),
     }
 }
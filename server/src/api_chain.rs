@@ -1,5 +1,7 @@
+use crate::auth::Editor;
 use crate::database;
 use crate::database::MainDB;
+use crate::error::ApiError;
 use inv_zastavky_core::model::StopId;
 use inv_zastavky_core::model::chain_station::ChainStation;
 use inv_zastavky_core::model::station::Station;
@@ -44,13 +46,13 @@ impl ListSlChainsParams {
 pub async fn list_sl_chains(
     mut db: Connection<MainDB>,
     params: ListSlChainsParams,
-) -> Result<Json<Vec<ChainStation>>, String> {
+) -> Result<Json<Vec<ChainStation>>, ApiError> {
     let limit = params.limit();
     let offset = params.page();
     let chains = ChainStation::get_chains(&mut db, limit, offset).await;
     match chains {
         Ok(chains) => Ok(Json(chains)),
-        Err(err) => Err(format!("{}", err)),
+        Err(err) => Err(err.into()),
     }
 }
 
@@ -58,21 +60,24 @@ pub async fn list_sl_chains(
 pub async fn sl_chain_by_chain_hash(
     mut db: Connection<MainDB>,
     chain_hash: String,
-) -> Result<Json<Vec<ChainStation>>, String> {
+) -> Result<Json<Vec<ChainStation>>, ApiError> {
     let chains = ChainStation::get_by_chain_hash(&mut db, &chain_hash).await;
     match chains {
         Ok(chains) => Ok(Json(chains)),
-        Err(err) => Err(format!("{}", err)),
+        Err(err) => Err(err.into()),
     }
 }
 
+/// Deprecated alias of `PUT /api/v2/chains/<chain_hash>/positions/<pos>`.
 #[get("/locate_by_id?<params..>")]
 pub async fn locate_by_id(
+    editor: Editor,
     mut db: Connection<MainDB>,
     params: LocateByIdParams,
-) -> Result<Json<Station>, String> {
+) -> Result<Json<Station>, ApiError> {
     match database::locate_chain_by_id(
         &mut db,
+        editor.author(),
         &params.chain_hash,
         &params.name,
         params.pos,
@@ -80,19 +85,21 @@ pub async fn locate_by_id(
     )
     .await
     {
-        Ok(Some(station)) => Ok(Json(station)),
-        Ok(None) => Err(String::from("no station found")),
-        Err(err) => Err(format!("{}", err)),
+        Ok(station) => Ok(Json(station)),
+        Err(err) => Err(err.into()),
     }
 }
 
+/// Deprecated alias of `PUT /api/v2/chains/<chain_hash>/positions/<pos>`.
 #[get("/locate_by_loc?<params..>")]
 pub async fn locate_by_loc(
+    editor: Editor,
     mut db: Connection<MainDB>,
     params: LocateByLocParams,
-) -> Result<Json<Station>, String> {
+) -> Result<Json<Station>, ApiError> {
     match database::locate_chain_by_loc(
         &mut db,
+        editor.author(),
         &params.chain_hash,
         &params.name,
         params.pos,
@@ -101,8 +108,7 @@ pub async fn locate_by_loc(
     )
     .await
     {
-        Ok(Some(station)) => Ok(Json(station)),
-        Ok(None) => Err(String::from("failed to create station")),
-        Err(err) => Err(format!("{}", err)),
+        Ok(station) => Ok(Json(station)),
+        Err(err) => Err(err.into()),
     }
 }
@@ -1,5 +1,6 @@
 use crate::config::ServerConfig;
 use crate::database::MainDB;
+use crate::error::ApiError;
 use inv_zastavky_core::model::stats::Stats;
 use rocket::serde::json::Json;
 use rocket::{State, get};
@@ -7,11 +8,11 @@ use rocket_db_pools::Connection;
 use std::collections::HashMap;
 
 #[get("/other_stats")]
-pub async fn other_stats(mut db: Connection<MainDB>) -> Result<Json<Stats>, String> {
+pub async fn other_stats(mut db: Connection<MainDB>) -> Result<Json<Stats>, ApiError> {
     let cities = Stats::get(&mut db).await;
     match cities {
         Ok(stats) => Ok(Json(stats)),
-        Err(err) => Err(format!("{}", err)),
+        Err(err) => Err(err.into()),
     }
 }
 
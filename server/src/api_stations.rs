@@ -1,9 +1,15 @@
+use crate::auth::Editor;
+use crate::config::ServerConfig;
 use crate::database::MainDB;
+use crate::error::ApiError;
+use inv_zastavky_core::model::StopId;
 use inv_zastavky_core::model::bbox::BBox;
+use inv_zastavky_core::model::boundary::CheckedStation;
+use inv_zastavky_core::model::cluster::Clustered;
 use inv_zastavky_core::model::station::Station;
-use inv_zastavky_core::model::StopId;
+use rocket::Either;
 use rocket::serde::json::Json;
-use rocket::{get, FromForm};
+use rocket::{FromForm, State, get};
 use rocket_db_pools::Connection;
 
 #[derive(FromForm)]
@@ -45,89 +51,118 @@ pub struct AddStationNameParams {
     name: String,
 }
 
-impl Into<BBox> for BBoxParams {
-    fn into(self) -> BBox {
-        BBox::new([self.lat_from, self.lat_to], [self.lon_from, self.lon_to])
+impl From<BBoxParams> for BBox {
+    fn from(params: BBoxParams) -> Self {
+        BBox::new(
+            [params.lat_from, params.lat_to],
+            [params.lon_from, params.lon_to],
+        )
     }
 }
 
-#[get("/el_stations_bbox?<bbox..>")]
+/// Stations in the bbox, with `zoom` they are clustered when there are too many of them.
+#[get("/el_stations_bbox?<zoom>&<bbox..>")]
 pub async fn get_stations_by_bbox(
     mut db: Connection<MainDB>,
     bbox: BBoxParams,
-) -> Result<Json<Vec<Station>>, String> {
-    let stations = Station::get_by_bbox(&mut db, &bbox.into()).await;
-    match stations {
-        Ok(stations) => Ok(Json(stations)),
-        Err(err) => Err(format!("{}", err)),
-    }
+    zoom: Option<u8>,
+) -> Result<Either<Json<Vec<Station>>, Json<Clustered<Station>>>, ApiError> {
+    let bbox = bbox.into();
+    Ok(match zoom {
+        None => Either::Left(Json(Station::get_by_bbox(&mut db, &bbox).await?)),
+        Some(zoom) => Either::Right(Json(Station::get_clustered(&mut db, &bbox, zoom).await?)),
+    })
 }
 
+/// Deprecated alias of `POST /api/v2/stations`.
 #[get("/el_add_station?<params..>")]
 pub async fn add_station(
+    editor: Editor,
     mut db: Connection<MainDB>,
     params: AddStationParams,
-) -> Result<Json<Station>, String> {
+) -> Result<Json<CheckedStation>, ApiError> {
     let station = Station::create_station(
         &mut db,
-        rand::random::<i32>() as StopId,
+        editor.author(),
         params.lat,
         params.lon,
         &params.name,
     )
     .await;
     match station {
-        Ok(Some(station)) => Ok(Json(station)),
-        Ok(None) => Err(String::from("no station added")),
-        Err(err) => Err(format!("{}", err)),
+        Ok(station) => Ok(Json(CheckedStation::check(&mut db, station).await?)),
+        Err(err) => Err(err.into()),
     }
 }
 
+/// Deprecated alias of `DELETE /api/v2/stations/<stop_id>`.
 #[get("/el_rm_station?<params..>")]
-pub async fn rm_station(mut db: Connection<MainDB>, params: RmStationParams) -> Result<(), String> {
-    let station = Station::remove_station(&mut db, params.stop_id).await;
+pub async fn rm_station(
+    editor: Editor,
+    mut db: Connection<MainDB>,
+    state: &State<ServerConfig>,
+    params: RmStationParams,
+) -> Result<(), ApiError> {
+    let station = Station::remove_station(
+        &mut db,
+        editor.author(),
+        params.stop_id,
+        state.delete_policy,
+    )
+    .await;
     match station {
         Ok(_) => Ok(()),
-        Err(err) => Err(format!("{}", err)),
+        Err(err) => Err(err.into()),
     }
 }
 
+/// Deprecated alias of `PATCH /api/v2/stations/<stop_id>`.
 #[get("/el_move_station?<params..>")]
 pub async fn move_station(
+    editor: Editor,
     mut db: Connection<MainDB>,
     params: MoveStationParams,
-) -> Result<Json<Station>, String> {
-    let station = Station::move_station(&mut db, params.stop_id, params.lat, params.lon).await;
+) -> Result<Json<CheckedStation>, ApiError> {
+    let station = Station::move_station(
+        &mut db,
+        editor.author(),
+        params.stop_id,
+        params.lat,
+        params.lon,
+    )
+    .await;
     match station {
-        Ok(Some(station)) => Ok(Json(station)),
-        Ok(None) => Err(String::from("no station to move")),
-        Err(err) => Err(format!("{}", err)),
+        Ok(station) => Ok(Json(CheckedStation::check(&mut db, station).await?)),
+        Err(err) => Err(err.into()),
     }
 }
 
+/// Deprecated alias of `POST /api/v2/stations/<stop_id>/names`.
 #[get("/el_add_station_name?<params..>")]
 pub async fn add_station_name(
+    editor: Editor,
     mut db: Connection<MainDB>,
     params: AddStationNameParams,
-) -> Result<Json<Station>, String> {
-    let station = Station::add_name(&mut db, params.stop_id, &params.name).await;
+) -> Result<Json<Station>, ApiError> {
+    let station = Station::add_name(&mut db, editor.author(), params.stop_id, &params.name).await;
     match station {
-        Ok(Some(station)) => Ok(Json(station)),
-        Ok(None) => Err(String::from("no station updated")),
-        Err(err) => Err(format!("{}", err)),
+        Ok(station) => Ok(Json(station)),
+        Err(err) => Err(err.into()),
     }
 }
 
+/// Deprecated alias of `DELETE /api/v2/stations/<stop_id>/names/<name>`.
 #[get("/el_rm_station_name?<params..>")]
 pub async fn rm_station_name(
+    editor: Editor,
     mut db: Connection<MainDB>,
     params: RmStationNameParams,
-) -> Result<Json<Station>, String> {
-    let station = Station::remove_station_name(&mut db, params.stop_id, &params.name).await;
+) -> Result<Json<Station>, ApiError> {
+    let station =
+        Station::remove_station_name(&mut db, editor.author(), params.stop_id, &params.name).await;
     match station {
-        Ok(Some(station)) => Ok(Json(station)),
-        Ok(None) => Err(String::from("no station updated")),
-        Err(err) => Err(format!("{}", err)),
+        Ok(station) => Ok(Json(station)),
+        Err(err) => Err(err.into()),
     }
 }
 
@@ -135,10 +170,10 @@ pub async fn rm_station_name(
 pub async fn search_stations(
     mut db: Connection<MainDB>,
     query: String,
-) -> Result<Json<Vec<Station>>, String> {
+) -> Result<Json<Vec<Station>>, ApiError> {
     let cities = Station::search(&mut db, &query).await;
     match cities {
         Ok(cities) => Ok(Json(cities)),
-        Err(err) => Err(format!("{}", err)),
+        Err(err) => Err(err.into()),
     }
 }
@@ -1,5 +1,6 @@
 use crate::config::ServerConfig;
 use crate::database::MainDB;
+use crate::error::ApiError;
 use inv_zastavky_core::suggest::chain::{
     ChainCitySuggestion, ChainStationsSuggestion, chain_options, path_options,
 };
@@ -7,27 +8,38 @@ use rocket::serde::json::Json;
 use rocket::{State, get};
 use rocket_db_pools::Connection;
 
-#[get("/suggest_stations?<chain_hash>")]
+#[get("/suggest_stations?<chain_hash>&<limit>")]
 pub async fn suggest_stations(
     mut db: Connection<MainDB>,
+    state: &State<ServerConfig>,
     chain_hash: String,
-) -> Result<Json<Vec<ChainStationsSuggestion>>, String> {
-    let suggestion = chain_options(&mut db, &chain_hash).await;
+    limit: Option<usize>,
+) -> Result<Json<Vec<ChainStationsSuggestion>>, ApiError> {
+    let mut params = state.chain_search.clone();
+    if let Some(limit) = limit {
+        params.top_k = limit.min(params.top_k);
+    }
+    let suggestion = chain_options(&mut db, &chain_hash, &params).await;
     match suggestion {
         Ok(cities) => Ok(Json(cities)),
-        Err(err) => Err(format!("{}", err)),
+        Err(err) => Err(err.into()),
     }
 }
 
-#[get("/suggest_cities?<chain_hash>")]
+#[get("/suggest_cities?<chain_hash>&<limit>")]
 pub async fn suggest_cities(
     mut db: Connection<MainDB>,
     state: &State<ServerConfig>,
     chain_hash: String,
-) -> Result<Json<Vec<ChainCitySuggestion>>, String> {
-    let suggestion = path_options(&mut db, &chain_hash, &state.city_remap).await;
+    limit: Option<usize>,
+) -> Result<Json<Vec<ChainCitySuggestion>>, ApiError> {
+    let mut params = state.chain_search.clone();
+    if let Some(limit) = limit {
+        params.top_k = limit.min(params.top_k);
+    }
+    let suggestion = path_options(&mut db, &chain_hash, &state.city_remap, &params).await;
     match suggestion {
         Ok(cities) => Ok(Json(cities)),
-        Err(err) => Err(format!("{}", err)),
+        Err(err) => Err(err.into()),
     }
 }
@@ -0,0 +1,199 @@
+use crate::database::MainDB;
+use crate::error::ApiError;
+use crate::mvt::{self, Layer, TileId, Value};
+use inv_zastavky_core::error::Error;
+use inv_zastavky_core::model::base_city::BaseCity;
+use inv_zastavky_core::model::base_station::BaseStation;
+use inv_zastavky_core::model::bbox::BBox;
+use inv_zastavky_core::model::cluster::{Cluster, Clustered};
+use inv_zastavky_core::model::station::Station;
+use rocket::http::ContentType;
+use rocket::request::FromParam;
+use rocket::{State, get};
+use rocket_db_pools::Connection;
+use rocket_db_pools::sqlx;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Number of cached tiles, the cache is emptied when it is full.
+const CACHE_CAPACITY: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TileLayer {
+    Stations,
+    BaseStations,
+    Cities,
+}
+
+impl<'a> FromParam<'a> for TileLayer {
+    type Error = &'a str;
+
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        match param {
+            "stations" => Ok(TileLayer::Stations),
+            "base_stations" => Ok(TileLayer::BaseStations),
+            "cities" => Ok(TileLayer::Cities),
+            _ => Err(param),
+        }
+    }
+}
+
+/// Last path segment of a tile, `<y>.mvt`.
+pub struct TileRow(u32);
+
+impl<'a> FromParam<'a> for TileRow {
+    type Error = &'a str;
+
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        param
+            .strip_suffix(".mvt")
+            .and_then(|y| y.parse().ok())
+            .map(TileRow)
+            .ok_or(param)
+    }
+}
+
+/// Encoded tile and the version of data it was encoded from.
+type CachedTile = (i64, Arc<[u8]>);
+
+/// Encoded tiles with the data version they were encoded from.
+///
+/// Stations are versioned by a counter of changes of their positions and names, so tiles are
+/// re-encoded after any edit, also one made by `manager`. Base stations and cities have their own
+/// counters, which are increased by every `manager import` of them.
+#[derive(Default)]
+pub struct TileCache {
+    tiles: Mutex<HashMap<(TileLayer, TileId), CachedTile>>,
+}
+
+impl TileCache {
+    fn get(&self, layer: TileLayer, tile: TileId, version: i64) -> Option<Arc<[u8]>> {
+        let tiles = self.tiles.lock().unwrap_or_else(|err| err.into_inner());
+        tiles
+            .get(&(layer, tile))
+            .filter(|(cached_version, _)| *cached_version == version)
+            .map(|(_, data)| data.clone())
+    }
+
+    fn insert(&self, layer: TileLayer, tile: TileId, version: i64, data: Arc<[u8]>) {
+        let mut tiles = self.tiles.lock().unwrap_or_else(|err| err.into_inner());
+        if tiles.len() >= CACHE_CAPACITY {
+            tiles.clear();
+        }
+        tiles.insert((layer, tile), (version, data));
+    }
+}
+
+async fn data_version(db: &mut Connection<MainDB>, layer: TileLayer) -> Result<i64, sqlx::Error> {
+    match layer {
+        TileLayer::Stations => Station::data_version(db).await,
+        TileLayer::BaseStations => BaseStation::data_version(db).await,
+        TileLayer::Cities => BaseCity::data_version(db).await,
+    }
+}
+
+async fn encode_tile(
+    db: &mut Connection<MainDB>,
+    layer: TileLayer,
+    tile: TileId,
+) -> Result<Vec<u8>, ApiError> {
+    let (lat, lon) = tile.bounds();
+    let bbox = BBox::new(lat, lon);
+    let features = match layer {
+        TileLayer::Stations => {
+            let mut features = Layer::new(tile, "stations");
+            match Station::get_clustered(db, &bbox, tile.zoom()).await? {
+                Clustered::Points(stations) => {
+                    for station in stations {
+                        features.add_point(
+                            u64::try_from(station.stop_id()).ok(),
+                            station.lat(),
+                            station.lon(),
+                            &[
+                                ("stop_id", Value::Int(i64::from(station.stop_id()))),
+                                ("name", Value::String(&station.names()[0])),
+                                ("names", Value::String(&station.names().join("\n"))),
+                            ],
+                        );
+                    }
+                }
+                Clustered::Clusters(clusters) => add_clusters(&mut features, &clusters),
+            }
+            features
+        }
+        TileLayer::BaseStations => {
+            let mut features = Layer::new(tile, "base_stations");
+            match BaseStation::get_clustered(db, &bbox, tile.zoom()).await? {
+                Clustered::Points(stations) => {
+                    for station in stations {
+                        let mut attributes = vec![
+                            ("name", Value::String(station.name())),
+                            ("source", Value::String(station.source().tag())),
+                        ];
+                        if let Some(source_id) = station.source_id() {
+                            attributes.push(("source_id", Value::String(source_id)));
+                        }
+                        features.add_point(None, station.lat(), station.lon(), &attributes);
+                    }
+                }
+                Clustered::Clusters(clusters) => add_clusters(&mut features, &clusters),
+            }
+            features
+        }
+        TileLayer::Cities => {
+            let mut features = Layer::new(tile, "cities");
+            match BaseCity::get_clustered(db, &bbox, tile.zoom()).await? {
+                Clustered::Points(cities) => {
+                    for city in cities {
+                        let mut attributes = vec![("name", Value::String(city.name()))];
+                        if let Some(district) = city.district() {
+                            attributes.push(("district", Value::String(district)));
+                        }
+                        features.add_point(None, city.lat(), city.lon(), &attributes);
+                    }
+                }
+                Clustered::Clusters(clusters) => add_clusters(&mut features, &clusters),
+            }
+            features
+        }
+    };
+    Ok(mvt::encode(&[features]))
+}
+
+/// Clusters are points with the number of clustered features in `count`.
+fn add_clusters(features: &mut Layer, clusters: &[Cluster]) {
+    for cluster in clusters {
+        let count = i64::try_from(cluster.count()).unwrap_or(i64::MAX);
+        features.add_point(
+            None,
+            cluster.lat(),
+            cluster.lon(),
+            &[("count", Value::Int(count))],
+        );
+    }
+}
+
+/// Layer of stations, base stations or cities as a Mapbox Vector Tile.
+///
+/// Stations, base stations and cities are clustered when a tile has more than 500 of them.
+#[get("/<layer>/<z>/<x>/<y>")]
+pub async fn get_tile(
+    mut db: Connection<MainDB>,
+    cache: &State<TileCache>,
+    layer: TileLayer,
+    z: u8,
+    x: u32,
+    y: TileRow,
+) -> Result<(ContentType, Vec<u8>), ApiError> {
+    let tile = TileId::new(z, x, y.0).ok_or_else(|| {
+        Error::not_found("tile_not_found", format!("no tile {}/{}/{}", z, x, y.0))
+    })?;
+    let content_type = ContentType::new("application", "vnd.mapbox-vector-tile");
+    let version = data_version(&mut db, layer).await?;
+    if let Some(data) = cache.get(layer, tile, version) {
+        return Ok((content_type, data.to_vec()));
+    }
+    let data: Arc<[u8]> = encode_tile(&mut db, layer, tile).await?.into();
+    cache.insert(layer, tile, version, data.clone());
+    Ok((content_type, data.to_vec()))
+}
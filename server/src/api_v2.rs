@@ -0,0 +1,323 @@
+use crate::auth::Editor;
+use crate::config::ServerConfig;
+use crate::database;
+use crate::database::MainDB;
+use crate::error::ApiError;
+use inv_zastavky_core::error::Error;
+use inv_zastavky_core::model::StopId;
+use inv_zastavky_core::model::boundary::CheckedStation;
+use inv_zastavky_core::model::chain_station::ChainStation;
+use inv_zastavky_core::model::change::{Change, ChangeId};
+use inv_zastavky_core::model::split::{Split, SplitPart};
+use inv_zastavky_core::model::station::{MergePosition, Station};
+use inv_zastavky_core::search::{self, SearchMatch};
+use rocket::http::Status;
+use rocket::response::status::Created;
+use rocket::serde::Deserialize;
+use rocket::serde::json::Json;
+use rocket::{State, delete, get, options, patch, post, put};
+use rocket_db_pools::Connection;
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct NewStation {
+    lat: f64,
+    lon: f64,
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct StationPatch {
+    lat: f64,
+    lon: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct NewName {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct MergeRequest {
+    remove: StopId,
+    #[serde(default)]
+    position: MergePosition,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SplitRequest {
+    parts: Vec<SplitPart>,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Station assigned to a chain position, either an existing one or a new one at the location.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", untagged)]
+pub enum Assignment {
+    Existing { stop_id: StopId },
+    New { lat: f64, lon: f64 },
+}
+
+#[post("/stations", data = "<station>")]
+pub async fn create_station(
+    editor: Editor,
+    mut db: Connection<MainDB>,
+    station: Json<NewStation>,
+) -> Result<Created<Json<CheckedStation>>, ApiError> {
+    let station = Station::create_station(
+        &mut db,
+        editor.author(),
+        station.lat,
+        station.lon,
+        &station.name,
+    )
+    .await?;
+    let location = format!("/api/v2/stations/{}", station.stop_id());
+    let station = CheckedStation::check(&mut db, station).await?;
+    Ok(Created::new(location).body(Json(station)))
+}
+
+#[get("/stations/<stop_id>")]
+pub async fn get_station(
+    mut db: Connection<MainDB>,
+    stop_id: StopId,
+) -> Result<Json<Station>, ApiError> {
+    Ok(Json(Station::require(&mut db, stop_id).await?))
+}
+
+#[patch("/stations/<stop_id>", data = "<patch>")]
+pub async fn patch_station(
+    editor: Editor,
+    mut db: Connection<MainDB>,
+    stop_id: StopId,
+    patch: Json<StationPatch>,
+) -> Result<Json<CheckedStation>, ApiError> {
+    let station =
+        Station::move_station(&mut db, editor.author(), stop_id, patch.lat, patch.lon).await?;
+    Ok(Json(CheckedStation::check(&mut db, station).await?))
+}
+
+#[delete("/stations/<stop_id>")]
+pub async fn delete_station(
+    editor: Editor,
+    mut db: Connection<MainDB>,
+    state: &State<ServerConfig>,
+    stop_id: StopId,
+) -> Result<Status, ApiError> {
+    Station::remove_station(&mut db, editor.author(), stop_id, state.delete_policy).await?;
+    Ok(Status::NoContent)
+}
+
+/// Merges another station into this one, the other station is removed.
+#[post("/stations/<stop_id>/merge", data = "<merge>")]
+pub async fn merge_station(
+    editor: Editor,
+    mut db: Connection<MainDB>,
+    stop_id: StopId,
+    merge: Json<MergeRequest>,
+) -> Result<Json<Station>, ApiError> {
+    let station = Station::merge(
+        &mut db,
+        editor.author(),
+        stop_id,
+        merge.remove,
+        merge.position,
+    )
+    .await?;
+    Ok(Json(station))
+}
+
+/// Splits new stations off this one, with `dry_run` the result is only previewed.
+#[post("/stations/<stop_id>/split", data = "<split>")]
+pub async fn split_station(
+    editor: Editor,
+    mut db: Connection<MainDB>,
+    stop_id: StopId,
+    split: Json<SplitRequest>,
+) -> Result<Json<Split>, ApiError> {
+    let split = Station::split(
+        &mut db,
+        editor.author(),
+        stop_id,
+        &split.parts,
+        split.dry_run,
+    )
+    .await?;
+    Ok(Json(split))
+}
+
+#[get("/stations/<stop_id>/names")]
+pub async fn get_station_names(
+    mut db: Connection<MainDB>,
+    stop_id: StopId,
+) -> Result<Json<Vec<String>>, ApiError> {
+    let station = Station::require(&mut db, stop_id).await?;
+    Ok(Json(station.names().to_vec()))
+}
+
+#[post("/stations/<stop_id>/names", data = "<name>")]
+pub async fn add_station_name(
+    editor: Editor,
+    mut db: Connection<MainDB>,
+    stop_id: StopId,
+    name: Json<NewName>,
+) -> Result<Json<Station>, ApiError> {
+    Ok(Json(
+        Station::add_name(&mut db, editor.author(), stop_id, &name.name).await?,
+    ))
+}
+
+#[delete("/stations/<stop_id>/names/<name>")]
+pub async fn delete_station_name(
+    editor: Editor,
+    mut db: Connection<MainDB>,
+    stop_id: StopId,
+    name: &str,
+) -> Result<Json<Station>, ApiError> {
+    Ok(Json(
+        Station::remove_station_name(&mut db, editor.author(), stop_id, name).await?,
+    ))
+}
+
+async fn chain_position(
+    db: &mut Connection<MainDB>,
+    chain_hash: &str,
+    pos: i32,
+) -> Result<ChainStation, ApiError> {
+    ChainStation::get_position(db, chain_hash, pos)
+        .await?
+        .ok_or_else(|| {
+            Error::not_found(
+                "position_not_found",
+                format!("chain {} has no position {}", chain_hash, pos),
+            )
+            .into()
+        })
+}
+
+#[get("/chains/<chain_hash>/positions/<pos>")]
+pub async fn get_position(
+    mut db: Connection<MainDB>,
+    chain_hash: &str,
+    pos: i32,
+) -> Result<Json<ChainStation>, ApiError> {
+    Ok(Json(chain_position(&mut db, chain_hash, pos).await?))
+}
+
+/// Assigns a station to the chain position, the name of the position is added to the station.
+#[put("/chains/<chain_hash>/positions/<pos>", data = "<assignment>")]
+pub async fn assign_position(
+    editor: Editor,
+    mut db: Connection<MainDB>,
+    chain_hash: &str,
+    pos: i32,
+    assignment: Json<Assignment>,
+) -> Result<Json<Station>, ApiError> {
+    let position = chain_position(&mut db, chain_hash, pos).await?;
+    let station = match assignment.into_inner() {
+        Assignment::Existing { stop_id } => {
+            database::locate_chain_by_id(
+                &mut db,
+                editor.author(),
+                chain_hash,
+                position.name(),
+                pos,
+                stop_id,
+            )
+            .await
+        }
+        Assignment::New { lat, lon } => {
+            database::locate_chain_by_loc(
+                &mut db,
+                editor.author(),
+                chain_hash,
+                position.name(),
+                pos,
+                lat,
+                lon,
+            )
+            .await
+        }
+    };
+    Ok(Json(station?))
+}
+
+#[delete("/chains/<chain_hash>/positions/<pos>")]
+pub async fn unassign_position(
+    editor: Editor,
+    mut db: Connection<MainDB>,
+    chain_hash: &str,
+    pos: i32,
+) -> Result<Status, ApiError> {
+    chain_position(&mut db, chain_hash, pos).await?;
+    if !database::unassign_chain_position(&mut db, editor.author(), chain_hash, pos).await? {
+        return Err(Error::not_found(
+            "assignment_not_found",
+            format!("position {} of chain {} has no station", pos, chain_hash),
+        )
+        .into());
+    }
+    Ok(Status::NoContent)
+}
+
+#[get("/stations/<stop_id>/history")]
+pub async fn station_history(
+    mut db: Connection<MainDB>,
+    stop_id: StopId,
+) -> Result<Json<Vec<Change>>, ApiError> {
+    Ok(Json(Change::get_by_stop_id(&mut db, stop_id).await?))
+}
+
+#[get("/sessions/<session_id>/changes")]
+pub async fn session_changes(
+    mut db: Connection<MainDB>,
+    session_id: &str,
+) -> Result<Json<Vec<Change>>, ApiError> {
+    Ok(Json(Change::get_by_session(&mut db, session_id).await?))
+}
+
+/// Reverts one change, fails if the station or assignment was changed again since.
+#[post("/changes/<change_id>/revert")]
+pub async fn revert_change(
+    editor: Editor,
+    mut db: Connection<MainDB>,
+    change_id: ChangeId,
+) -> Result<Json<Change>, ApiError> {
+    Ok(Json(
+        Change::revert(&mut db, editor.author(), change_id).await?,
+    ))
+}
+
+/// Reverts all changes of the session which were not reverted yet.
+#[post("/sessions/<session_id>/revert")]
+pub async fn revert_session(
+    editor: Editor,
+    mut db: Connection<MainDB>,
+    session_id: &str,
+) -> Result<Json<Vec<Change>>, ApiError> {
+    Ok(Json(
+        Change::revert_session(&mut db, editor.author(), session_id).await?,
+    ))
+}
+
+/// Searches names of stations, cities and chain stations, names starting with `q` first.
+#[get("/search?<q>&<limit>")]
+pub async fn search_names(
+    mut db: Connection<MainDB>,
+    q: &str,
+    limit: Option<u32>,
+) -> Result<Json<Vec<SearchMatch>>, ApiError> {
+    let limit = limit.unwrap_or(20).min(100);
+    Ok(Json(search::search(&mut db, q, limit).await?))
+}
+
+/// Answers CORS preflight requests.
+#[options("/<_..>")]
+pub fn preflight() -> Status {
+    Status::NoContent
+}
@@ -0,0 +1,175 @@
+use crate::database::MainDB;
+use crate::error::ApiError;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use inv_zastavky_core::error::Error;
+use inv_zastavky_core::model::change::ChangeAuthor;
+use inv_zastavky_core::model::user::{Credentials, User};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::status::Created;
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::task::spawn_blocking;
+use rocket::{Request, error, get, post};
+use rocket_db_pools::{Connection, Database};
+
+/// Authenticated user, required by every route which changes the data.
+///
+/// Accepts only `Authorization: Bearer <token>` with an API token, passwords are exchanged for
+/// tokens at [`create_token`]. Changes are grouped into the session given by `X-Edit-Session`.
+///
+/// The guard returns its database connection to the pool before the route runs, routes should
+/// list it before their own [`Connection`] to not hold two connections at once.
+pub struct Editor {
+    user: User,
+    author: ChangeAuthor,
+}
+
+impl Editor {
+    pub fn author(&self) -> &ChangeAuthor {
+        &self.author
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Editor {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(token) = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "))
+        else {
+            return Outcome::Error((Status::Unauthorized, ()));
+        };
+        let Some(pool) = MainDB::fetch(request.rocket()) else {
+            return Outcome::Error((Status::InternalServerError, ()));
+        };
+        let user = match pool.acquire().await {
+            Ok(mut db) => User::get_by_token(&mut db, token.trim()).await,
+            Err(err) => Err(err),
+        };
+        match user {
+            Ok(Some(user)) => {
+                let session_id = request
+                    .headers()
+                    .get_one("X-Edit-Session")
+                    .map(String::from);
+                let author = ChangeAuthor::new(user.user_id(), session_id);
+                Outcome::Success(Editor { user, author })
+            }
+            Ok(None) => Outcome::Error((Status::Unauthorized, ())),
+            Err(err) => {
+                error!("Failed to authenticate: {}", err);
+                Outcome::Error((Status::InternalServerError, ()))
+            }
+        }
+    }
+}
+
+/// Username and password of HTTP basic authentication, accepted only by [`create_basic_token`].
+pub struct BasicCredentials {
+    username: String,
+    password: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BasicCredentials {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let credentials = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Basic "))
+            .and_then(|credentials| BASE64_STANDARD.decode(credentials.trim()).ok())
+            .and_then(|credentials| String::from_utf8(credentials).ok())
+            .and_then(|credentials| {
+                let (username, password) = credentials.split_once(':')?;
+                Some(BasicCredentials {
+                    username: username.to_string(),
+                    password: password.to_string(),
+                })
+            });
+        match credentials {
+            Some(credentials) => Outcome::Success(credentials),
+            None => Outcome::Error((Status::Unauthorized, ())),
+        }
+    }
+}
+
+/// Checks the password on a blocking thread, PBKDF2 would otherwise stall the async executor.
+async fn authenticate(
+    db: &mut Connection<MainDB>,
+    username: &str,
+    password: String,
+) -> Result<User, ApiError> {
+    let credentials = User::get_credentials(db, username).await?;
+    let user = spawn_blocking(move || match credentials {
+        Some(credentials) => credentials.verify(&password),
+        None => Credentials::reject(&password),
+    })
+    .await
+    .expect("password verification panicked");
+    user.ok_or_else(|| {
+        Error::unauthorized("invalid_credentials", "wrong username or password").into()
+    })
+}
+
+async fn issue_token(
+    mut db: Connection<MainDB>,
+    username: &str,
+    password: String,
+    name: &str,
+) -> Result<Created<Json<NewToken>>, ApiError> {
+    let user = authenticate(&mut db, username, password).await?;
+    let token = User::create_token(&mut db, user.user_id(), name).await?;
+    Ok(Created::new("/api/v2/me").body(Json(NewToken { token, user })))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Login {
+    username: String,
+    password: String,
+    name: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct NewToken {
+    token: String,
+    user: User,
+}
+
+/// Exchanges username and password for a new API token.
+#[post("/tokens", format = "json", data = "<login>")]
+pub async fn create_token(
+    db: Connection<MainDB>,
+    login: Json<Login>,
+) -> Result<Created<Json<NewToken>>, ApiError> {
+    let Login {
+        username,
+        password,
+        name,
+    } = login.into_inner();
+    issue_token(db, &username, password, name.as_deref().unwrap_or("web")).await
+}
+
+/// Exchanges HTTP basic authentication for a new API token, e.g. for scripts.
+#[post("/tokens?<name>", rank = 2)]
+pub async fn create_basic_token(
+    credentials: BasicCredentials,
+    db: Connection<MainDB>,
+    name: Option<&str>,
+) -> Result<Created<Json<NewToken>>, ApiError> {
+    let BasicCredentials { username, password } = credentials;
+    issue_token(db, &username, password, name.unwrap_or("api")).await
+}
+
+#[get("/me")]
+pub fn me(editor: Editor) -> Json<User> {
+    Json(editor.user)
+}
@@ -1,17 +1,25 @@
+use inv_zastavky_core::model::station::DeletePolicy;
+use inv_zastavky_core::suggest::chain::ChainSearchParams;
 use std::collections::HashMap;
 
 pub struct ServerConfig {
     pub city_remap: HashMap<String, String>,
+    pub chain_search: ChainSearchParams,
+    pub delete_policy: DeletePolicy,
 }
 
 impl ServerConfig {
-    pub fn new() -> ServerConfig {
+    pub fn new(chain_search: ChainSearchParams, delete_policy: DeletePolicy) -> ServerConfig {
         let mut map = HashMap::new();
         for line in include_str!("czech-city-remap.txt").lines() {
             if let Some((left, right)) = line.split_once("\t") {
                 map.insert(String::from(left), String::from(right));
             }
         }
-        ServerConfig { city_remap: map }
+        ServerConfig {
+            city_remap: map,
+            chain_search,
+            delete_policy,
+        }
     }
 }
@@ -1,53 +1,112 @@
 use inv_zastavky_core::model::StopId;
-use inv_zastavky_core::model::station::Station;
-use rocket_db_pools::sqlx::pool::PoolConnection;
-use rocket_db_pools::sqlx::{Sqlite, query};
+use inv_zastavky_core::model::chain_station::ChainStation;
+use inv_zastavky_core::model::change::{Change, ChangeAction, ChangeAuthor};
+use inv_zastavky_core::model::station::{Station, StationError};
+use rocket_db_pools::sqlx::{Connection, SqliteConnection, query};
 use rocket_db_pools::{Database, sqlx};
 
 #[derive(Database)]
 #[database("main")]
 pub struct MainDB(sqlx::SqlitePool);
 
-pub async fn locate_chain_by_id(
-    db: &mut PoolConnection<Sqlite>,
+/// Stores assignment of the chain position and logs the change.
+async fn assign(
+    tx: &mut SqliteConnection,
+    author: &ChangeAuthor,
     chain: &str,
     name: &str,
     pos: i32,
     stop_id: StopId,
-) -> Result<Option<Station>, sqlx::Error> {
+) -> Result<(), sqlx::Error> {
+    let before = ChainStation::get_assignment(tx, chain, pos).await?;
     query(
-        "INSERT OR REPLACE INTO hl_stations (chain_hash, station_name, pos, stop_id) VALUES ($1, $2, $3, $4);
-INSERT OR IGNORE INTO el_station_names (stop_id, station_name) VALUES ($4, $2);",
+        "INSERT OR REPLACE INTO hl_stations (chain_hash, station_name, pos, stop_id, edited_by, edited_at) VALUES ($1, $2, $3, $4, $5, CURRENT_TIMESTAMP);",
     )
     .bind(chain)
     .bind(name)
     .bind(pos)
     .bind(stop_id)
-    .execute(&mut **db)
+    .bind(author.user_id())
+    .execute(&mut *tx)
+    .await?;
+    let after = ChainStation::get_assignment(tx, chain, pos).await?;
+    Change::record_assignment(
+        tx,
+        author,
+        ChangeAction::Assign,
+        chain,
+        pos,
+        before.as_ref(),
+        after.as_ref(),
+    )
     .await?;
-    Station::get_by_id(db, stop_id).await
+    Ok(())
 }
 
+/// Assigns existing station to the chain position and adds the name to the station.
+pub async fn locate_chain_by_id(
+    db: &mut SqliteConnection,
+    author: &ChangeAuthor,
+    chain: &str,
+    name: &str,
+    pos: i32,
+    stop_id: StopId,
+) -> Result<Station, StationError> {
+    let mut tx = db.begin().await?;
+    let before = Station::require(&mut tx, stop_id).await?;
+    assign(&mut tx, author, chain, name, pos, stop_id).await?;
+    let station = if before.names().iter().any(|known| known == name) {
+        before
+    } else {
+        Station::add_name(&mut tx, author, stop_id, name).await?
+    };
+    tx.commit().await?;
+    Ok(station)
+}
+
+/// Creates a new station and assigns it to the chain position.
 pub async fn locate_chain_by_loc(
-    db: &mut PoolConnection<Sqlite>,
+    db: &mut SqliteConnection,
+    author: &ChangeAuthor,
     chain: &str,
     name: &str,
     pos: i32,
     lat: f64,
     lon: f64,
-) -> Result<Option<Station>, sqlx::Error> {
-    let station =
-        Station::create_station(db, rand::random::<i32>() as StopId, lat, lon, name).await?;
-    if let Some(station) = &station {
-        query(
-            "INSERT OR REPLACE INTO hl_stations (chain_hash, station_name, pos, stop_id) VALUES ($1, $2, $3, $4);",
-        )
-            .bind(chain)
-            .bind(name)
-            .bind(pos)
-            .bind(station.stop_id())
-            .execute(&mut **db)
-            .await?;
-    }
+) -> Result<Station, StationError> {
+    let mut tx = db.begin().await?;
+    let station = Station::create_station(&mut tx, author, lat, lon, name).await?;
+    assign(&mut tx, author, chain, name, pos, station.stop_id()).await?;
+    tx.commit().await?;
     Ok(station)
 }
+
+/// Removes station assignment of the chain position, returns false if there was none.
+pub async fn unassign_chain_position(
+    db: &mut SqliteConnection,
+    author: &ChangeAuthor,
+    chain: &str,
+    pos: i32,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let Some(before) = ChainStation::get_assignment(&mut tx, chain, pos).await? else {
+        return Ok(false);
+    };
+    query("DELETE FROM hl_stations WHERE chain_hash = $1 AND pos = $2;")
+        .bind(chain)
+        .bind(pos)
+        .execute(&mut *tx)
+        .await?;
+    Change::record_assignment(
+        &mut tx,
+        author,
+        ChangeAction::Unassign,
+        chain,
+        pos,
+        Some(&before),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(true)
+}
@@ -0,0 +1,122 @@
+use inv_zastavky_core::error::Error;
+use rocket::http::Status;
+use rocket::response::Responder;
+use rocket::serde::Serialize;
+use rocket::serde::json::Json;
+use rocket::{Request, Response, catch, error, response};
+
+/// Error response of the API, serialized as JSON with a stable error code.
+#[derive(Debug)]
+pub struct ApiError(Error);
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ErrorBody {
+    error: &'static str,
+    message: String,
+}
+
+impl<E: Into<Error>> From<E> for ApiError {
+    fn from(err: E) -> Self {
+        ApiError(err.into())
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = match &self.0 {
+            Error::NotFound { .. } => Status::NotFound,
+            Error::Conflict { .. } => Status::Conflict,
+            Error::Validation { .. } => Status::UnprocessableEntity,
+            Error::Unauthorized { .. } => Status::Unauthorized,
+            Error::Storage(err) => {
+                error!("Database error: {}", err);
+                Status::InternalServerError
+            }
+        };
+        // database errors may expose queries and paths, they are only logged
+        let message = match &self.0 {
+            Error::Storage(_) => "internal storage error".to_string(),
+            err => err.to_string(),
+        };
+        let body = Json(ErrorBody {
+            error: self.0.code(),
+            message,
+        });
+        Response::build_from(body.respond_to(request)?)
+            .status(status)
+            .ok()
+    }
+}
+
+fn error_body(error: &'static str, message: &str) -> Json<ErrorBody> {
+    Json(ErrorBody {
+        error,
+        message: message.to_string(),
+    })
+}
+
+#[catch(400)]
+pub fn bad_request() -> Json<ErrorBody> {
+    error_body("bad_request", "malformed request")
+}
+
+#[catch(401)]
+pub fn unauthorized() -> Json<ErrorBody> {
+    error_body("unauthorized", "missing or invalid credentials")
+}
+
+#[catch(404)]
+pub fn not_found() -> Json<ErrorBody> {
+    error_body("route_not_found", "no such endpoint")
+}
+
+#[catch(422)]
+pub fn unprocessable_entity() -> Json<ErrorBody> {
+    error_body("invalid_parameters", "missing or malformed parameters")
+}
+
+#[catch(500)]
+pub fn internal_error() -> Json<ErrorBody> {
+    error_body("internal", "internal server error")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use rocket::{get, routes};
+    use rocket_db_pools::sqlx;
+
+    #[get("/storage")]
+    fn storage() -> Result<(), ApiError> {
+        Err(sqlx::Error::Protocol("no such table: el_station_pos".to_string()).into())
+    }
+
+    #[get("/conflict")]
+    fn conflict() -> Result<(), ApiError> {
+        Err(Error::conflict("station_assigned", "station 7 is assigned").into())
+    }
+
+    #[test]
+    fn storage_errors_hide_database_details() {
+        let rocket = rocket::build().mount("/", routes![storage, conflict]);
+        let client = Client::untracked(rocket).unwrap();
+
+        let response = client.get("/storage").dispatch();
+        assert_eq!(response.status(), Status::InternalServerError);
+        let body = response.into_string().unwrap();
+        assert!(!body.contains("el_station_pos"));
+        assert_eq!(
+            body,
+            r#"{"error":"storage","message":"internal storage error"}"#
+        );
+
+        let response = client.get("/conflict").dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+        assert_eq!(
+            response.into_string().unwrap(),
+            r#"{"error":"station_assigned","message":"station 7 is assigned"}"#
+        );
+    }
+}
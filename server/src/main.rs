@@ -1,9 +1,11 @@
+use crate::api_tiles::TileCache;
 use crate::config::ServerConfig;
 use crate::database::MainDB;
-use rocket::fairing::{Fairing, Info, Kind};
+use rocket::fairing::{AdHoc, Fairing, Info, Kind};
 use rocket::fs::FileServer;
 use rocket::http::Header;
-use rocket::{Request, Response, launch, routes};
+use rocket::{Build, Request, Response, Rocket, catchers, launch, routes};
+use inv_zastavky_core::migrations;
 use rocket_db_pools::Database;
 
 mod api_base;
@@ -11,15 +13,34 @@ mod api_chain;
 mod api_other;
 mod api_stations;
 mod api_suggest;
+mod api_tiles;
+mod api_v2;
+mod auth;
 mod config;
 mod database;
+mod error;
+mod mvt;
 
 #[launch]
 fn rocket() -> _ {
-    rocket::build()
-        .attach(CORS)
+    let rocket = rocket::build();
+    let allowed_origins = rocket
+        .figment()
+        .extract_inner("cors_origins")
+        .unwrap_or_default();
+    let chain_search = rocket
+        .figment()
+        .extract_inner("chain_search")
+        .unwrap_or_default();
+    let delete_policy = rocket
+        .figment()
+        .extract_inner("station_delete_policy")
+        .unwrap_or_default();
+    rocket
+        .attach(Cors { allowed_origins })
         .attach(MainDB::init())
-        .manage(ServerConfig::new())
+        .attach(AdHoc::try_on_ignite("Database migrations", run_migrations))
+        .manage(ServerConfig::new(chain_search, delete_policy))
         .mount(
             "/api",
             routes![
@@ -42,12 +63,73 @@ fn rocket() -> _ {
                 api_suggest::suggest_cities,
             ],
         )
+        .mount(
+            "/api/v2",
+            routes![
+                api_v2::create_station,
+                api_v2::get_station,
+                api_v2::patch_station,
+                api_v2::delete_station,
+                api_v2::merge_station,
+                api_v2::split_station,
+                api_v2::get_station_names,
+                api_v2::add_station_name,
+                api_v2::delete_station_name,
+                api_v2::get_position,
+                api_v2::assign_position,
+                api_v2::unassign_position,
+                api_v2::station_history,
+                api_v2::session_changes,
+                api_v2::revert_change,
+                api_v2::revert_session,
+                api_v2::search_names,
+                api_v2::preflight,
+                auth::create_token,
+                auth::create_basic_token,
+                auth::me,
+            ],
+        )
+        .manage(TileCache::default())
+        .mount("/tiles", routes![api_tiles::get_tile])
+        .register(
+            "/api",
+            catchers![
+                error::bad_request,
+                error::unauthorized,
+                error::not_found,
+                error::unprocessable_entity,
+                error::internal_error,
+            ],
+        )
         .mount("/", FileServer::from("web_ui/dist"))
 }
 
-struct CORS;
+async fn run_migrations(rocket: Rocket<Build>) -> Result<Rocket<Build>, Rocket<Build>> {
+    let Some(db) = MainDB::fetch(&rocket) else {
+        return Err(rocket);
+    };
+    let result = match db.acquire().await {
+        Ok(mut conn) => migrations::migrate(&mut conn).await,
+        Err(err) => Err(err.into()),
+    };
+    match result {
+        Ok(_) => Ok(rocket),
+        Err(err) => {
+            rocket::error!("Failed to migrate database: {}", err);
+            Err(rocket)
+        }
+    }
+}
+
+/// Allows cross-origin requests from configured origins, e.g. the web UI development server.
+///
+/// Credentials are sent in the `Authorization` header, so cookies are never allowed.
+struct Cors {
+    allowed_origins: Vec<String>,
+}
+
 #[rocket::async_trait]
-impl Fairing for CORS {
+impl Fairing for Cors {
     fn info(&self) -> Info {
         Info {
             name: "Add CORS headers to responses",
@@ -55,13 +137,22 @@ impl Fairing for CORS {
         }
     }
 
-    async fn on_response<'r>(&self, _request: &'r Request<'_>, response: &mut Response<'r>) {
-        response.set_header(Header::new("Access-Control-Allow-Origin", "*"));
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Some(origin) = request.headers().get_one("Origin") else {
+            return;
+        };
+        if !self.allowed_origins.iter().any(|allowed| allowed == origin) {
+            return;
+        }
+        response.set_header(Header::new("Access-Control-Allow-Origin", origin.to_string()));
+        response.set_header(Header::new("Vary", "Origin"));
         response.set_header(Header::new(
             "Access-Control-Allow-Methods",
-            "POST, GET, PATCH, OPTIONS",
+            "POST, GET, PUT, PATCH, DELETE, OPTIONS",
+        ));
+        response.set_header(Header::new(
+            "Access-Control-Allow-Headers",
+            "Authorization, Content-Type, X-Edit-Session",
         ));
-        response.set_header(Header::new("Access-Control-Allow-Headers", "*"));
-        response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
     }
 }
@@ -0,0 +1,371 @@
+//! Encoder of point layers in Mapbox Vector Tiles, https://github.com/mapbox/vector-tile-spec.
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+/// Size of a tile in tile coordinates.
+const EXTENT: u32 = 4096;
+
+/// Points this far outside of the tile are included, so symbols are not cut at tile edges.
+const BUFFER: u32 = 64;
+
+/// Highest zoom level of served tiles.
+pub const MAX_ZOOM: u8 = 22;
+
+const WIRE_VARINT: u8 = 0;
+const WIRE_LENGTH: u8 = 2;
+
+const GEOM_POINT: u64 = 1;
+const COMMAND_MOVE_TO: u32 = 1;
+
+/// Tile of the Web Mercator tile grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TileId {
+    z: u8,
+    x: u32,
+    y: u32,
+}
+
+impl TileId {
+    /// Returns `None` for tiles outside of the grid.
+    pub fn new(z: u8, x: u32, y: u32) -> Option<Self> {
+        if z > MAX_ZOOM || u64::from(x) >= 1 << z || u64::from(y) >= 1 << z {
+            return None;
+        }
+        Some(Self { z, x, y })
+    }
+
+    pub fn zoom(&self) -> u8 {
+        self.z
+    }
+
+    fn tiles(&self) -> f64 {
+        f64::powi(2.0, i32::from(self.z))
+    }
+
+    /// Bounds of the tile with buffer as `([lat_south, lat_north], [lon_west, lon_east])`.
+    pub fn bounds(&self) -> ([f64; 2], [f64; 2]) {
+        let buffer = f64::from(BUFFER) / f64::from(EXTENT);
+        let lon = |x: f64| x / self.tiles() * 360.0 - 180.0;
+        let lat = |y: f64| f64::atan(f64::sinh(PI * (1.0 - 2.0 * y / self.tiles()))).to_degrees();
+        let x = f64::from(self.x);
+        let y = f64::from(self.y);
+        (
+            [lat(y + 1.0 + buffer), lat(y - buffer)],
+            [lon(x - buffer), lon(x + 1.0 + buffer)],
+        )
+    }
+
+    /// Position of the point in tile coordinates.
+    fn project(&self, lat: f64, lon: f64) -> (i64, i64) {
+        let lat = lat.clamp(-85.051_128_78, 85.051_128_78).to_radians();
+        let x = (lon + 180.0) / 360.0 * self.tiles();
+        let y = (1.0 - f64::ln(f64::tan(lat) + 1.0 / f64::cos(lat)) / PI) / 2.0 * self.tiles();
+        let extent = f64::from(EXTENT);
+        (
+            ((x - f64::from(self.x)) * extent).round() as i64,
+            ((y - f64::from(self.y)) * extent).round() as i64,
+        )
+    }
+}
+
+/// Attribute value of a feature.
+pub enum Value<'a> {
+    String(&'a str),
+    Int(i64),
+}
+
+impl Value<'_> {
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match self {
+            Value::String(value) => write_bytes(&mut buf, 1, value.as_bytes()),
+            Value::Int(value) => {
+                write_key(&mut buf, 6, WIRE_VARINT);
+                write_varint(&mut buf, zigzag(*value));
+            }
+        }
+        buf
+    }
+}
+
+/// Layer of point features, keys and values of attributes are shared by its features.
+pub struct Layer {
+    tile: TileId,
+    name: String,
+    features: Vec<Vec<u8>>,
+    keys: Vec<String>,
+    key_index: HashMap<String, u32>,
+    values: Vec<Vec<u8>>,
+    value_index: HashMap<Vec<u8>, u32>,
+}
+
+impl Layer {
+    pub fn new(tile: TileId, name: &str) -> Self {
+        Self {
+            tile,
+            name: name.to_string(),
+            features: Vec::new(),
+            keys: Vec::new(),
+            key_index: HashMap::new(),
+            values: Vec::new(),
+            value_index: HashMap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    pub fn add_point(&mut self, id: Option<u64>, lat: f64, lon: f64, attributes: &[(&str, Value)]) {
+        let mut tags = Vec::new();
+        for (key, value) in attributes {
+            tags.push(self.key(key));
+            tags.push(self.value(value));
+        }
+        let (x, y) = self.tile.project(lat, lon);
+        let mut feature = Vec::new();
+        if let Some(id) = id {
+            write_key(&mut feature, 1, WIRE_VARINT);
+            write_varint(&mut feature, id);
+        }
+        write_packed(&mut feature, 2, tags.iter().map(|tag| u64::from(*tag)));
+        write_key(&mut feature, 3, WIRE_VARINT);
+        write_varint(&mut feature, GEOM_POINT);
+        write_packed(
+            &mut feature,
+            4,
+            [u64::from(COMMAND_MOVE_TO | (1 << 3)), zigzag(x), zigzag(y)],
+        );
+        self.features.push(feature);
+    }
+
+    fn key(&mut self, key: &str) -> u32 {
+        if let Some(index) = self.key_index.get(key) {
+            return *index;
+        }
+        let index = self.keys.len() as u32;
+        self.keys.push(key.to_string());
+        self.key_index.insert(key.to_string(), index);
+        index
+    }
+
+    fn value(&mut self, value: &Value) -> u32 {
+        let encoded = value.encode();
+        if let Some(index) = self.value_index.get(&encoded) {
+            return *index;
+        }
+        let index = self.values.len() as u32;
+        self.values.push(encoded.clone());
+        self.value_index.insert(encoded, index);
+        index
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_key(&mut buf, 15, WIRE_VARINT);
+        write_varint(&mut buf, 2);
+        write_bytes(&mut buf, 1, self.name.as_bytes());
+        for feature in &self.features {
+            write_bytes(&mut buf, 2, feature);
+        }
+        for key in &self.keys {
+            write_bytes(&mut buf, 3, key.as_bytes());
+        }
+        for value in &self.values {
+            write_bytes(&mut buf, 4, value);
+        }
+        write_key(&mut buf, 5, WIRE_VARINT);
+        write_varint(&mut buf, u64::from(EXTENT));
+        buf
+    }
+}
+
+/// Encodes layers into a tile, empty layers are left out.
+pub fn encode(layers: &[Layer]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for layer in layers.iter().filter(|layer| !layer.is_empty()) {
+        write_bytes(&mut buf, 3, &layer.encode());
+    }
+    buf
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn write_key(buf: &mut Vec<u8>, field: u32, wire_type: u8) {
+    write_varint(buf, (u64::from(field) << 3) | u64::from(wire_type));
+}
+
+fn write_bytes(buf: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    write_key(buf, field, WIRE_LENGTH);
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn write_packed(buf: &mut Vec<u8>, field: u32, values: impl IntoIterator<Item = u64>) {
+    let mut packed = Vec::new();
+    for value in values {
+        write_varint(&mut packed, value);
+    }
+    write_bytes(buf, field, &packed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Field<'a> {
+        Varint(u64),
+        Bytes(&'a [u8]),
+    }
+
+    fn read_varint(buf: &[u8], pos: &mut usize) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = buf[*pos];
+            *pos += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte < 0x80 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    /// Decodes fields of a protobuf message in their order.
+    fn fields(buf: &[u8]) -> Vec<(u64, Field<'_>)> {
+        let mut fields = Vec::new();
+        let mut pos = 0;
+        while pos < buf.len() {
+            let key = read_varint(buf, &mut pos);
+            let field = match key & 7 {
+                0 => Field::Varint(read_varint(buf, &mut pos)),
+                2 => {
+                    let len = read_varint(buf, &mut pos) as usize;
+                    pos += len;
+                    Field::Bytes(&buf[pos - len..pos])
+                }
+                wire_type => panic!("unexpected wire type {}", wire_type),
+            };
+            fields.push((key >> 3, field));
+        }
+        fields
+    }
+
+    fn packed(buf: &[u8]) -> Vec<u64> {
+        let mut values = Vec::new();
+        let mut pos = 0;
+        while pos < buf.len() {
+            values.push(read_varint(buf, &mut pos));
+        }
+        values
+    }
+
+    fn bytes<'a>(fields: &[(u64, Field<'a>)], number: u64) -> Vec<&'a [u8]> {
+        fields
+            .iter()
+            .filter_map(|(field, value)| match value {
+                Field::Bytes(bytes) if *field == number => Some(*bytes),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn zigzag_maps_signed_to_unsigned() {
+        assert_eq!(zigzag(0), 0);
+        assert_eq!(zigzag(-1), 1);
+        assert_eq!(zigzag(1), 2);
+        assert_eq!(zigzag(-2), 3);
+        assert_eq!(zigzag(2048), 4096);
+        assert_eq!(zigzag(i64::MIN), u64::MAX);
+    }
+
+    #[test]
+    fn varints_use_seven_bits_per_byte() {
+        let mut buf = Vec::new();
+        write_varint(&mut buf, 1);
+        write_varint(&mut buf, 300);
+        assert_eq!(buf, [0x01, 0xac, 0x02]);
+    }
+
+    #[test]
+    fn point_layer_is_decodable() {
+        let tile = TileId::new(0, 0, 0).unwrap();
+        let mut layer = Layer::new(tile, "stations");
+        layer.add_point(
+            Some(7),
+            0.0,
+            0.0,
+            &[
+                ("name", Value::String("Praha")),
+                ("stop_id", Value::Int(-3)),
+            ],
+        );
+        layer.add_point(None, 0.0, -180.0, &[("name", Value::String("Praha"))]);
+        let data = encode(&[layer, Layer::new(tile, "empty")]);
+
+        let tile_fields = fields(&data);
+        assert_eq!(tile_fields.len(), 1, "empty layers are left out");
+        let layer = bytes(&tile_fields, 3)[0];
+        let layer_fields = fields(layer);
+        assert!(layer_fields.contains(&(15, Field::Varint(2))));
+        assert_eq!(bytes(&layer_fields, 1), [b"stations"]);
+        assert!(layer_fields.contains(&(5, Field::Varint(4096))));
+        assert_eq!(bytes(&layer_fields, 3), [b"name".as_slice(), b"stop_id"]);
+        let values: Vec<_> = bytes(&layer_fields, 4).into_iter().map(fields).collect();
+        assert_eq!(
+            values,
+            [
+                vec![(1, Field::Bytes(b"Praha"))],
+                vec![(6, Field::Varint(zigzag(-3)))]
+            ]
+        );
+
+        let features: Vec<_> = bytes(&layer_fields, 2).into_iter().map(fields).collect();
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[0][0], (1, Field::Varint(7)));
+        assert_eq!(packed(bytes(&features[0], 2)[0]), [0, 0, 1, 1]);
+        assert!(features[0].contains(&(3, Field::Varint(GEOM_POINT))));
+        // MoveTo with one point at the tile center
+        assert_eq!(packed(bytes(&features[0], 4)[0]), [9, 4096, 4096]);
+        assert!(!features[1].iter().any(|(field, _)| *field == 1));
+        assert_eq!(packed(bytes(&features[1], 2)[0]), [0, 0]);
+        assert_eq!(packed(bytes(&features[1], 4)[0]), [9, 0, 4096]);
+    }
+
+    #[test]
+    fn points_are_relative_to_the_tile() {
+        let tile = TileId::new(1, 1, 0).unwrap();
+        assert_eq!(tile.project(0.0, 0.0), (0, 4096));
+        assert_eq!(tile.project(85.051_128_78, 180.0), (4096, 0));
+        let (x, _) = tile.project(0.0, -1.0);
+        assert!(x < 0);
+        let mut layer = Layer::new(tile, "stations");
+        layer.add_point(None, 0.0, -1.0, &[]);
+        let data = encode(&[layer]);
+        let layer_fields = fields(bytes(&fields(&data), 3)[0]);
+        let feature = fields(bytes(&layer_fields, 2)[0]);
+        assert_eq!(packed(bytes(&feature, 4)[0]), [9, zigzag(x), zigzag(4096)]);
+    }
+
+    #[test]
+    fn tiles_outside_of_the_grid_are_rejected() {
+        assert!(TileId::new(0, 1, 0).is_none());
+        assert!(TileId::new(2, 3, 4).is_none());
+        assert!(TileId::new(MAX_ZOOM + 1, 0, 0).is_none());
+        assert!(TileId::new(MAX_ZOOM, (1 << MAX_ZOOM) - 1, 0).is_some());
+    }
+}
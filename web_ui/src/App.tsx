@@ -3,7 +3,7 @@ import "./App.css"
 import {MapContainer, TileLayer} from "react-leaflet";
 import {BBox, Mode} from "./model/model.ts";
 import {MapRef} from "react-leaflet/MapContainer";
-import {createStation, getCityRemap, getStations, getStats} from "./data/interact.ts";
+import {createStation, getCityRemap, getStations, getStats, login} from "./data/interact.ts";
 import {StationMarker} from "./components/browse/StationMarker.tsx";
 import {EditableStationMarker} from "./components/editor/EditableStationMarker.tsx";
 import {AddStationDialog} from "./components/editor/AddStationDialog.tsx";
@@ -146,6 +146,15 @@ function App() {
                             alert(JSON.stringify(stats, null, 2))
                         }}>Get stats
                         </button>
+                        <button onClick={async () => {
+                            const username = prompt("Username")
+                            const password = username && prompt("Password")
+                            if (!username || !password) return
+                            login(username, password)
+                                .then(user => alert(`Logged in as ${user.username}`))
+                                .catch(error => alert(error.message))
+                        }}>Log in
+                        </button>
                     </div>
                 </MapContainer>
             </AppDispatchContext.Provider>
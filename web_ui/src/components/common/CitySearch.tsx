@@ -17,7 +17,7 @@ export function CitySearch() {
             setCities([])
             return
         }
-        setCities(await searchBaseCity(query + "%"))
+        setCities(await searchBaseCity(query))
     }
 
     useEffect(() => {
@@ -26,7 +26,7 @@ export function CitySearch() {
         if (query.length == 0) {
             setCities([])
         } else {
-            searchBaseCity(query + "%")
+            searchBaseCity(query)
                 .then(cities => {
                     if (cancelFence) return
                     setCities(cities.slice(0, 20))
@@ -17,7 +17,7 @@ export function StationSearch() {
             setStations([])
             return
         }
-        setStations((await searchStations("%" + query + "%")).slice(0, 10))
+        setStations((await searchStations(query)).slice(0, 10))
     }
 
     useEffect(() => {
@@ -26,7 +26,7 @@ export function StationSearch() {
         if (query.length == 0) {
             setStations([])
         } else {
-            searchStations("%" + query + "%")
+            searchStations(query)
                 .then(stations => {
                     if (cancelFence) return
                     setStations(stations.slice(0, 10))
@@ -218,7 +218,7 @@ export function LocateView() {
                 </button>
             </div>
             <div className="StationsSuggestion__wrapper map-overlay">
-                {state.stationsSuggestions.sort((left, right) => left.len - right.len)
+                {state.stationsSuggestions.sort((left, right) => left.cost - right.cost)
                     .slice(0, 20)
                     .map((suggestion, idx) => (
                         <button key={suggestion.len + " " + idx}
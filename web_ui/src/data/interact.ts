@@ -1,4 +1,5 @@
 import {
+    ApiError,
     BaseCity,
     BaseStation,
     BBox,
@@ -6,10 +7,41 @@ import {
     ChainStation,
     ChainStationsSuggestion, CityRemap,
     Station,
-    Stats
+    Stats,
+    User
 } from "../model/model.ts";
 import {config} from "../config.ts";
 
+async function rejectResponse(response: Response): Promise<never> {
+    const body = await response.json().catch(() => ({error: "unknown", message: response.statusText}));
+    const error: ApiError = {status: response.status, error: body.error, message: body.message};
+    return Promise.reject(error);
+}
+
+const TOKEN_KEY = "api_token";
+// changes made since the page was loaded can be reverted together
+const EDIT_SESSION = crypto.randomUUID();
+
+async function sendJson(method: string, path: string, body?: object): Promise<Response> {
+    const headers: Record<string, string> = {};
+    const token = localStorage.getItem(TOKEN_KEY);
+    if (token) headers["Authorization"] = `Bearer ${token}`;
+    headers["X-Edit-Session"] = EDIT_SESSION;
+    if (body !== undefined) headers["Content-Type"] = "application/json";
+    return await fetch(config.api_endpoint + "/v2" + path, {
+        method,
+        headers,
+        body: body === undefined ? undefined : JSON.stringify(body),
+    });
+}
+
+export async function login(username: string, password: string): Promise<User> {
+    const response = await sendJson("POST", "/tokens", {username, password});
+    const data = await (response.ok ? response.json() : rejectResponse(response));
+    localStorage.setItem(TOKEN_KEY, data.token);
+    return data.user as User;
+}
+
 export async function getStations(bbox: BBox): Promise<Station[]> {
     const url = new URL(config.api_endpoint + "/el_stations_bbox");
     url.searchParams.append("lat_from", String(bbox.lat_from))
@@ -17,52 +49,36 @@ export async function getStations(bbox: BBox): Promise<Station[]> {
     url.searchParams.append("lon_from", String(bbox.lon_from))
     url.searchParams.append("lon_to", String(bbox.lon_to))
     return await fetch(url)
-        .then(response => response.ok ? response.json() : Promise.reject())
+        .then(response => response.ok ? response.json() : rejectResponse(response))
         .then(data => data as Station[])
 }
 
 export async function createStation(lat: number, lon: number, name: string): Promise<Station> {
-    const url = new URL(config.api_endpoint + "/el_add_station");
-    url.searchParams.append("lat", String(lat))
-    url.searchParams.append("lon", String(lon))
-    url.searchParams.append("name", name)
-    const response = await fetch(url);
-    const data = await (response.ok ? response.json() : Promise.reject());
+    const response = await sendJson("POST", "/stations", {lat, lon, name});
+    const data = await (response.ok ? response.json() : rejectResponse(response));
     return data as Station;
 }
 
 export async function rmStation(stop_id: number): Promise<void> {
-    const url = new URL(config.api_endpoint + "/el_rm_station");
-    url.searchParams.append("stop_id", String(stop_id))
-    const response = await fetch(url);
-    return await (response.ok ? Promise.resolve() : Promise.reject());
+    const response = await sendJson("DELETE", `/stations/${stop_id}`);
+    return await (response.ok ? Promise.resolve() : rejectResponse(response));
 }
 
 export async function moveStation(stop_id: number, lat: number, lon: number): Promise<Station> {
-    const url = new URL(config.api_endpoint + "/el_move_station");
-    url.searchParams.append("stop_id", String(stop_id))
-    url.searchParams.append("lat", String(lat))
-    url.searchParams.append("lon", String(lon))
-    const response = await fetch(url);
-    const data = await (response.ok ? response.json() : Promise.reject());
+    const response = await sendJson("PATCH", `/stations/${stop_id}`, {lat, lon});
+    const data = await (response.ok ? response.json() : rejectResponse(response));
     return data as Station;
 }
 
 export async function addStationName(stop_id: number, name: string): Promise<Station> {
-    const url = new URL(config.api_endpoint + "/el_add_station_name");
-    url.searchParams.append("stop_id", String(stop_id))
-    url.searchParams.append("name", name)
-    const response = await fetch(url);
-    const data = await (response.ok ? response.json() : Promise.reject());
+    const response = await sendJson("POST", `/stations/${stop_id}/names`, {name});
+    const data = await (response.ok ? response.json() : rejectResponse(response));
     return data as Station;
 }
 
 export async function rmStationName(stop_id: number, name: string): Promise<Station> {
-    const url = new URL(config.api_endpoint + "/el_rm_station_name");
-    url.searchParams.append("stop_id", String(stop_id))
-    url.searchParams.append("name", name)
-    const response = await fetch(url);
-    const data = await (response.ok ? response.json() : Promise.reject());
+    const response = await sendJson("DELETE", `/stations/${stop_id}/names/${encodeURIComponent(name)}`);
+    const data = await (response.ok ? response.json() : rejectResponse(response));
     return data as Station;
 }
 
@@ -71,7 +87,7 @@ export async function getChainStations(offset: number, limit: number): Promise<C
     url.searchParams.append("offset", String(offset))
     url.searchParams.append("limit", String(limit))
     const response = await fetch(url);
-    const data = await (response.ok ? response.json() : Promise.reject());
+    const data = await (response.ok ? response.json() : rejectResponse(response));
     return data as ChainStation[];
 }
 
@@ -79,30 +95,21 @@ export async function getChainStationsByHash(chain_hash: string): Promise<ChainS
     const url = new URL(config.api_endpoint + "/sl_chain");
     url.searchParams.append("chain_hash", chain_hash)
     const response = await fetch(url);
-    const data = await (response.ok ? response.json() : Promise.reject());
+    const data = await (response.ok ? response.json() : rejectResponse(response));
     return data as ChainStation[];
 }
 
 export async function locateByLoc(chainStation: ChainStation, lat: number, lon: number): Promise<Station> {
-    const url = new URL(config.api_endpoint + "/locate_by_loc");
-    url.searchParams.append("chain_hash", String(chainStation.chain_hash))
-    url.searchParams.append("name", String(chainStation.name))
-    url.searchParams.append("pos", String(chainStation.pos))
-    url.searchParams.append("lat", String(lat))
-    url.searchParams.append("lon", String(lon))
-    const response = await fetch(url);
-    const data = await (response.ok ? response.json() : Promise.reject());
+    const path = `/chains/${encodeURIComponent(chainStation.chain_hash)}/positions/${chainStation.pos}`;
+    const response = await sendJson("PUT", path, {lat, lon});
+    const data = await (response.ok ? response.json() : rejectResponse(response));
     return data as Station;
 }
 
 export async function locateById(chainStation: ChainStation, stop_id: number): Promise<Station> {
-    const url = new URL(config.api_endpoint + "/locate_by_id");
-    url.searchParams.append("chain_hash", String(chainStation.chain_hash))
-    url.searchParams.append("name", String(chainStation.name))
-    url.searchParams.append("pos", String(chainStation.pos))
-    url.searchParams.append("stop_id", String(stop_id))
-    const response = await fetch(url);
-    const data = await (response.ok ? response.json() : Promise.reject());
+    const path = `/chains/${encodeURIComponent(chainStation.chain_hash)}/positions/${chainStation.pos}`;
+    const response = await sendJson("PUT", path, {stop_id});
+    const data = await (response.ok ? response.json() : rejectResponse(response));
     return data as Station;
 }
 
@@ -113,7 +120,7 @@ export async function getBaseStations(bbox: BBox): Promise<BaseStation[]> {
     url.searchParams.append("lon_from", String(bbox.lon_from))
     url.searchParams.append("lon_to", String(bbox.lon_to))
     return await fetch(url)
-        .then(response => response.ok ? response.json() : Promise.reject())
+        .then(response => response.ok ? response.json() : rejectResponse(response))
         .then(data => data as BaseStation[])
 }
 
@@ -121,7 +128,7 @@ export async function searchBaseCity(query: string): Promise<BaseCity[]> {
     const url = new URL(config.api_endpoint + "/sl_base_city");
     url.searchParams.append("query", query)
     return await fetch(url)
-        .then(response => response.ok ? response.json() : Promise.reject())
+        .then(response => response.ok ? response.json() : rejectResponse(response))
         .then(data => data as BaseCity[])
 }
 
@@ -129,14 +136,14 @@ export async function searchStations(query: string): Promise<Station[]> {
     const url = new URL(config.api_endpoint + "/el_stations_search");
     url.searchParams.append("query", query)
     return await fetch(url)
-        .then(response => response.ok ? response.json() : Promise.reject())
+        .then(response => response.ok ? response.json() : rejectResponse(response))
         .then(data => data as Station[])
 }
 
 export async function getStats(): Promise<Stats> {
     const url = new URL(config.api_endpoint + "/other_stats");
     return await fetch(url)
-        .then(response => response.ok ? response.json() : Promise.reject())
+        .then(response => response.ok ? response.json() : rejectResponse(response))
         .then(data => data as Stats)
 }
 
@@ -144,7 +151,7 @@ export async function suggestChainStations(chain_hash: string): Promise<ChainSta
     const url = new URL(config.api_endpoint + "/suggest_stations");
     url.searchParams.append("chain_hash", chain_hash)
     return await fetch(url)
-        .then(response => response.ok ? response.json() : Promise.reject())
+        .then(response => response.ok ? response.json() : rejectResponse(response))
         .then(data => data as ChainStationsSuggestion[])
 }
 
@@ -152,13 +159,13 @@ export async function suggestChainCities(chain_hash: string): Promise<ChainCitie
     const url = new URL(config.api_endpoint + "/suggest_cities");
     url.searchParams.append("chain_hash", chain_hash)
     return await fetch(url)
-        .then(response => response.ok ? response.json() : Promise.reject())
+        .then(response => response.ok ? response.json() : rejectResponse(response))
         .then(data => data as ChainCitiesSuggestion[])
 }
 
 export async function getCityRemap(): Promise<CityRemap> {
     const url = new URL(config.api_endpoint + "/other_city_remap");
     return await fetch(url)
-        .then(response => response.ok ? response.json() : Promise.reject())
+        .then(response => response.ok ? response.json() : rejectResponse(response))
         .then(data => data as CityRemap)
 }
@@ -46,7 +46,9 @@ export interface ChainStation {
 export interface BaseStation {
     lat: number,
     lon: number,
-    name: string
+    name: string,
+    source: "geo_json" | "netex" | "gtfs",
+    source_id: string | null
 }
 
 export interface BaseCity {
@@ -62,6 +64,7 @@ export interface Stats {
 
 export interface ChainStationsSuggestion {
     len: number,
+    cost: number,
     chain_hash: string,
     path: number[][],
 }
@@ -70,6 +73,19 @@ export interface ChainCitiesSuggestion {
     len: number,
     chain_hash: string,
     path: number[][],
+    matches: ("matched" | "missing")[],
+    positions: (number | null)[],
 }
 
 export type CityRemap = { [key: string]: string }
+
+export interface User {
+    user_id: number,
+    username: string,
+}
+
+export interface ApiError {
+    status: number,
+    error: string,
+    message: string,
+}